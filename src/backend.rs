@@ -1,5 +1,16 @@
-use crate::db::{self, Db, Snippet};
+use crate::config::AuthScheme;
+use crate::db::{self, Db, Snippet, SnippetMeta};
 use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Once};
+use std::time::Duration;
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Extra attempts made after a transient remote failure.
+pub const DEFAULT_RETRIES: u32 = 2;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(2);
 
 #[derive(Debug)]
 pub enum BackendError {
@@ -7,6 +18,8 @@ pub enum BackendError {
     Unauthorized(String),
     Network(String),
     Database(String),
+    /// The server refused the snippet, e.g. because it is too large.
+    Rejected(String),
 }
 
 impl fmt::Display for BackendError {
@@ -16,6 +29,7 @@ impl fmt::Display for BackendError {
             BackendError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             BackendError::Network(msg) => write!(f, "Network error: {}", msg),
             BackendError::Database(msg) => write!(f, "Database error: {}", msg),
+            BackendError::Rejected(msg) => write!(f, "Rejected by server: {}", msg),
         }
     }
 }
@@ -28,138 +42,519 @@ impl From<db::DbError> for BackendError {
     }
 }
 
-pub enum Backend {
-    Local {
-        db: Db,
-    },
-    Remote {
-        base_url: String,
-        api_key: Option<String>,
-        client: reqwest::blocking::Client,
-    },
+impl From<reqwest::Error> for BackendError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            BackendError::Network("request timed out".into())
+        } else {
+            BackendError::Network(e.to_string())
+        }
+    }
+}
+
+/// Where the TUI and CLI read and write snippets. Implemented by the local
+/// database and the remote API, and by anything else that can stand in for
+/// them, such as a cache or an in-memory fake.
+pub trait SnippetStore: Send + Sync {
+    fn list_snippets(&self) -> Result<Vec<Snippet>, BackendError>;
+
+    /// Lists every snippet without its content, which is much smaller than
+    /// [`SnippetStore::list_snippets`] for a large store.
+    fn list_snippet_metas(&self) -> Result<Vec<SnippetMeta>, BackendError> {
+        Ok(self.list_snippets()?.iter().map(Snippet::meta).collect())
+    }
+
+    fn count_snippets(&self) -> Result<usize, BackendError>;
+
+    /// Checks that the store can be reached at all.
+    fn health_check(&self) -> Result<(), BackendError>;
+
+    fn get_snippet(&self, short_id: &str) -> Result<Option<Snippet>, BackendError>;
+
+    fn create_snippet(
+        &self,
+        name: &str,
+        content: &str,
+        language: Option<&str>,
+        tags: &[String],
+    ) -> Result<Snippet, BackendError>;
+
+    /// Like [`SnippetStore::create_snippet`], also returning the URL the store
+    /// says the new snippet can be viewed at, when it gives one.
+    fn create_snippet_with_link(
+        &self,
+        name: &str,
+        content: &str,
+        language: Option<&str>,
+        tags: &[String],
+    ) -> Result<(Snippet, Option<String>), BackendError> {
+        Ok((self.create_snippet(name, content, language, tags)?, None))
+    }
+
+    /// Checks whether [`SnippetStore::create_snippet`] would accept this
+    /// snippet without creating it. Stores without limits accept anything.
+    fn validate_snippet(&self, _name: &str, _content: &str) -> Result<(), BackendError> {
+        Ok(())
+    }
+
+    fn update_snippet(
+        &self,
+        short_id: &str,
+        name: &str,
+        content: &str,
+        language: Option<&str>,
+        tags: &[String],
+    ) -> Result<Option<Snippet>, BackendError>;
+
+    /// Changes only the name, without resending the content.
+    fn rename_snippet(&self, short_id: &str, name: &str) -> Result<Option<Snippet>, BackendError>;
+
+    fn delete_snippet(&self, short_id: &str) -> Result<bool, BackendError>;
+}
+
+/// A store shared between the TUI and its background content loader.
+pub type SharedStore = Arc<dyn SnippetStore>;
+
+/// Snippets in a SQLite database on this machine.
+#[derive(Clone)]
+pub struct LocalBackend {
+    db: Db,
+}
+
+/// Snippets on a sipp server, reached through its JSON API.
+#[derive(Clone)]
+pub struct RemoteBackend {
+    base_url: String,
+    api_key: Option<String>,
+    auth_scheme: AuthScheme,
+    client: reqwest::blocking::Client,
+    retries: u32,
+}
+
+fn with_auth(
+    req: reqwest::blocking::RequestBuilder,
+    api_key: &Option<String>,
+    auth_scheme: AuthScheme,
+) -> reqwest::blocking::RequestBuilder {
+    match (api_key, auth_scheme) {
+        (Some(key), AuthScheme::ApiKey) => req.header("x-api-key", key),
+        (Some(key), AuthScheme::Bearer) => req.bearer_auth(key),
+        (None, _) => req,
+    }
+}
+
+/// Sends the request from `build`, retrying up to `retries` times with
+/// exponential backoff. Connection failures are always retried since the
+/// request never reached the server; timeouts and 5xx responses only when
+/// `idempotent`, so a create is never repeated after the server saw it.
+fn send_with_retry(
+    build: impl Fn() -> reqwest::blocking::RequestBuilder,
+    retries: u32,
+    idempotent: bool,
+) -> Result<reqwest::blocking::Response, BackendError> {
+    let mut attempt = 0;
+    loop {
+        let last = attempt >= retries;
+        match build().send() {
+            Ok(resp) if idempotent && resp.status().is_server_error() && !last => {}
+            Ok(resp) => return Ok(resp),
+            Err(e) if !last && (e.is_connect() || (idempotent && e.is_timeout())) => {}
+            Err(e) => return Err(e.into()),
+        }
+        let delay = RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt));
+        std::thread::sleep(delay.min(RETRY_MAX_DELAY));
+        attempt += 1;
+    }
+}
+
+/// How much of a non-JSON body is quoted in the error, for debugging.
+const BODY_PREVIEW_CHARS: usize = 200;
+
+/// Decodes a successful response as JSON. A body with another content type,
+/// such as a proxy's HTML error page, gets a clearer error than serde's.
+fn read_json<T: serde::de::DeserializeOwned>(
+    resp: reqwest::blocking::Response,
+) -> Result<T, BackendError> {
+    let is_json = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|mime| mime.trim().to_ascii_lowercase())
+        .is_some_and(|mime| mime == "application/json" || mime.ends_with("+json"));
+    if !is_json {
+        let body = resp.text().unwrap_or_default();
+        let preview: String = body.chars().take(BODY_PREVIEW_CHARS).collect();
+        return Err(BackendError::Network(format!(
+            "server returned non-JSON response (is the URL correct?): {}",
+            preview.trim()
+        )));
+    }
+    resp.json::<T>().map_err(BackendError::from)
+}
+
+/// The `{"error": ...}` body the server sends with failing API responses.
+#[derive(serde::Deserialize)]
+struct ErrorBody {
+    error: String,
+}
+
+/// Describes a response with an unexpected status using the server's error
+/// message, falling back to the status line when the body isn't one. The
+/// server's request id is included when it sent one so the failure can be
+/// found in its logs.
+fn unexpected_status(resp: reqwest::blocking::Response) -> BackendError {
+    let status = resp.status();
+    let request_id = resp
+        .headers()
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let message = resp
+        .text()
+        .ok()
+        .and_then(|body| serde_json::from_str::<ErrorBody>(&body).ok())
+        .map(|body| body.error)
+        .unwrap_or_else(|| format!("HTTP {}", status));
+    match request_id {
+        Some(id) => BackendError::Network(format!("{} (request id {})", message, id)),
+        None => BackendError::Network(message),
+    }
+}
+
+impl LocalBackend {
+    pub fn open() -> Result<Self, BackendError> {
+        Ok(Self { db: db::init_db()? })
+    }
+
+    /// Opens the database at `path`.
+    pub fn open_at(path: PathBuf) -> Result<Self, BackendError> {
+        Ok(Self {
+            db: db::init_db_at(path)?,
+        })
+    }
+
+    /// Wraps an already opened database.
+    pub fn from_db(db: Db) -> Self {
+        Self { db }
+    }
+}
+
+impl SnippetStore for LocalBackend {
+    fn list_snippets(&self) -> Result<Vec<Snippet>, BackendError> {
+        Ok(db::get_all_snippets(&self.db)?)
+    }
+
+    fn list_snippet_metas(&self) -> Result<Vec<SnippetMeta>, BackendError> {
+        Ok(db::get_snippet_metas_page(&self.db, -1, 0)?)
+    }
+
+    fn count_snippets(&self) -> Result<usize, BackendError> {
+        Ok(db::count_snippets(&self.db)?.max(0) as usize)
+    }
+
+    fn health_check(&self) -> Result<(), BackendError> {
+        Ok(db::ping(&self.db)?)
+    }
+
+    fn get_snippet(&self, short_id: &str) -> Result<Option<Snippet>, BackendError> {
+        Ok(db::get_snippet_by_short_id(&self.db, short_id)?)
+    }
+
+    fn create_snippet(
+        &self,
+        name: &str,
+        content: &str,
+        language: Option<&str>,
+        tags: &[String],
+    ) -> Result<Snippet, BackendError> {
+        Ok(db::create_snippet(&self.db, name, content, language, tags)?)
+    }
+
+    fn update_snippet(
+        &self,
+        short_id: &str,
+        name: &str,
+        content: &str,
+        language: Option<&str>,
+        tags: &[String],
+    ) -> Result<Option<Snippet>, BackendError> {
+        Ok(db::update_snippet_by_short_id(
+            &self.db,
+            short_id,
+            name,
+            content,
+            db::ContentEncoding::Utf8,
+            language,
+            Some(tags),
+        )?)
+    }
+
+    fn rename_snippet(&self, short_id: &str, name: &str) -> Result<Option<Snippet>, BackendError> {
+        Ok(db::rename_snippet_by_short_id(&self.db, short_id, name)?)
+    }
+
+    fn delete_snippet(&self, short_id: &str) -> Result<bool, BackendError> {
+        Ok(db::delete_snippet_by_short_id(&self.db, short_id)?)
+    }
 }
 
-impl Backend {
-    pub fn local() -> Result<Self, BackendError> {
-        Ok(Backend::Local { db: db::init_db()? })
+impl RemoteBackend {
+    pub fn new(base_url: String, api_key: Option<String>) -> Self {
+        Self::with_opts(
+            base_url,
+            api_key,
+            DEFAULT_TIMEOUT,
+            DEFAULT_CONNECT_TIMEOUT,
+            DEFAULT_RETRIES,
+            false,
+        )
     }
 
-    pub fn remote(base_url: String, api_key: Option<String>) -> Self {
-        Backend::Remote {
+    /// Builds a remote backend. `insecure` accepts any TLS certificate, which
+    /// is only meant for testing against a server with a self-signed one.
+    pub fn with_opts(
+        base_url: String,
+        api_key: Option<String>,
+        timeout: Duration,
+        connect_timeout: Duration,
+        retries: u32,
+        insecure: bool,
+    ) -> Self {
+        if insecure {
+            static WARNED: Once = Once::new();
+            WARNED.call_once(|| {
+                eprintln!("warning: TLS certificate verification is disabled (--insecure)");
+            });
+        }
+        let client = reqwest::blocking::Client::builder()
+            .timeout(timeout)
+            .connect_timeout(connect_timeout)
+            .user_agent(concat!("sipp-tui/", env!("CARGO_PKG_VERSION")))
+            .danger_accept_invalid_certs(insecure)
+            .build()
+            .unwrap_or_else(|_| reqwest::blocking::Client::new());
+        Self {
             base_url,
             api_key,
-            client: reqwest::blocking::Client::new(),
+            auth_scheme: AuthScheme::default(),
+            client,
+            retries,
         }
     }
 
-    pub fn list_snippets(&self) -> Result<Vec<Snippet>, BackendError> {
-        match self {
-            Backend::Local { db } => Ok(db::get_all_snippets(db)?),
-            Backend::Remote {
-                base_url,
-                api_key,
-                client,
-            } => {
-                let mut req = client.get(format!("{}/api/snippets", base_url));
-                if let Some(key) = api_key {
-                    req = req.header("x-api-key", key);
-                }
-                let resp = req.send().map_err(|e| BackendError::Network(e.to_string()))?;
-                match resp.status().as_u16() {
-                    200 => resp
-                        .json::<Vec<Snippet>>()
-                        .map_err(|e| BackendError::Network(e.to_string())),
-                    401 => Err(BackendError::Unauthorized("Invalid API key".into())),
-                    403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
-                    _ => Err(BackendError::Network(format!("HTTP {}", resp.status()))),
-                }
-            }
+    /// Sets how the API key is sent.
+    pub fn with_auth_scheme(mut self, scheme: AuthScheme) -> Self {
+        self.auth_scheme = scheme;
+        self
+    }
+
+    /// Turns a `Location` header into a full URL. Absolute ones are used as
+    /// is, and paths are appended to the base URL so a path prefix the server
+    /// is proxied under is kept.
+    fn resolve_location(&self, location: &str) -> String {
+        if url::Url::parse(location).is_ok() {
+            location.to_string()
+        } else {
+            format!("{}/{}", self.base_url.trim_end_matches('/'), location.trim_start_matches('/'))
         }
     }
 
-    pub fn create_snippet(&self, name: &str, content: &str) -> Result<Snippet, BackendError> {
-        match self {
-            Backend::Local { db } => Ok(db::create_snippet(db, name, content)?),
-            Backend::Remote {
-                base_url,
-                api_key,
-                client,
-            } => {
-                let mut req = client
-                    .post(format!("{}/api/snippets", base_url))
-                    .json(&serde_json::json!({"name": name, "content": content}));
-                if let Some(key) = api_key {
-                    req = req.header("x-api-key", key);
-                }
-                let resp = req.send().map_err(|e| BackendError::Network(e.to_string()))?;
-                match resp.status().as_u16() {
-                    201 => resp
-                        .json::<Snippet>()
-                        .map_err(|e| BackendError::Network(e.to_string())),
-                    401 => Err(BackendError::Unauthorized("Invalid API key".into())),
-                    403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
-                    _ => Err(BackendError::Network(format!("HTTP {}", resp.status()))),
-                }
+    /// Sends the request from `build` with credentials attached, retrying per
+    /// [`send_with_retry`].
+    fn send(
+        &self,
+        build: impl Fn(&reqwest::blocking::Client) -> reqwest::blocking::RequestBuilder,
+        idempotent: bool,
+    ) -> Result<reqwest::blocking::Response, BackendError> {
+        send_with_retry(
+            || with_auth(build(&self.client), &self.api_key, self.auth_scheme),
+            self.retries,
+            idempotent,
+        )
+    }
+}
+
+impl SnippetStore for RemoteBackend {
+    fn list_snippets(&self) -> Result<Vec<Snippet>, BackendError> {
+        let resp = self.send(|c| c.get(format!("{}/api/snippets", self.base_url)), true)?;
+        match resp.status().as_u16() {
+            200 => read_json::<Vec<Snippet>>(resp),
+            401 => Err(BackendError::Unauthorized("Invalid API key".into())),
+            403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
+            _ => Err(unexpected_status(resp)),
+        }
+    }
+
+    fn list_snippet_metas(&self) -> Result<Vec<SnippetMeta>, BackendError> {
+        let resp = self.send(
+            |c| c.get(format!("{}/api/snippets?include_content=false", self.base_url)),
+            true,
+        )?;
+        match resp.status().as_u16() {
+            200 => read_json::<Vec<SnippetMeta>>(resp),
+            401 => Err(BackendError::Unauthorized("Invalid API key".into())),
+            403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
+            _ => Err(unexpected_status(resp)),
+        }
+    }
+
+    /// Asks for an empty page and reads the server's `X-Total-Count` header,
+    /// so no snippets are downloaded.
+    fn count_snippets(&self) -> Result<usize, BackendError> {
+        let resp = self.send(
+            |c| c.get(format!("{}/api/snippets?limit=0&include_content=false", self.base_url)),
+            true,
+        )?;
+        match resp.status().as_u16() {
+            200 => resp
+                .headers()
+                .get("x-total-count")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| BackendError::Network("server did not report a snippet count".into())),
+            401 => Err(BackendError::Unauthorized("Invalid API key".into())),
+            403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
+            _ => Err(unexpected_status(resp)),
+        }
+    }
+
+    fn health_check(&self) -> Result<(), BackendError> {
+        let resp = self.send(|c| c.get(format!("{}/healthz", self.base_url)), true)?;
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(unexpected_status(resp))
+        }
+    }
+
+    fn get_snippet(&self, short_id: &str) -> Result<Option<Snippet>, BackendError> {
+        let resp = self.send(
+            |c| c.get(format!("{}/api/snippets/{}", self.base_url, short_id)),
+            true,
+        )?;
+        match resp.status().as_u16() {
+            200 => read_json::<Snippet>(resp).map(Some),
+            401 => Err(BackendError::Unauthorized("Invalid API key".into())),
+            403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
+            404 => Ok(None),
+            _ => Err(unexpected_status(resp)),
+        }
+    }
+
+    fn create_snippet(
+        &self,
+        name: &str,
+        content: &str,
+        language: Option<&str>,
+        tags: &[String],
+    ) -> Result<Snippet, BackendError> {
+        self.create_snippet_with_link(name, content, language, tags).map(|(snippet, _)| snippet)
+    }
+
+    fn create_snippet_with_link(
+        &self,
+        name: &str,
+        content: &str,
+        language: Option<&str>,
+        tags: &[String],
+    ) -> Result<(Snippet, Option<String>), BackendError> {
+        let resp = self.send(
+            |c| {
+                c.post(format!("{}/api/snippets", self.base_url))
+                    .json(&serde_json::json!({"name": name, "content": content, "language": language, "tags": tags}))
+            },
+            false,
+        )?;
+        match resp.status().as_u16() {
+            201 => {
+                let link = resp
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|location| self.resolve_location(location));
+                Ok((read_json::<Snippet>(resp)?, link))
             }
+            401 => Err(BackendError::Unauthorized("Invalid API key".into())),
+            403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
+            _ => Err(unexpected_status(resp)),
+        }
+    }
+
+    fn validate_snippet(&self, name: &str, content: &str) -> Result<(), BackendError> {
+        let resp = self.send(
+            |c| {
+                c.post(format!("{}/api/snippets/validate", self.base_url))
+                    .json(&serde_json::json!({"name": name, "content": content}))
+            },
+            true,
+        )?;
+        match resp.status().as_u16() {
+            200 => Ok(()),
+            // Servers from before the endpoint existed; the create itself will tell
+            404 | 405 => Ok(()),
+            400 | 413 => Err(match unexpected_status(resp) {
+                BackendError::Network(msg) => BackendError::Rejected(msg),
+                e => e,
+            }),
+            401 => Err(BackendError::Unauthorized("Invalid API key".into())),
+            403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
+            _ => Err(unexpected_status(resp)),
         }
     }
 
-    pub fn update_snippet(
+    fn update_snippet(
         &self,
         short_id: &str,
         name: &str,
         content: &str,
+        language: Option<&str>,
+        tags: &[String],
     ) -> Result<Option<Snippet>, BackendError> {
-        match self {
-            Backend::Local { db } => Ok(db::update_snippet_by_short_id(db, short_id, name, content)?),
-            Backend::Remote {
-                base_url,
-                api_key,
-                client,
-            } => {
-                let mut req = client
-                    .put(format!("{}/api/snippets/{}", base_url, short_id))
-                    .json(&serde_json::json!({"name": name, "content": content}));
-                if let Some(key) = api_key {
-                    req = req.header("x-api-key", key);
-                }
-                let resp = req.send().map_err(|e| BackendError::Network(e.to_string()))?;
-                match resp.status().as_u16() {
-                    200 => resp
-                        .json::<Snippet>()
-                        .map(Some)
-                        .map_err(|e| BackendError::Network(e.to_string())),
-                    401 => Err(BackendError::Unauthorized("Invalid API key".into())),
-                    403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
-                    404 => Ok(None),
-                    _ => Err(BackendError::Network(format!("HTTP {}", resp.status()))),
-                }
-            }
+        let resp = self.send(
+            |c| {
+                c.put(format!("{}/api/snippets/{}", self.base_url, short_id))
+                    .json(&serde_json::json!({"name": name, "content": content, "language": language, "tags": tags}))
+            },
+            true,
+        )?;
+        match resp.status().as_u16() {
+            200 => read_json::<Snippet>(resp).map(Some),
+            401 => Err(BackendError::Unauthorized("Invalid API key".into())),
+            403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
+            404 => Ok(None),
+            _ => Err(unexpected_status(resp)),
         }
     }
 
-    pub fn delete_snippet(&self, short_id: &str) -> Result<bool, BackendError> {
-        match self {
-            Backend::Local { db } => Ok(db::delete_snippet_by_short_id(db, short_id)?),
-            Backend::Remote {
-                base_url,
-                api_key,
-                client,
-            } => {
-                let mut req =
-                    client.delete(format!("{}/api/snippets/{}", base_url, short_id));
-                if let Some(key) = api_key {
-                    req = req.header("x-api-key", key);
-                }
-                let resp = req.send().map_err(|e| BackendError::Network(e.to_string()))?;
-                match resp.status().as_u16() {
-                    200 => Ok(true),
-                    401 => Err(BackendError::Unauthorized("Invalid API key".into())),
-                    403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
-                    404 => Ok(false),
-                    _ => Err(BackendError::Network(format!("HTTP {}", resp.status()))),
-                }
-            }
+    fn rename_snippet(&self, short_id: &str, name: &str) -> Result<Option<Snippet>, BackendError> {
+        let resp = self.send(
+            |c| {
+                c.post(format!("{}/api/snippets/{}/rename", self.base_url, short_id))
+                    .json(&serde_json::json!({"name": name}))
+            },
+            true,
+        )?;
+        match resp.status().as_u16() {
+            200 => read_json::<Snippet>(resp).map(Some),
+            401 => Err(BackendError::Unauthorized("Invalid API key".into())),
+            403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
+            404 => Ok(None),
+            _ => Err(unexpected_status(resp)),
+        }
+    }
+
+    fn delete_snippet(&self, short_id: &str) -> Result<bool, BackendError> {
+        let resp = self.send(
+            |c| c.delete(format!("{}/api/snippets/{}", self.base_url, short_id)),
+            true,
+        )?;
+        match resp.status().as_u16() {
+            200 => Ok(true),
+            401 => Err(BackendError::Unauthorized("Invalid API key".into())),
+            403 => Err(BackendError::Unauthorized("No API key configured on server".into())),
+            404 => Ok(false),
+            _ => Err(unexpected_status(resp)),
         }
     }
 }
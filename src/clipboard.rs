@@ -0,0 +1,151 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::io::{IsTerminal, Write};
+
+/// Somewhere copied text can be sent.
+pub trait Clipboard {
+    fn set_text(&mut self, text: &str) -> Result<(), String>;
+}
+
+/// The system clipboard. Unavailable without a display server, e.g. over SSH.
+pub struct SystemClipboard;
+
+impl Clipboard for SystemClipboard {
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.set_text(text))
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Asks the terminal to set its clipboard with an OSC 52 escape sequence,
+/// which reaches the local machine even from a remote session.
+pub struct Osc52<W> {
+    out: W,
+}
+
+impl<W: Write> Osc52<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+}
+
+impl Osc52<std::io::Stdout> {
+    /// Writes to stdout, or `None` when stdout isn't a terminal to talk to.
+    pub fn stdout() -> Option<Self> {
+        let out = std::io::stdout();
+        out.is_terminal().then(|| Self::new(out))
+    }
+}
+
+impl<W: Write> Clipboard for Osc52<W> {
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        write!(self.out, "\x1b]52;c;{}\x07", STANDARD.encode(text))
+            .and_then(|_| self.out.flush())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Tries each clipboard in order until one accepts the text.
+pub struct FallbackClipboard {
+    clipboards: Vec<Box<dyn Clipboard>>,
+}
+
+impl FallbackClipboard {
+    pub fn new(clipboards: Vec<Box<dyn Clipboard>>) -> Self {
+        Self { clipboards }
+    }
+
+    /// The system clipboard, then OSC 52 when stdout is a terminal.
+    pub fn detect() -> Self {
+        let mut clipboards: Vec<Box<dyn Clipboard>> = vec![Box::new(SystemClipboard)];
+        if let Some(osc52) = Osc52::stdout() {
+            clipboards.push(Box::new(osc52));
+        }
+        Self::new(clipboards)
+    }
+}
+
+impl Clipboard for FallbackClipboard {
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        let mut last_error = "no clipboard available".to_string();
+        for clipboard in &mut self.clipboards {
+            match clipboard.set_text(text) {
+                Ok(()) => return Ok(()),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A clipboard that fails with `error`, or records the text it is given.
+    struct Fake {
+        error: Option<&'static str>,
+        copied: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Clipboard for Fake {
+        fn set_text(&mut self, text: &str) -> Result<(), String> {
+            if let Some(error) = self.error {
+                return Err(error.to_string());
+            }
+            self.copied.borrow_mut().push(text.to_string());
+            Ok(())
+        }
+    }
+
+    /// A terminal whose output stays readable after the clipboard owns it.
+    #[derive(Clone, Default)]
+    struct Terminal(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Terminal {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn system_then_osc52(error: Option<&'static str>) -> (FallbackClipboard, Rc<RefCell<Vec<String>>>, Terminal) {
+        let copied = Rc::new(RefCell::new(Vec::new()));
+        let terminal = Terminal::default();
+        let clipboard = FallbackClipboard::new(vec![
+            Box::new(Fake { error, copied: copied.clone() }),
+            Box::new(Osc52::new(terminal.clone())),
+        ]);
+        (clipboard, copied, terminal)
+    }
+
+    #[test]
+    fn osc52_is_used_only_when_the_system_clipboard_fails() {
+        let (mut clipboard, copied, terminal) = system_then_osc52(None);
+        clipboard.set_text("hi").unwrap();
+        assert_eq!(*copied.borrow(), ["hi"]);
+        assert!(terminal.0.borrow().is_empty());
+
+        let (mut clipboard, copied, terminal) = system_then_osc52(Some("no display"));
+        clipboard.set_text("hi").unwrap();
+        assert!(copied.borrow().is_empty());
+        assert_eq!(*terminal.0.borrow(), b"\x1b]52;c;aGk=\x07");
+    }
+
+    #[test]
+    fn the_last_error_is_reported_when_every_clipboard_fails() {
+        let copied = Rc::new(RefCell::new(Vec::new()));
+        let mut clipboard = FallbackClipboard::new(vec![
+            Box::new(Fake { error: Some("no display"), copied: copied.clone() }),
+            Box::new(Fake { error: Some("not a terminal"), copied }),
+        ]);
+        assert_eq!(clipboard.set_text("hi"), Err("not a terminal".to_string()));
+        assert_eq!(FallbackClipboard::new(Vec::new()).set_text("hi"), Err("no clipboard available".to_string()));
+    }
+}
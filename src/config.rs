@@ -1,25 +1,208 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// How long TUI status messages stay up when `status_timeout_ms` isn't set.
+pub const DEFAULT_STATUS_TIMEOUT_MS: u64 = 2000;
+
+/// How the API key is sent to a remote server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthScheme {
+    /// `x-api-key: <key>`
+    #[default]
+    ApiKey,
+    /// `Authorization: Bearer <key>`
+    Bearer,
+}
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Config {
     pub remote_url: Option<String>,
     pub api_key: Option<String>,
+    #[serde(default)]
+    pub auth_scheme: AuthScheme,
+    /// Base URL that share links are built on, for servers whose public
+    /// address differs from `remote_url`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_url: Option<String>,
+    /// Path to a local database, used when neither `--db-path` nor
+    /// `SIPP_DB_PATH` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db_path: Option<String>,
+    /// Name of the syntax highlighting theme used by the TUI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    /// How long TUI status messages stay up, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_timeout_ms: Option<u64>,
+    /// TUI keys under `[keybindings]`, from action name to a key string or a
+    /// list of them. Kept loosely typed so one bad entry is reported instead
+    /// of discarding the whole config.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keybindings: BTreeMap<String, toml::Value>,
+    /// Whether the TUI's create form starts with ANSI stripping on.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strip_ansi: bool,
+    /// Reopen the TUI on the snippet that was selected when it last quit.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub restore_session: bool,
+    /// Profile used when `--profile` isn't given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    /// Named remotes under `[profiles.<name>]`, which override the top-level
+    /// remote settings when selected.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub remote_url: Option<String>,
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub auth_scheme: AuthScheme,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_url: Option<String>,
+}
+
+/// Checks that `input` is an absolute `http`/`https` URL with a host and
+/// returns it without a trailing slash.
+pub fn normalize_remote_url(input: &str) -> Result<String, String> {
+    let input = input.trim();
+    if !input.contains("://") {
+        return Err(format!(
+            "Invalid remote URL '{}': missing scheme, try 'https://{}'",
+            input, input
+        ));
+    }
+    let url = url::Url::parse(input)
+        .map_err(|e| format!("Invalid remote URL '{}': {}", input, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "Invalid remote URL '{}': scheme must be http or https",
+            input
+        ));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(format!("Invalid remote URL '{}': missing host", input));
+    }
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// sipp's directory under `base`, a per-platform config directory such as
+/// the one from [`dirs::config_dir`].
+fn config_dir_in(base: &Path) -> PathBuf {
+    base.join("sipp")
+}
+
+/// sipp's config directory: `%APPDATA%\sipp` on Windows,
+/// `~/Library/Application Support/sipp` on macOS, and `$XDG_CONFIG_HOME/sipp`
+/// or `~/.config/sipp` elsewhere.
+fn config_dir() -> PathBuf {
+    config_dir_in(&dirs::config_dir().unwrap_or_else(|| PathBuf::from(".")))
 }
 
+/// Where versions before per-platform paths kept the config, under `home`.
+fn legacy_config_path_in(home: &Path) -> PathBuf {
+    home.join(".config/sipp/config.toml")
+}
+
+/// The config file to read: the canonical one under `base`, or the legacy
+/// one under `home` when only that one exists.
+fn read_path_in(base: &Path, home: Option<&Path>) -> PathBuf {
+    let path = config_dir_in(base).join("config.toml");
+    if path.exists() {
+        return path;
+    }
+    match home.map(legacy_config_path_in) {
+        Some(legacy) if legacy.exists() => legacy,
+        _ => path,
+    }
+}
+
+/// The canonical config file location, which [`save_config`] writes to.
 pub fn config_path() -> PathBuf {
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-    PathBuf::from(home).join(".config/sipp/config.toml")
+    config_dir().join("config.toml")
 }
 
 pub fn load_config() -> Config {
-    let path = config_path();
+    let base = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    let path = read_path_in(&base, dirs::home_dir().as_deref());
     match std::fs::read_to_string(&path) {
         Ok(contents) => toml::from_str(&contents).unwrap_or_default(),
         Err(_) => Config::default(),
     }
 }
 
+/// `config` with the remote settings of profile `name` in place of the
+/// top-level ones, or an error naming the configured profiles when there is
+/// no such profile.
+fn apply_profile(mut config: Config, name: &str) -> Result<Config, String> {
+    let Some(profile) = config.profiles.get(name).cloned() else {
+        let names: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+        return Err(if names.is_empty() {
+            format!("Unknown profile '{}': no profiles are configured", name)
+        } else {
+            format!("Unknown profile '{}'; configured profiles: {}", name, names.join(", "))
+        });
+    };
+    config.remote_url = profile.remote_url;
+    config.api_key = profile.api_key;
+    config.auth_scheme = profile.auth_scheme;
+    config.public_url = profile.public_url;
+    Ok(config)
+}
+
+/// Loads the config with the remote settings of profile `name` in place of
+/// the top-level ones. Fails if the config has no such profile.
+pub fn load_profile(name: &str) -> Result<Config, String> {
+    apply_profile(load_config(), name)
+}
+
+/// Loads the config for `profile`, falling back to `default_profile` and then
+/// to the top-level settings. Fails if the chosen profile isn't configured.
+pub fn load_active(profile: Option<&str>) -> Result<Config, String> {
+    let config = load_config();
+    match profile.map(str::to_string).or(config.default_profile.clone()) {
+        Some(name) => apply_profile(config, &name),
+        None => Ok(config),
+    }
+}
+
+/// What the TUI remembers between launches when `restore_session` is set.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SessionState {
+    /// Short id of the snippet selected on quit.
+    pub selected: Option<String>,
+    /// First visible row of the snippet list on quit.
+    #[serde(default)]
+    pub list_offset: usize,
+}
+
+/// Where the TUI session is saved, next to the config file.
+pub fn state_path() -> PathBuf {
+    config_dir().join("state.json")
+}
+
+/// Reads the saved session, or an empty one when there is none or it can't
+/// be read.
+pub fn load_session() -> SessionState {
+    std::fs::read_to_string(state_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_session(state: &SessionState) -> Result<(), Box<dyn std::error::Error>> {
+    let path = state_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(state)?)?;
+    Ok(())
+}
+
 pub fn save_config(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     let path = config_path();
     if let Some(parent) = path.parent() {
@@ -29,3 +212,45 @@ pub fn save_config(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     std::fs::write(&path, contents)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_replace_the_remote_and_unknown_ones_fail() {
+        let config = || -> Config {
+            toml::from_str(
+                "remote_url = \"https://default.example\"\n\
+                 [profiles.prod]\nremote_url = \"https://prod.example\"\n\
+                 [profiles.staging]\nremote_url = \"https://staging.example\"\n",
+            )
+            .unwrap()
+        };
+        let prod = apply_profile(config(), "prod").unwrap();
+        assert_eq!(prod.remote_url.as_deref(), Some("https://prod.example"));
+
+        let typo = apply_profile(config(), "prdo").unwrap_err();
+        assert_eq!(typo, "Unknown profile 'prdo'; configured profiles: prod, staging");
+        let none = apply_profile(Config::default(), "prod").unwrap_err();
+        assert_eq!(none, "Unknown profile 'prod': no profiles are configured");
+    }
+
+    #[test]
+    fn reads_the_legacy_config_only_when_the_canonical_one_is_missing() {
+        let base = tempfile::tempdir().unwrap();
+        let home = tempfile::tempdir().unwrap();
+        let canonical = base.path().join("sipp/config.toml");
+        let legacy = home.path().join(".config/sipp/config.toml");
+
+        assert_eq!(read_path_in(base.path(), Some(home.path())), canonical);
+        std::fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        std::fs::write(&legacy, "").unwrap();
+        assert_eq!(read_path_in(base.path(), Some(home.path())), legacy);
+        assert_eq!(read_path_in(base.path(), None), canonical);
+
+        std::fs::create_dir_all(canonical.parent().unwrap()).unwrap();
+        std::fs::write(&canonical, "").unwrap();
+        assert_eq!(read_path_in(base.path(), Some(home.path())), canonical);
+    }
+}
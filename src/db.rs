@@ -1,22 +1,33 @@
-use nanoid::nanoid;
-use rusqlite::{Connection, params};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{Connection, TransactionBehavior, params};
 use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::sync::{Arc, Mutex};
+use std::path::Path;
+use std::time::Duration;
 
-pub type Db = Arc<Mutex<Connection>>;
+pub type Db = Pool<SqliteConnectionManager>;
 
 #[derive(Debug)]
 pub enum DbError {
     Sqlite(rusqlite::Error),
-    LockPoisoned,
+    Pool(r2d2::Error),
+    /// A custom short id was requested but is already in use.
+    ShortIdTaken(String),
+    /// Storing more snippets would go over the cap of this many.
+    StoreFull(i64),
+    /// Content sent as base64 couldn't be decoded.
+    InvalidBase64,
 }
 
 impl fmt::Display for DbError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             DbError::Sqlite(e) => write!(f, "Database error: {}", e),
-            DbError::LockPoisoned => write!(f, "Database lock poisoned"),
+            DbError::Pool(e) => write!(f, "Database pool error: {}", e),
+            DbError::ShortIdTaken(id) => write!(f, "Short id '{}' is already taken", id),
+            DbError::StoreFull(max) => write!(f, "Snippet storage is full ({} snippets)", max),
+            DbError::InvalidBase64 => write!(f, "Content is not valid base64"),
         }
     }
 }
@@ -29,65 +40,609 @@ impl From<rusqlite::Error> for DbError {
     }
 }
 
+impl From<r2d2::Error> for DbError {
+    fn from(e: r2d2::Error) -> Self {
+        DbError::Pool(e)
+    }
+}
+
+/// How a snippet's `content` is carried in JSON: as the text itself, or as
+/// base64 for arbitrary bytes. Base64 snippets are stored decoded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentEncoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+impl ContentEncoding {
+    pub fn is_utf8(&self) -> bool {
+        *self == ContentEncoding::Utf8
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ContentEncoding::Utf8 => "utf8",
+            ContentEncoding::Base64 => "base64",
+        }
+    }
+
+    fn from_column(value: &str) -> Self {
+        if value == "base64" { ContentEncoding::Base64 } else { ContentEncoding::Utf8 }
+    }
+}
+
+/// Decodes base64 content, ignoring the line breaks and spaces that wrapped
+/// blobs usually carry.
+pub fn decode_base64(content: &str) -> Option<Vec<u8>> {
+    use base64::Engine;
+
+    let compact: String = content.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    base64::engine::general_purpose::STANDARD.decode(compact).ok()
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Snippet {
     pub id: i64,
     pub short_id: String,
     pub content: String,
     pub name: String,
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Labels for organizing snippets, normalized by [`normalize_tags`].
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// How many times the snippet page or its raw content has been served.
+    #[serde(default)]
+    pub view_count: i64,
+    /// Unix seconds; `None` for snippets stored before this was recorded.
+    #[serde(default)]
+    pub created_at: Option<i64>,
+    /// Unix seconds of the last change to anything but the view count. Starts
+    /// out equal to `created_at`, or 0 when that is unknown.
+    #[serde(default)]
+    pub updated_at: i64,
+    /// Only sent for base64 snippets; see [`ContentEncoding`].
+    #[serde(default, skip_serializing_if = "ContentEncoding::is_utf8")]
+    pub encoding: ContentEncoding,
+    /// Where the snippet can be viewed. Filled in by the server on API
+    /// responses, never stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Never sent over the API; see [`crate::password`].
+    #[serde(skip)]
+    pub password_hash: Option<String>,
+}
+
+impl Snippet {
+    /// Whether viewing this snippet requires a password.
+    pub fn is_protected(&self) -> bool {
+        self.password_hash.is_some()
+    }
+
+    /// Whether the content is arbitrary bytes rather than text.
+    pub fn is_binary(&self) -> bool {
+        self.encoding == ContentEncoding::Base64
+    }
+
+    /// The content as served raw: the decoded bytes of a base64 snippet, or
+    /// the text of any other.
+    pub fn raw_bytes(&self) -> Vec<u8> {
+        match self.encoding {
+            ContentEncoding::Base64 => decode_base64(&self.content).unwrap_or_default(),
+            ContentEncoding::Utf8 => self.content.clone().into_bytes(),
+        }
+    }
+
+    /// The snippet's listing fields, without its content.
+    pub fn meta(&self) -> SnippetMeta {
+        SnippetMeta {
+            id: self.id,
+            short_id: self.short_id.clone(),
+            name: self.name.clone(),
+            language: self.language.clone(),
+            tags: self.tags.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            encoding: self.encoding,
+            url: self.url.clone(),
+        }
+    }
+}
+
+/// A snippet without its content, used when listing many snippets at once.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SnippetMeta {
+    pub id: i64,
+    pub short_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub created_at: Option<i64>,
+    #[serde(default)]
+    pub updated_at: i64,
+    #[serde(default, skip_serializing_if = "ContentEncoding::is_utf8")]
+    pub encoding: ContentEncoding,
+    /// See [`Snippet::url`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// Trims each tag, splits any that contain commas, and drops empty and
+/// repeated ones, keeping the first occurrence's position.
+pub fn normalize_tags<S: AsRef<str>>(tags: impl IntoIterator<Item = S>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        for part in tag.as_ref().split(',').map(str::trim) {
+            if !part.is_empty() && !normalized.iter().any(|t| t == part) {
+                normalized.push(part.to_string());
+            }
+        }
+    }
+    normalized
+}
+
+/// Parses a comma-separated tag list such as `"rust, sql"`.
+pub fn parse_tags(input: &str) -> Vec<String> {
+    normalize_tags([input])
+}
+
+/// The `tags` column value for `tags`, which [`parse_tags`] reads back.
+fn join_tags(tags: &[String]) -> String {
+    normalize_tags(tags).join(",")
+}
+
+fn tags_from_column(value: Option<String>) -> Vec<String> {
+    value.as_deref().map(parse_tags).unwrap_or_default()
+}
+
+const SNIPPET_COLUMNS: &str =
+    "id, short_id, content, name, language, password_hash, view_count, created_at, tags, updated_at, compressed, encoding";
+
+/// Content at least this many bytes long is stored gzip-compressed.
+pub const COMPRESS_THRESHOLD: usize = 4 * 1024;
+
+/// The form `content` is stored in, and whether it is compressed: the
+/// decoded bytes of base64 content, gzip bytes for large text that shrinks,
+/// and the text itself otherwise.
+fn encode_content(content: &str, encoding: ContentEncoding) -> Result<(rusqlite::types::Value, bool), DbError> {
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+
+    if encoding == ContentEncoding::Base64 {
+        let bytes = decode_base64(content).ok_or(DbError::InvalidBase64)?;
+        return Ok((rusqlite::types::Value::Blob(bytes), false));
+    }
+    if content.len() >= COMPRESS_THRESHOLD {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        if let Ok(bytes) = encoder.write_all(content.as_bytes()).and_then(|()| encoder.finish())
+            && bytes.len() < content.len()
+        {
+            return Ok((rusqlite::types::Value::Blob(bytes), true));
+        }
+    }
+    Ok((rusqlite::types::Value::Text(content.to_string()), false))
+}
+
+fn decode_content(bytes: &[u8]) -> std::io::Result<String> {
+    use std::io::Read;
+
+    let mut content = String::new();
+    flate2::read::GzDecoder::new(bytes).read_to_string(&mut content)?;
+    Ok(content)
+}
+
+fn encoding_from_row(row: &rusqlite::Row) -> rusqlite::Result<ContentEncoding> {
+    Ok(ContentEncoding::from_column(&row.get::<_, String>(11)?))
+}
+
+fn content_from_row(row: &rusqlite::Row) -> rusqlite::Result<String> {
+    if encoding_from_row(row)? == ContentEncoding::Base64 {
+        use base64::Engine;
+
+        let bytes: Vec<u8> = row.get(2)?;
+        return Ok(base64::engine::general_purpose::STANDARD.encode(bytes));
+    }
+    if !row.get::<_, bool>(10)? {
+        return row.get(2);
+    }
+    let bytes: Vec<u8> = row.get(2)?;
+    decode_content(&bytes)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Blob, Box::new(e)))
+}
+
+fn snippet_from_row(row: &rusqlite::Row) -> rusqlite::Result<Snippet> {
+    Ok(Snippet {
+        id: row.get(0)?,
+        short_id: row.get(1)?,
+        content: content_from_row(row)?,
+        name: row.get(3)?,
+        language: row.get(4)?,
+        password_hash: row.get(5)?,
+        view_count: row.get(6)?,
+        created_at: row.get(7)?,
+        tags: tags_from_column(row.get(8)?),
+        updated_at: row.get(9)?,
+        encoding: encoding_from_row(row)?,
+        url: None,
+    })
+}
+
+/// The characters random short ids are drawn from by default.
+pub const SHORT_ID_ALPHABET: [char; 64] = nanoid::alphabet::SAFE;
+
+/// [`SHORT_ID_ALPHABET`] without `0`, `O`, `1`, `l`, and `I`, which are easy
+/// to misread when an id is copied by hand.
+pub const UNAMBIGUOUS_SHORT_ID_ALPHABET: [char; 59] = [
+    '_', '-', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i',
+    'j', 'k', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B', 'C',
+    'D', 'E', 'F', 'G', 'H', 'J', 'K', 'L', 'M', 'N', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X',
+    'Y', 'Z',
+];
+
+pub const DEFAULT_SHORT_ID_LEN: usize = 10;
+
+/// Shorter random ids would collide too often to be worth retrying.
+pub const MIN_SHORT_ID_LEN: usize = 4;
+
+/// How many freshly generated short ids to try before giving up on an insert.
+const SHORT_ID_ATTEMPTS: usize = 5;
+
+/// Attempts for ids shorter than [`DEFAULT_SHORT_ID_LEN`], which collide more often.
+const SHORT_ID_ATTEMPTS_SHORT: usize = 20;
+
+/// Length and alphabet of generated short ids.
+#[derive(Debug, Clone, Copy)]
+pub struct ShortIdFormat {
+    pub len: usize,
+    pub alphabet: &'static [char],
+}
+
+impl Default for ShortIdFormat {
+    fn default() -> Self {
+        Self { len: DEFAULT_SHORT_ID_LEN, alphabet: &SHORT_ID_ALPHABET }
+    }
+}
+
+impl ShortIdFormat {
+    fn generate(&self) -> String {
+        nanoid::format(nanoid::rngs::default, self.alphabet, self.len)
+    }
+
+    fn attempts(&self) -> usize {
+        if self.len < DEFAULT_SHORT_ID_LEN { SHORT_ID_ATTEMPTS_SHORT } else { SHORT_ID_ATTEMPTS }
+    }
 }
 
-fn generate_short_id() -> String {
-    nanoid!(10)
+const INSERT_SNIPPET: &str = "INSERT INTO snippets (short_id, content, name, language, password_hash, created_at, tags, updated_at, compressed, encoding)
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?6, ?8, ?9)";
+
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn is_unique_violation(e: &rusqlite::Error) -> bool {
+    matches!(
+        e,
+        rusqlite::Error::SqliteFailure(err, _)
+            if err.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE
+    )
+}
+
+/// Inserts `snippet` under its custom short id, failing with
+/// [`DbError::ShortIdTaken`] if that id is already in use, or under a freshly
+/// generated one that is regenerated whenever it collides.
+fn insert_snippet(conn: &Connection, snippet: &NewSnippet) -> Result<Snippet, DbError> {
+    insert_snippet_with(conn, snippet, || snippet.short_id_format.generate())
+}
+
+/// [`insert_snippet`] with the generated short ids drawn from `generate`.
+fn insert_snippet_with(
+    conn: &Connection,
+    snippet: &NewSnippet,
+    mut generate: impl FnMut() -> String,
+) -> Result<Snippet, DbError> {
+    let mut stmt = conn.prepare_cached(INSERT_SNIPPET)?;
+    let created_at = unix_now();
+    let tags = normalize_tags(snippet.tags);
+    let (content, compressed) = encode_content(snippet.content, snippet.encoding)?;
+    let mut attempt = 1;
+    let short_id = loop {
+        let short_id = snippet
+            .short_id
+            .map(str::to_string)
+            .unwrap_or_else(&mut generate);
+        match stmt.execute(params![
+            short_id,
+            content,
+            snippet.name,
+            snippet.language,
+            snippet.password_hash,
+            created_at,
+            tags.join(","),
+            compressed,
+            snippet.encoding.as_str()
+        ]) {
+            Ok(_) => break short_id,
+            Err(e) if is_unique_violation(&e) && snippet.short_id.is_some() => {
+                return Err(DbError::ShortIdTaken(short_id));
+            }
+            Err(e) if is_unique_violation(&e) && attempt < snippet.short_id_format.attempts() => {
+                attempt += 1
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+    Ok(Snippet {
+        id: conn.last_insert_rowid(),
+        short_id,
+        content: snippet.content.to_string(),
+        name: snippet.name.to_string(),
+        language: snippet.language.map(|l| l.to_string()),
+        tags,
+        password_hash: snippet.password_hash.map(|h| h.to_string()),
+        view_count: 0,
+        created_at: Some(created_at),
+        updated_at: created_at,
+        encoding: snippet.encoding,
+        url: None,
+    })
 }
 
+/// The path [`init_db_at`] treats as a request for an in-memory database.
+pub const MEMORY_PATH: &str = ":memory:";
+
 pub fn db_path() -> String {
     std::env::var("SIPP_DB_PATH").unwrap_or_else(|_| "sipp.sqlite".to_string())
 }
 
+pub fn pool_size() -> u32 {
+    std::env::var("SIPP_DB_POOL_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(4)
+}
+
 pub fn init_db() -> Result<Db, DbError> {
-    let conn = Connection::open(db_path())?;
+    init_db_at(db_path())
+}
+
+/// Like [`init_db`] but opens the database at `path` instead of [`db_path`].
+/// `:memory:` opens a fresh in-memory database that lasts as long as the pool.
+pub fn init_db_at(path: impl AsRef<Path>) -> Result<Db, DbError> {
+    let path = path.as_ref();
+    let builder = Pool::builder();
+    // Every connection to `:memory:` gets its own database, so the pool keeps
+    // exactly one and never closes it
+    let pool = if path == Path::new(MEMORY_PATH) {
+        let manager = SqliteConnectionManager::memory().with_init(|conn| conn.busy_timeout(Duration::from_secs(5)));
+        builder.max_size(1).idle_timeout(None).max_lifetime(None).build(manager)?
+    } else {
+        // Write-ahead logging lets readers carry on while a write is in progress
+        let manager = SqliteConnectionManager::file(path).with_init(|conn| {
+            conn.busy_timeout(Duration::from_secs(5))?;
+            conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))
+        });
+        builder.max_size(pool_size()).build(manager)?
+    };
+    init_schema(&*pool.get()?)?;
+    Ok(pool)
+}
+
+/// A fresh in-memory database, e.g. for tests or a throwaway server.
+pub fn init_db_in_memory() -> Result<Db, DbError> {
+    init_db_at(MEMORY_PATH)
+}
+
+/// Creates the `snippets` table on `conn`, or brings an older one up to date.
+/// Works on any connection, including `Connection::open_in_memory()`.
+pub fn init_schema(conn: &Connection) -> Result<(), DbError> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS snippets (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             short_id TEXT NOT NULL UNIQUE,
             content TEXT NOT NULL,
-            name TEXT NOT NULL
+            name TEXT NOT NULL,
+            language TEXT,
+            password_hash TEXT,
+            view_count INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER,
+            tags TEXT,
+            updated_at INTEGER NOT NULL DEFAULT 0,
+            compressed INTEGER NOT NULL DEFAULT 0,
+            encoding TEXT NOT NULL DEFAULT 'utf8'
         )",
         [],
     )?;
-    Ok(Arc::new(Mutex::new(conn)))
+    add_column_if_missing(conn, "language", "TEXT")?;
+    add_column_if_missing(conn, "password_hash", "TEXT")?;
+    add_column_if_missing(conn, "view_count", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "created_at", "INTEGER")?;
+    add_column_if_missing(conn, "tags", "TEXT")?;
+    if add_column_if_missing(conn, "updated_at", "INTEGER NOT NULL DEFAULT 0")? {
+        conn.execute("UPDATE snippets SET updated_at = COALESCE(created_at, 0)", [])?;
+    }
+    // Set when `content` holds gzip bytes rather than text
+    add_column_if_missing(conn, "compressed", "INTEGER NOT NULL DEFAULT 0")?;
+    // 'base64' when `content` holds arbitrary bytes, see `ContentEncoding`
+    add_column_if_missing(conn, "encoding", "TEXT NOT NULL DEFAULT 'utf8'")?;
+    Ok(())
 }
 
-pub fn create_snippet(db: &Db, name: &str, content: &str) -> Result<Snippet, DbError> {
-    let conn = db.lock().map_err(|_| DbError::LockPoisoned)?;
-    let short_id = generate_short_id();
-    conn.execute(
-        "INSERT INTO snippets (short_id, content, name) VALUES (?1, ?2, ?3)",
-        params![short_id, content, name],
-    )?;
-    let id = conn.last_insert_rowid();
-    Ok(Snippet {
-        id,
-        short_id,
-        content: content.to_string(),
-        name: name.to_string(),
-    })
+/// Checks that a connection can be obtained and a trivial query succeeds.
+pub fn ping(db: &Db) -> Result<(), DbError> {
+    let conn = db.get()?;
+    conn.query_row("SELECT 1", [], |_| Ok(()))?;
+    Ok(())
+}
+
+/// Collapses the write-ahead log back into the main database file.
+pub fn checkpoint(db: &Db) -> Result<(), DbError> {
+    let conn = db.get()?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    Ok(())
+}
+
+/// Adds a column to `snippets` when opening a database created by an older
+/// version, returning whether it had to.
+fn add_column_if_missing(conn: &Connection, column: &str, definition: &str) -> Result<bool, DbError> {
+    let exists = conn
+        .prepare("SELECT 1 FROM pragma_table_info('snippets') WHERE name = ?1")?
+        .exists(params![column])?;
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE snippets ADD COLUMN {} {}", column, definition),
+            [],
+        )?;
+    }
+    Ok(!exists)
+}
+
+pub fn create_snippet(
+    db: &Db,
+    name: &str,
+    content: &str,
+    language: Option<&str>,
+    tags: &[String],
+) -> Result<Snippet, DbError> {
+    create_snippet_from(
+        db,
+        &NewSnippet {
+            short_id: None,
+            short_id_format: ShortIdFormat::default(),
+            name,
+            content,
+            language,
+            tags,
+            password_hash: None,
+            encoding: ContentEncoding::Utf8,
+        },
+    )
+}
+
+/// A snippet to be created by [`create_snippet_from`] or [`create_snippets`].
+pub struct NewSnippet<'a> {
+    /// A custom short id; a random one is generated when `None`.
+    pub short_id: Option<&'a str>,
+    /// How the random short id is generated when `short_id` is `None`.
+    pub short_id_format: ShortIdFormat,
+    pub name: &'a str,
+    pub content: &'a str,
+    pub language: Option<&'a str>,
+    pub tags: &'a [String],
+    /// A hash from [`crate::password::hash_password`] gating who may view it.
+    pub password_hash: Option<&'a str>,
+    /// How `content` is encoded; base64 content is decoded before storing.
+    pub encoding: ContentEncoding,
+}
+
+/// Creates a single snippet with every option [`NewSnippet`] allows.
+pub fn create_snippet_from(db: &Db, snippet: &NewSnippet) -> Result<Snippet, DbError> {
+    let conn = db.get()?;
+    insert_snippet(&conn, snippet)
+}
+
+/// Creates every snippet in `snippets` in a single transaction, so either all
+/// of them are stored or none are.
+pub fn create_snippets(db: &Db, snippets: &[NewSnippet]) -> Result<Vec<Snippet>, DbError> {
+    create_snippets_capped(db, snippets, None)
+}
+
+/// Like [`create_snippet_from`] but keeps the store within `cap`; see
+/// [`create_snippets_capped`].
+pub fn create_snippet_capped(
+    db: &Db,
+    snippet: &NewSnippet,
+    cap: Option<SnippetCap>,
+) -> Result<Snippet, DbError> {
+    let mut created = create_snippets_capped(db, std::slice::from_ref(snippet), cap)?;
+    Ok(created.remove(0))
+}
+
+/// What happens when new snippets would take the store over its cap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Refuse with [`DbError::StoreFull`].
+    Reject,
+    /// Delete the oldest snippets to make room.
+    EvictOldest,
+}
+
+/// A limit on how many snippets are stored at once.
+#[derive(Clone, Copy, Debug)]
+pub struct SnippetCap {
+    pub max: i64,
+    pub overflow: Overflow,
+}
+
+/// Like [`create_snippets`] but keeps the store within `cap`. The count and
+/// any eviction happen in the same write transaction as the inserts, so
+/// concurrent creates can't overshoot it together.
+pub fn create_snippets_capped(
+    db: &Db,
+    snippets: &[NewSnippet],
+    cap: Option<SnippetCap>,
+) -> Result<Vec<Snippet>, DbError> {
+    let mut conn = db.get()?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    if let Some(cap) = cap {
+        let count: i64 = tx.query_row("SELECT COUNT(*) FROM snippets", [], |row| row.get(0))?;
+        let excess = count + snippets.len() as i64 - cap.max;
+        if excess > 0 {
+            if cap.overflow == Overflow::Reject || snippets.len() as i64 > cap.max {
+                return Err(DbError::StoreFull(cap.max));
+            }
+            tx.execute(
+                "DELETE FROM snippets WHERE id IN (SELECT id FROM snippets ORDER BY id ASC LIMIT ?1)",
+                params![excess],
+            )?;
+        }
+    }
+    let created = snippets
+        .iter()
+        .map(|snippet| insert_snippet(&tx, snippet))
+        .collect::<Result<Vec<_>, _>>()?;
+    tx.commit()?;
+    Ok(created)
+}
+
+/// Like [`create_snippet`] but stores the snippet under a caller-chosen short id.
+pub fn create_snippet_with_id(
+    db: &Db,
+    short_id: &str,
+    name: &str,
+    content: &str,
+    language: Option<&str>,
+) -> Result<Snippet, DbError> {
+    create_snippet_from(
+        db,
+        &NewSnippet {
+            short_id: Some(short_id),
+            short_id_format: ShortIdFormat::default(),
+            name,
+            content,
+            language,
+            tags: &[],
+            password_hash: None,
+            encoding: ContentEncoding::Utf8,
+        },
+    )
 }
 
 pub fn get_snippet_by_short_id(db: &Db, short_id: &str) -> Result<Option<Snippet>, DbError> {
-    let conn = db.lock().map_err(|_| DbError::LockPoisoned)?;
+    let conn = db.get()?;
     match conn.query_row(
-        "SELECT id, short_id, content, name FROM snippets WHERE short_id = ?1",
+        &format!("SELECT {} FROM snippets WHERE short_id = ?1", SNIPPET_COLUMNS),
         params![short_id],
-        |row| {
-            Ok(Snippet {
-                id: row.get(0)?,
-                short_id: row.get(1)?,
-                content: row.get(2)?,
-                name: row.get(3)?,
-            })
-        },
+        snippet_from_row,
     ) {
         Ok(snippet) => Ok(Some(snippet)),
         Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
@@ -95,25 +650,146 @@ pub fn get_snippet_by_short_id(db: &Db, short_id: &str) -> Result<Option<Snippet
     }
 }
 
+/// Counts one view of a snippet.
+pub fn record_view(db: &Db, short_id: &str) -> Result<(), DbError> {
+    let conn = db.get()?;
+    conn.execute(
+        "UPDATE snippets SET view_count = view_count + 1 WHERE short_id = ?1",
+        params![short_id],
+    )?;
+    Ok(())
+}
+
 pub fn get_all_snippets(db: &Db) -> Result<Vec<Snippet>, DbError> {
-    let conn = db.lock().map_err(|_| DbError::LockPoisoned)?;
-    let mut stmt = conn
-        .prepare("SELECT id, short_id, content, name FROM snippets ORDER BY id DESC")?;
-    let snippets = stmt.query_map([], |row| {
-        Ok(Snippet {
-            id: row.get(0)?,
-            short_id: row.get(1)?,
-            content: row.get(2)?,
-            name: row.get(3)?,
-        })
-    })?
-    .filter_map(|r| r.ok())
-    .collect();
+    get_snippets_page(db, -1, 0)
+}
+
+/// Returns up to `limit` snippets, newest first, skipping the first `offset`.
+/// A negative `limit` returns every remaining snippet.
+pub fn get_snippets_page(db: &Db, limit: i64, offset: i64) -> Result<Vec<Snippet>, DbError> {
+    get_snippets_in_range(db, None, None, None, limit, offset)
+}
+
+/// Returns every snippet tagged exactly `tag`, newest first.
+pub fn get_snippets_by_tag(db: &Db, tag: &str) -> Result<Vec<Snippet>, DbError> {
+    get_snippets_in_range(db, None, None, Some(tag.trim()), -1, 0)
+}
+
+/// Matches snippets created strictly after `?1` and strictly before `?2`, with
+/// either bound skipped when it is NULL.
+const IN_RANGE: &str = "(?1 IS NULL OR created_at > ?1) AND (?2 IS NULL OR created_at < ?2)";
+
+/// Matches snippets tagged exactly `?3`, or every snippet when it is NULL.
+const HAS_TAG: &str = "(?3 IS NULL OR instr(',' || tags || ',', ',' || ?3 || ',') > 0)";
+
+/// Like [`get_snippets_page`] but only over snippets created after `since`
+/// and before `before`, both in Unix seconds, and tagged `tag` when it is
+/// given. Snippets without a creation time are left out whenever either
+/// bound is given.
+pub fn get_snippets_in_range(
+    db: &Db,
+    since: Option<i64>,
+    before: Option<i64>,
+    tag: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<Snippet>, DbError> {
+    let conn = db.get()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM snippets WHERE {} AND {} ORDER BY id DESC LIMIT ?4 OFFSET ?5",
+        SNIPPET_COLUMNS, IN_RANGE, HAS_TAG
+    ))?;
+    let snippets = stmt
+        .query_map(params![since, before, tag, limit, offset], snippet_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
     Ok(snippets)
 }
 
+/// Calls `f` with each snippet matching the same filters as
+/// [`get_snippets_in_range`], newest first, reading rows one at a time rather
+/// than collecting them. Stops early once `f` returns `false`.
+pub fn for_each_snippet_in_range(
+    db: &Db,
+    since: Option<i64>,
+    before: Option<i64>,
+    tag: Option<&str>,
+    mut f: impl FnMut(Snippet) -> bool,
+) -> Result<(), DbError> {
+    let conn = db.get()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM snippets WHERE {} AND {} ORDER BY id DESC",
+        SNIPPET_COLUMNS, IN_RANGE, HAS_TAG
+    ))?;
+    let mut rows = stmt.query(params![since, before, tag])?;
+    while let Some(row) = rows.next()? {
+        if !f(snippet_from_row(row)?) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Like [`get_snippets_page`] but without loading snippet content.
+pub fn get_snippet_metas_page(db: &Db, limit: i64, offset: i64) -> Result<Vec<SnippetMeta>, DbError> {
+    get_snippet_metas_in_range(db, None, None, None, limit, offset)
+}
+
+/// Like [`get_snippets_in_range`] but without loading snippet content.
+pub fn get_snippet_metas_in_range(
+    db: &Db,
+    since: Option<i64>,
+    before: Option<i64>,
+    tag: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<SnippetMeta>, DbError> {
+    let conn = db.get()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, short_id, name, language, created_at, tags, updated_at, encoding FROM snippets WHERE {} AND {}
+         ORDER BY id DESC LIMIT ?4 OFFSET ?5",
+        IN_RANGE, HAS_TAG
+    ))?;
+    let metas = stmt
+        .query_map(params![since, before, tag, limit, offset], |row| {
+            Ok(SnippetMeta {
+                id: row.get(0)?,
+                short_id: row.get(1)?,
+                name: row.get(2)?,
+                language: row.get(3)?,
+                created_at: row.get(4)?,
+                tags: tags_from_column(row.get(5)?),
+                updated_at: row.get(6)?,
+                encoding: ContentEncoding::from_column(&row.get::<_, String>(7)?),
+                url: None,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(metas)
+}
+
+pub fn count_snippets(db: &Db) -> Result<i64, DbError> {
+    count_snippets_in_range(db, None, None, None)
+}
+
+/// Counts the snippets [`get_snippets_in_range`] would page through.
+pub fn count_snippets_in_range(
+    db: &Db,
+    since: Option<i64>,
+    before: Option<i64>,
+    tag: Option<&str>,
+) -> Result<i64, DbError> {
+    let conn = db.get()?;
+    Ok(conn.query_row(
+        &format!("SELECT COUNT(*) FROM snippets WHERE {} AND {}", IN_RANGE, HAS_TAG),
+        params![since, before, tag],
+        |row| row.get(0),
+    )?)
+}
+
 pub fn delete_snippet_by_short_id(db: &Db, short_id: &str) -> Result<bool, DbError> {
-    let conn = db.lock().map_err(|_| DbError::LockPoisoned)?;
+    let conn = db.get()?;
     let rows_affected = conn.execute(
         "DELETE FROM snippets WHERE short_id = ?1",
         params![short_id],
@@ -121,34 +797,293 @@ pub fn delete_snippet_by_short_id(db: &Db, short_id: &str) -> Result<bool, DbErr
     Ok(rows_affected > 0)
 }
 
+/// Replaces a snippet's fields. Its tags are kept when `tags` is `None`.
 pub fn update_snippet_by_short_id(
     db: &Db,
     short_id: &str,
     name: &str,
     content: &str,
+    encoding: ContentEncoding,
+    language: Option<&str>,
+    tags: Option<&[String]>,
 ) -> Result<Option<Snippet>, DbError> {
-    let conn = db.lock().map_err(|_| DbError::LockPoisoned)?;
+    let conn = db.get()?;
+    let (content, compressed) = encode_content(content, encoding)?;
     let rows_affected = conn.execute(
-        "UPDATE snippets SET name = ?1, content = ?2 WHERE short_id = ?3",
-        params![name, content, short_id],
+        "UPDATE snippets SET name = ?1, content = ?2, language = ?3, tags = COALESCE(?4, tags),
+         updated_at = ?6, compressed = ?7, encoding = ?8 WHERE short_id = ?5",
+        params![
+            name,
+            content,
+            language,
+            tags.map(join_tags),
+            short_id,
+            unix_now(),
+            compressed,
+            encoding.as_str()
+        ],
     )?;
     if rows_affected == 0 {
         return Ok(None);
     }
     match conn.query_row(
-        "SELECT id, short_id, content, name FROM snippets WHERE short_id = ?1",
+        &format!("SELECT {} FROM snippets WHERE short_id = ?1", SNIPPET_COLUMNS),
         params![short_id],
-        |row| {
-            Ok(Snippet {
-                id: row.get(0)?,
-                short_id: row.get(1)?,
-                content: row.get(2)?,
-                name: row.get(3)?,
-            })
-        },
+        snippet_from_row,
+    ) {
+        Ok(snippet) => Ok(Some(snippet)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(DbError::Sqlite(e)),
+    }
+}
+
+/// Changes only a snippet's name, keeping its short id and everything else.
+pub fn rename_snippet_by_short_id(db: &Db, short_id: &str, name: &str) -> Result<Option<Snippet>, DbError> {
+    let conn = db.get()?;
+    match conn.query_row(
+        &format!(
+            "UPDATE snippets SET name = ?1, updated_at = ?3 WHERE short_id = ?2 RETURNING {}",
+            SNIPPET_COLUMNS
+        ),
+        params![name, short_id, unix_now()],
+        snippet_from_row,
+    ) {
+        Ok(snippet) => Ok(Some(snippet)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(DbError::Sqlite(e)),
+    }
+}
+
+/// Updates only the fields that are `Some`, leaving the rest as they are.
+/// New content is always text, even for a snippet that held base64 bytes.
+pub fn patch_snippet_by_short_id(
+    db: &Db,
+    short_id: &str,
+    name: Option<&str>,
+    content: Option<&str>,
+) -> Result<Option<Snippet>, DbError> {
+    let conn = db.get()?;
+    let encoded = content.map(|c| encode_content(c, ContentEncoding::Utf8)).transpose()?;
+    match conn.query_row(
+        &format!(
+            "UPDATE snippets SET name = COALESCE(?1, name), content = COALESCE(?2, content),
+             compressed = COALESCE(?5, compressed), encoding = COALESCE(?6, encoding), updated_at = ?4
+             WHERE short_id = ?3 RETURNING {}",
+            SNIPPET_COLUMNS
+        ),
+        params![
+            name,
+            encoded.as_ref().map(|(content, _)| content),
+            short_id,
+            unix_now(),
+            encoded.as_ref().map(|(_, compressed)| compressed),
+            encoded.as_ref().map(|_| ContentEncoding::Utf8.as_str())
+        ],
+        snippet_from_row,
     ) {
         Ok(snippet) => Ok(Some(snippet)),
         Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
         Err(e) => Err(DbError::Sqlite(e)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A text snippet named `name` with a generated short id.
+    fn plain_snippet(name: &str) -> NewSnippet<'_> {
+        NewSnippet {
+            short_id: None,
+            short_id_format: ShortIdFormat::default(),
+            name,
+            content: "content",
+            language: None,
+            tags: &[],
+            password_hash: None,
+            encoding: ContentEncoding::Utf8,
+        }
+    }
+
+    /// Whether the row is marked compressed, and SQLite's type for its content.
+    fn stored_form(db: &Db, short_id: &str) -> (bool, String) {
+        db.get()
+            .unwrap()
+            .query_row(
+                "SELECT compressed, typeof(content) FROM snippets WHERE short_id = ?1",
+                params![short_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn file_databases_use_wal_and_checkpoint_truncates_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sipp.sqlite");
+        let db = init_db_at(&path).unwrap();
+        let mode: String = db.get().unwrap().query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(mode, "wal");
+
+        create_snippet(&db, "a.txt", "a", None, &[]).unwrap();
+        let wal = dir.path().join("sipp.sqlite-wal");
+        assert!(std::fs::metadata(&wal).unwrap().len() > 0);
+        checkpoint(&db).unwrap();
+        assert_eq!(std::fs::metadata(&wal).unwrap().len(), 0);
+        assert_eq!(get_all_snippets(&db).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn pool_serves_concurrent_reads_alongside_a_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = init_db_at(dir.path().join("sipp.sqlite")).unwrap();
+        let short_id = create_snippet(&db, "a.txt", "shared", None, &[]).unwrap().short_id;
+
+        std::thread::scope(|scope| {
+            let writer = scope.spawn(|| {
+                for n in 0..20 {
+                    create_snippet(&db, &format!("{}.txt", n), "written", None, &[]).unwrap();
+                }
+            });
+            let readers: Vec<_> = (0..50)
+                .map(|_| scope.spawn(|| get_snippet_by_short_id(&db, &short_id).unwrap().unwrap().content))
+                .collect();
+            for reader in readers {
+                assert_eq!(reader.join().unwrap(), "shared");
+            }
+            writer.join().unwrap();
+        });
+        assert_eq!(count_snippets(&db).unwrap(), 21);
+    }
+
+    #[test]
+    fn large_content_round_trips_compressed() {
+        let db = init_db_in_memory().unwrap();
+        let content = "fn main() { println!(\"héllo, 世界\"); }\n".repeat(500);
+        let created = create_snippet(&db, "big.rs", &content, None, &[]).unwrap();
+        assert_eq!(created.content, content);
+        assert_eq!(stored_form(&db, &created.short_id), (true, "blob".to_string()));
+        assert_eq!(get_snippet_by_short_id(&db, &created.short_id).unwrap().unwrap().content, content);
+        assert_eq!(get_all_snippets(&db).unwrap()[0].content, content);
+
+        let edited = content.replace("main", "start");
+        let updated = update_snippet_by_short_id(&db, &created.short_id, "big.rs", &edited, ContentEncoding::Utf8, None, None).unwrap();
+        assert_eq!(updated.unwrap().content, edited);
+        assert_eq!(stored_form(&db, &created.short_id), (true, "blob".to_string()));
+
+        // Shrinking below the threshold goes back to plain text
+        let patched = patch_snippet_by_short_id(&db, &created.short_id, None, Some("tiny")).unwrap();
+        assert_eq!(patched.unwrap().content, "tiny");
+        assert_eq!(stored_form(&db, &created.short_id), (false, "text".to_string()));
+    }
+
+    #[test]
+    fn gzip_encoding_shrinks_content_and_decodes_back() {
+        let content = "let total = items.iter().map(|item| item.price).sum();\n".repeat(200);
+        assert!(content.len() >= COMPRESS_THRESHOLD);
+        let (stored, compressed) = encode_content(&content, ContentEncoding::Utf8).unwrap();
+        assert!(compressed);
+        let rusqlite::types::Value::Blob(bytes) = stored else {
+            panic!("compressed content should be stored as a blob");
+        };
+        assert!(bytes.len() < content.len());
+        assert_eq!(decode_content(&bytes).unwrap(), content);
+
+        let db = init_db_in_memory().unwrap();
+        let created = create_snippet(&db, "sum.rs", &content, None, &[]).unwrap();
+        let stored_len: i64 = db
+            .get()
+            .unwrap()
+            .query_row("SELECT length(content) FROM snippets WHERE short_id = ?1", [&created.short_id], |row| row.get(0))
+            .unwrap();
+        assert!((stored_len as usize) < content.len());
+        assert_eq!(get_snippet_by_short_id(&db, &created.short_id).unwrap().unwrap().content, content);
+    }
+
+    #[test]
+    fn colliding_short_ids_are_regenerated() {
+        let db = init_db_in_memory().unwrap();
+        for taken in ["taken1", "taken2"] {
+            let snippet = NewSnippet { short_id: Some(taken), ..plain_snippet("seed.txt") };
+            create_snippet_from(&db, &snippet).unwrap();
+        }
+
+        let conn = db.get().unwrap();
+        let mut ids = ["taken1", "taken2", "fresh"].into_iter();
+        let mut generated = 0;
+        let created = insert_snippet_with(&conn, &plain_snippet("new.txt"), || {
+            generated += 1;
+            ids.next().unwrap().to_string()
+        })
+        .unwrap();
+        assert_eq!((created.short_id.as_str(), generated), ("fresh", 3));
+
+        // Gives up once every attempt has collided
+        let mut generated = 0;
+        let exhausted = insert_snippet_with(&conn, &plain_snippet("new.txt"), || {
+            generated += 1;
+            "taken1".to_string()
+        });
+        assert!(exhausted.is_err());
+        assert_eq!(generated, ShortIdFormat::default().attempts());
+        drop(conn);
+        assert_eq!(count_snippets(&db).unwrap(), 3);
+    }
+
+    #[test]
+    fn batches_are_created_in_one_transaction() {
+        let db = init_db_in_memory().unwrap();
+        let created = create_snippets(&db, &[plain_snippet("a.txt"), plain_snippet("b.txt")]).unwrap();
+        let names: Vec<_> = created.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["a.txt", "b.txt"]);
+        assert_ne!(created[0].short_id, created[1].short_id);
+        assert_eq!(count_snippets(&db).unwrap(), 2);
+
+        // A failing item rolls back the ones before it
+        let taken = NewSnippet { short_id: Some(created[0].short_id.as_str()), ..plain_snippet("c.txt") };
+        let result = create_snippets(&db, &[plain_snippet("d.txt"), taken]);
+        assert!(matches!(result, Err(DbError::ShortIdTaken(_))));
+        assert_eq!(count_snippets(&db).unwrap(), 2);
+    }
+
+    #[test]
+    fn small_content_stays_uncompressed() {
+        let db = init_db_in_memory().unwrap();
+        let content = "a".repeat(COMPRESS_THRESHOLD - 1);
+        let created = create_snippet(&db, "small.txt", &content, None, &[]).unwrap();
+        assert_eq!(stored_form(&db, &created.short_id), (false, "text".to_string()));
+        assert_eq!(get_snippet_by_short_id(&db, &created.short_id).unwrap().unwrap().content, content);
+
+        // Renaming leaves the content column alone
+        rename_snippet_by_short_id(&db, &created.short_id, "renamed.txt").unwrap();
+        assert_eq!(stored_form(&db, &created.short_id), (false, "text".to_string()));
+    }
+
+    #[test]
+    fn base64_content_is_stored_as_bytes() {
+        let db = init_db_in_memory().unwrap();
+        let snippet = NewSnippet {
+            short_id: None,
+            short_id_format: ShortIdFormat::default(),
+            name: "blob.bin",
+            content: "AP/+\nAQ==",
+            language: None,
+            tags: &[],
+            password_hash: None,
+            encoding: ContentEncoding::Base64,
+        };
+        let created = create_snippet_from(&db, &snippet).unwrap();
+        assert_eq!(stored_form(&db, &created.short_id), (false, "blob".to_string()));
+        let fetched = get_snippet_by_short_id(&db, &created.short_id).unwrap().unwrap();
+        assert_eq!(fetched.encoding, ContentEncoding::Base64);
+        assert_eq!(fetched.content, "AP/+AQ==");
+        assert_eq!(fetched.raw_bytes(), [0x00, 0xff, 0xfe, 0x01]);
+
+        let invalid = NewSnippet { content: "not base64!", ..snippet };
+        assert!(matches!(create_snippet_from(&db, &invalid), Err(DbError::InvalidBase64)));
+
+        // Patching in text turns it back into a text snippet
+        let patched = patch_snippet_by_short_id(&db, &created.short_id, None, Some("text")).unwrap().unwrap();
+        assert_eq!((patched.encoding, patched.content.as_str()), (ContentEncoding::Utf8, "text"));
+    }
+}
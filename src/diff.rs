@@ -0,0 +1,114 @@
+use similar::{Algorithm, ChangeTag};
+use std::time::{Duration, Instant};
+
+/// How long the diff may search for the smallest edit before settling for a
+/// rougher one, so a huge paste can't freeze the TUI.
+const DIFF_DEADLINE: Duration = Duration::from_millis(200);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change<'a> {
+    pub tag: ChangeTag,
+    pub line: &'a str,
+}
+
+/// A run of changes with the unchanged lines around it, as in a unified
+/// diff. Starts are 1-based line numbers.
+#[derive(Debug)]
+pub struct Hunk<'a> {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub changes: Vec<Change<'a>>,
+}
+
+/// The line changes from `old` to `new` grouped into hunks with up to
+/// `context` unchanged lines on either side. Lines are compared without
+/// their endings, so a missing final newline or CRLF endings don't count as
+/// changes. Empty when the texts have the same lines.
+pub fn hunks<'a>(old: &'a str, new: &'a str, context: usize) -> Vec<Hunk<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let deadline = Instant::now() + DIFF_DEADLINE;
+    let ops = similar::capture_diff_slices_deadline(Algorithm::Myers, &old, &new, Some(deadline));
+    similar::group_diff_ops(ops, context)
+        .into_iter()
+        .map(|group| {
+            let (first, last) = (&group[0], &group[group.len() - 1]);
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+            let changes = group
+                .iter()
+                .flat_map(|op| op.iter_changes(&old, &new))
+                .map(|change| Change { tag: change.tag(), line: change.value() })
+                .collect();
+            Hunk {
+                old_start: old_range.start + 1,
+                old_len: old_range.len(),
+                new_start: new_range.start + 1,
+                new_len: new_range.len(),
+                changes,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes<'a>(old: &'a str, new: &'a str) -> Vec<(ChangeTag, &'a str)> {
+        hunks(old, new, 100)
+            .into_iter()
+            .flat_map(|hunk| hunk.changes)
+            .map(|c| (c.tag, c.line))
+            .collect()
+    }
+
+    #[test]
+    fn changes_keep_common_lines_in_order() {
+        assert_eq!(
+            changes("a\nb\nc\nd", "a\nc\nx\nd"),
+            [
+                (ChangeTag::Equal, "a"),
+                (ChangeTag::Delete, "b"),
+                (ChangeTag::Equal, "c"),
+                (ChangeTag::Insert, "x"),
+                (ChangeTag::Equal, "d")
+            ]
+        );
+    }
+
+    #[test]
+    fn hunks_split_distant_edits_and_number_lines() {
+        let old: String = (1..=20).map(|n| format!("{}\n", n)).collect();
+        let new: String = (1..=20)
+            .filter(|&n| n != 18)
+            .map(|n| if n == 2 { "two\n".to_string() } else { format!("{}\n", n) })
+            .collect();
+        let hunks = hunks(&old, &new, 2);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].old_start, hunks[0].old_len, hunks[0].new_start, hunks[0].new_len), (1, 4, 1, 4));
+        assert_eq!((hunks[1].old_start, hunks[1].old_len, hunks[1].new_start, hunks[1].new_len), (16, 5, 16, 4));
+    }
+
+    #[test]
+    fn empty_texts_diff_as_all_added_or_removed() {
+        assert!(hunks("", "", 3).is_empty());
+        let added = hunks("", "a\nb\n", 3);
+        assert_eq!((added[0].old_start, added[0].old_len, added[0].new_start, added[0].new_len), (1, 0, 1, 2));
+        assert_eq!(changes("", "a\nb\n"), [(ChangeTag::Insert, "a"), (ChangeTag::Insert, "b")]);
+        assert_eq!(changes("a\n", ""), [(ChangeTag::Delete, "a")]);
+    }
+
+    #[test]
+    fn line_endings_are_not_changes() {
+        assert!(hunks("same\n", "same", 3).is_empty());
+        assert!(hunks("a\nb", "a\nb\n", 3).is_empty());
+        assert!(hunks("a\r\nb\r\n", "a\nb\n", 3).is_empty());
+        assert_eq!(
+            changes("a\nb\n", "a\nc"),
+            [(ChangeTag::Equal, "a"), (ChangeTag::Delete, "b"), (ChangeTag::Insert, "c")]
+        );
+    }
+}
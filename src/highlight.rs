@@ -1,41 +1,313 @@
 use std::io::Cursor;
+use syntect::easy::HighlightLines;
 use syntect::highlighting::{Theme, ThemeSet};
-use syntect::html::highlighted_html_for_string;
-use syntect::parsing::SyntaxSet;
+use syntect::html::{IncludeBackground, highlighted_html_for_string, styled_line_to_highlighted_html};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+/// Theme used by the web view when none is configured.
+pub const DEFAULT_WEB_THEME: &str = "darkmatter";
+/// Theme used by the TUI when none is configured; it follows the terminal palette.
+pub const DEFAULT_TUI_THEME: &str = "ansi";
+
+/// Returns syntect's bundled themes plus the ones shipped with sipp.
+pub fn theme_set() -> ThemeSet {
+    let mut themes = ThemeSet::load_defaults();
+    for (name, data) in [
+        ("darkmatter", &include_bytes!("darkmatter.tmTheme")[..]),
+        ("ansi", &include_bytes!("ansi.tmTheme")[..]),
+    ] {
+        let theme = ThemeSet::load_from_reader(&mut Cursor::new(data))
+            .unwrap_or_else(|_| panic!("failed to load {} theme", name));
+        themes.themes.insert(name.to_string(), theme);
+    }
+    themes
+}
+
+/// Finds the theme called `name` (case-insensitive), accepting `dark` and
+/// `light` as shorthands.
+fn find_theme<'a>(themes: &'a ThemeSet, name: &str) -> Option<(&'a String, &'a Theme)> {
+    let name = match name.trim() {
+        n if n.eq_ignore_ascii_case("dark") => "darkmatter",
+        n if n.eq_ignore_ascii_case("light") => "InspiredGitHub",
+        n => n,
+    };
+    themes.themes.iter().find(|(key, _)| key.eq_ignore_ascii_case(name))
+}
+
+/// Loads the theme called `name`, or `default` when it is unset or unknown.
+pub fn load_theme(name: Option<&str>, default: &str) -> Theme {
+    let themes = theme_set();
+    name.and_then(|n| find_theme(&themes, n))
+        .map(|(_, theme)| theme)
+        .or_else(|| themes.themes.get(default))
+        .cloned()
+        .unwrap_or_else(|| panic!("missing bundled theme {}", default))
+}
 
 pub struct Highlighter {
     syntax_set: SyntaxSet,
-    theme: Theme,
+    themes: ThemeSet,
+    default_theme: String,
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Highlighter {
     pub fn new() -> Self {
-        let theme_data = include_bytes!("darkmatter.tmTheme");
-        let theme = ThemeSet::load_from_reader(&mut Cursor::new(&theme_data[..]))
-            .expect("failed to load darkmatter theme");
+        Self::with_theme(DEFAULT_WEB_THEME)
+    }
+
+    /// Uses the bundled theme called `name` by default, falling back to the
+    /// default web theme.
+    pub fn with_theme(name: &str) -> Self {
+        let themes = theme_set();
+        let default_theme = find_theme(&themes, name)
+            .map(|(key, _)| key.clone())
+            .unwrap_or_else(|| DEFAULT_WEB_THEME.to_string());
         Self {
             syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme,
+            themes,
+            default_theme,
         }
     }
 
-    pub fn highlight(&self, name: &str, content: &str) -> String {
-        let raw_ext = name.rsplit('.').next().unwrap_or("");
-        let ext = match raw_ext {
-            "ts" | "tsx" | "jsx" => "js",
-            other => other,
+    /// Name of the theme used when a request doesn't ask for one.
+    pub fn default_theme(&self) -> &str {
+        &self.default_theme
+    }
+
+    /// Returns the bundled name of the theme `theme_name` resolves to, so
+    /// aliases and unknown names map to the theme actually used.
+    pub fn resolve_theme(&self, theme_name: &str) -> &str {
+        find_theme(&self.themes, theme_name)
+            .map(|(key, _)| key.as_str())
+            .unwrap_or(&self.default_theme)
+    }
+
+    /// Returns the name of the syntax `token` (e.g. `rust` or `py`) selects,
+    /// or plain text when no bundled syntax matches it.
+    pub fn resolve_language(&self, token: &str) -> &str {
+        self.syntax_set
+            .find_syntax_by_token(token.trim())
+            .or_else(|| self.syntax_set.find_syntax_by_name(token.trim()))
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text())
+            .name
+            .as_str()
+    }
+
+    fn theme(&self, theme_name: &str) -> &Theme {
+        find_theme(&self.themes, theme_name)
+            .map(|(_, theme)| theme)
+            .unwrap_or(&self.themes.themes[&self.default_theme])
+    }
+
+    pub fn highlight(&self, name: &str, language: Option<&str>, content: &str) -> String {
+        self.highlight_with_theme(name, language, content, &self.default_theme)
+    }
+
+    /// Like [`Highlighter::highlight`] but with the theme called `theme_name`,
+    /// falling back to the default theme when it is unknown.
+    pub fn highlight_with_theme(
+        &self,
+        name: &str,
+        language: Option<&str>,
+        content: &str,
+        theme_name: &str,
+    ) -> String {
+        let syntax = find_syntax(&self.syntax_set, name, language, content);
+        highlighted_html_for_string(content, &self.syntax_set, syntax, self.theme(theme_name))
+            .unwrap_or_else(|_| format!("<pre>{}</pre>", escape_html(content)))
+    }
+
+    /// Like [`Highlighter::highlight_with_theme`] but wraps each line in an
+    /// element with `id="L{n}"` and a gutter link to that line, so pages can
+    /// anchor to `#L42` or `#L10-L20`.
+    pub fn highlight_with_line_numbers(
+        &self,
+        name: &str,
+        language: Option<&str>,
+        content: &str,
+        theme_name: &str,
+    ) -> String {
+        let theme = self.theme(theme_name);
+        let syntax = find_syntax(&self.syntax_set, name, language, content);
+        let mut highlighter = HighlightLines::new(syntax, theme);
+        let mut html = match theme.settings.background {
+            Some(bg) => format!(
+                "<pre class=\"numbered\" style=\"background-color:#{:02x}{:02x}{:02x};\">",
+                bg.r, bg.g, bg.b
+            ),
+            None => "<pre class=\"numbered\">".to_string(),
         };
-        let syntax = self
-            .syntax_set
-            .find_syntax_by_extension(ext)
-            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
-        highlighted_html_for_string(content, &self.syntax_set, syntax, &self.theme)
-            .unwrap_or_else(|_| {
-                let escaped = content
-                    .replace('&', "&amp;")
-                    .replace('<', "&lt;")
-                    .replace('>', "&gt;");
-                format!("<pre>{}</pre>", escaped)
-            })
+        for (i, line) in LinesWithEndings::from(content).enumerate() {
+            // The syntax set expects line endings, but each line is its own block element
+            let line_html = highlighter
+                .highlight_line(line, &self.syntax_set)
+                .ok()
+                .and_then(|regions| {
+                    let regions: Vec<_> = regions
+                        .into_iter()
+                        .map(|(style, text)| (style, text.trim_end_matches(['\n', '\r'])))
+                        .collect();
+                    styled_line_to_highlighted_html(&regions, IncludeBackground::No).ok()
+                })
+                .unwrap_or_else(|| escape_html(line.trim_end_matches(['\n', '\r'])));
+            html.push_str(&format!(
+                "<span class=\"line\" id=\"L{n}\"><a class=\"line-number\" href=\"#L{n}\">{n}</a><span class=\"line-content\">{}</span></span>",
+                line_html,
+                n = i + 1,
+            ));
+        }
+        html.push_str("</pre>");
+        html
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Resolves the syntax for a snippet, preferring an explicit language token
+/// or name, then the file extension in `name`, then the first line of
+/// `content` (e.g. a `#!/bin/bash` shebang).
+pub fn find_syntax<'a>(
+    syntax_set: &'a SyntaxSet,
+    name: &str,
+    language: Option<&str>,
+    content: &str,
+) -> &'a SyntaxReference {
+    let explicit = language
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .and_then(|l| {
+            syntax_set
+                .find_syntax_by_token(l)
+                .or_else(|| syntax_set.find_syntax_by_name(l))
+        });
+    if let Some(syntax) = explicit {
+        return syntax;
+    }
+    let plain_text = syntax_set.find_syntax_plain_text();
+    let by_name = resolve_syntax(syntax_set, name);
+    if by_name.name != plain_text.name {
+        return by_name;
+    }
+    syntax_set
+        .find_syntax_by_first_line(content.lines().next().unwrap_or(""))
+        .unwrap_or(plain_text)
+}
+
+/// Info string for a markdown code fence around `syntax`: its lowercased name
+/// when that is a plain word (`rust`), otherwise its first extension (`cpp`).
+/// Plain text gets no tag.
+pub fn fence_language(syntax_set: &SyntaxSet, syntax: &SyntaxReference) -> Option<String> {
+    if syntax.name == syntax_set.find_syntax_plain_text().name {
+        return None;
+    }
+    if syntax.name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Some(syntax.name.to_ascii_lowercase());
+    }
+    syntax.file_extensions.first().cloned()
+}
+
+/// Wraps `content` in a markdown code fence tagged with `language`, using a
+/// fence longer than any run of backticks inside it.
+pub fn markdown_code_block(content: &str, language: Option<&str>) -> String {
+    let longest_run = content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let newline = if content.ends_with('\n') { "" } else { "\n" };
+    format!("{fence}{}\n{content}{newline}{fence}", language.unwrap_or(""))
+}
+
+/// Extensions the bundled syntaxes don't list, mapped to the extension of the
+/// closest syntax that is bundled.
+const EXTENSION_ALIASES: &[(&str, &str)] = &[
+    ("ts", "js"),
+    ("tsx", "js"),
+    ("jsx", "js"),
+    ("mjs", "js"),
+    ("cjs", "js"),
+    ("jsonc", "json"),
+    ("json5", "json"),
+    ("h", "c"),
+    ("scss", "css"),
+    ("less", "css"),
+    ("vue", "html"),
+    ("svelte", "html"),
+    ("mdx", "md"),
+    ("ksh", "sh"),
+    ("plist", "xml"),
+    ("xaml", "xml"),
+];
+
+/// Resolves the syntax for a file name from its extension, or plain text when
+/// the extension is unknown.
+pub fn resolve_syntax<'a>(syntax_set: &'a SyntaxSet, name: &str) -> &'a SyntaxReference {
+    let raw_ext = name.rsplit('.').next().unwrap_or("");
+    let ext = EXTENSION_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == raw_ext)
+        .map_or(raw_ext, |(_, target)| target);
+    syntax_set
+        .find_syntax_by_extension(ext)
+        .unwrap_or_else(|| syntax_set.find_syntax_plain_text())
+}
+
+/// Removes ANSI escape sequences, such as the colors in captured terminal
+/// output, and keeps the text they decorate.
+pub fn strip_ansi(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => match chars.next() {
+                Some('[') => skip_csi(&mut chars),
+                Some(']' | 'P' | 'X' | '^' | '_') => skip_string(&mut chars),
+                // Sequences like `ESC ( B`: intermediate bytes, then one final byte
+                Some(' '..='/') => {
+                    while chars.next_if(|c| (' '..='/').contains(c)).is_some() {}
+                    chars.next();
+                }
+                // Everything else is two characters long, e.g. `ESC 7`
+                _ => {}
+            },
+            '\u{9b}' => skip_csi(&mut chars),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Skips the parameters of a control sequence up to its final byte.
+fn skip_csi(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    for c in chars.by_ref() {
+        if ('@'..='~').contains(&c) {
+            break;
+        }
+    }
+}
+
+/// Skips an OSC or similar string, which ends at BEL or `ESC \`.
+fn skip_string(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{7}' | '\u{9c}' => break,
+            '\u{1b}' => {
+                chars.next_if_eq(&'\\');
+                break;
+            }
+            _ => {}
+        }
     }
 }
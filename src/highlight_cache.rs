@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+struct Entry {
+    source_hash: u64,
+    html: String,
+    last_used: u64,
+}
+
+impl Entry {
+    fn size(&self, key: &(String, String)) -> usize {
+        key.0.len() + key.1.len() + self.html.len()
+    }
+}
+
+struct Inner {
+    entries: HashMap<(String, String), Entry>,
+    total_bytes: usize,
+    tick: u64,
+}
+
+/// A least-recently-used cache of highlighted HTML keyed by snippet short id
+/// and theme, bounded by the total size of the cached HTML.
+pub struct HighlightCache {
+    max_bytes: usize,
+    inner: Mutex<Inner>,
+}
+
+/// Hashes everything that affects highlighting, so edits made behind the
+/// cache's back are never served stale.
+pub fn source_hash(name: &str, language: Option<&str>, content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    language.hash(&mut hasher);
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl HighlightCache {
+    pub fn new(max_bytes: usize) -> Self {
+        HighlightCache {
+            max_bytes,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                total_bytes: 0,
+                tick: 0,
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes > 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Returns the cached HTML for `short_id` in `theme`, or renders it with
+    /// `render` and caches the result. The lock is not held while rendering.
+    pub fn get_or_render(
+        &self,
+        short_id: &str,
+        theme: &str,
+        source_hash: u64,
+        render: impl FnOnce() -> String,
+    ) -> String {
+        if !self.is_enabled() {
+            return render();
+        }
+        let key = (short_id.to_string(), theme.to_string());
+        {
+            let mut inner = self.lock();
+            inner.tick += 1;
+            let tick = inner.tick;
+            if let Some(entry) = inner.entries.get_mut(&key)
+                && entry.source_hash == source_hash
+            {
+                entry.last_used = tick;
+                return entry.html.clone();
+            }
+        }
+
+        let html = render();
+        let entry = Entry {
+            source_hash,
+            html: html.clone(),
+            last_used: 0,
+        };
+        let size = entry.size(&key);
+        if size > self.max_bytes {
+            return html;
+        }
+
+        let mut inner = self.lock();
+        inner.tick += 1;
+        let entry = Entry {
+            last_used: inner.tick,
+            ..entry
+        };
+        if let Some(old) = inner.entries.remove(&key) {
+            inner.total_bytes -= old.size(&key);
+        }
+        while inner.total_bytes + size > self.max_bytes {
+            let Some(oldest) = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            if let Some(old) = inner.entries.remove(&oldest) {
+                inner.total_bytes -= old.size(&oldest);
+            }
+        }
+        inner.total_bytes += size;
+        inner.entries.insert(key, entry);
+        html
+    }
+
+    /// Drops every cached rendering of `short_id`.
+    pub fn invalidate(&self, short_id: &str) {
+        let mut inner = self.lock();
+        let mut freed = 0;
+        inner.entries.retain(|key, entry| {
+            if key.0 == short_id {
+                freed += entry.size(key);
+                false
+            } else {
+                true
+            }
+        });
+        inner.total_bytes -= freed;
+    }
+}
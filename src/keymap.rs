@@ -0,0 +1,595 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Something a key can do in the snippet list or the content pane. The
+/// names in `[keybindings]` are the snake_case forms from [`Action::name`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+    Quit,
+    Back,
+    Open,
+    MoveDown,
+    MoveUp,
+    HalfPageDown,
+    HalfPageUp,
+    PageDown,
+    PageUp,
+    Top,
+    Bottom,
+    ScrollLeft,
+    ScrollRight,
+    ToggleWrap,
+    ToggleLineNumbers,
+    Copy,
+    CopyLink,
+    CopyMarkdown,
+    CopyLine,
+    Edit,
+    EditExternal,
+    OpenInBrowser,
+    Delete,
+    Undo,
+    Create,
+    Search,
+    Sort,
+    TagFilter,
+    TogglePreview,
+    Refresh,
+    Help,
+}
+
+/// Where a binding applies: the snippet list or the focused content pane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Context {
+    List,
+    Content,
+}
+
+impl Action {
+    pub const ALL: [Action; 31] = [
+        Action::Quit,
+        Action::Back,
+        Action::Open,
+        Action::MoveDown,
+        Action::MoveUp,
+        Action::HalfPageDown,
+        Action::HalfPageUp,
+        Action::PageDown,
+        Action::PageUp,
+        Action::Top,
+        Action::Bottom,
+        Action::ScrollLeft,
+        Action::ScrollRight,
+        Action::ToggleWrap,
+        Action::ToggleLineNumbers,
+        Action::Copy,
+        Action::CopyLink,
+        Action::CopyMarkdown,
+        Action::CopyLine,
+        Action::Edit,
+        Action::EditExternal,
+        Action::OpenInBrowser,
+        Action::Delete,
+        Action::Undo,
+        Action::Create,
+        Action::Search,
+        Action::Sort,
+        Action::TagFilter,
+        Action::TogglePreview,
+        Action::Refresh,
+        Action::Help,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Back => "back",
+            Action::Open => "open",
+            Action::MoveDown => "move_down",
+            Action::MoveUp => "move_up",
+            Action::HalfPageDown => "half_page_down",
+            Action::HalfPageUp => "half_page_up",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::ScrollLeft => "scroll_left",
+            Action::ScrollRight => "scroll_right",
+            Action::ToggleWrap => "toggle_wrap",
+            Action::ToggleLineNumbers => "toggle_line_numbers",
+            Action::Copy => "copy",
+            Action::CopyLink => "copy_link",
+            Action::CopyMarkdown => "copy_markdown",
+            Action::CopyLine => "copy_line",
+            Action::Edit => "edit",
+            Action::EditExternal => "edit_external",
+            Action::OpenInBrowser => "open_in_browser",
+            Action::Delete => "delete",
+            Action::Undo => "undo",
+            Action::Create => "create",
+            Action::Search => "search",
+            Action::Sort => "sort",
+            Action::TagFilter => "tag_filter",
+            Action::TogglePreview => "toggle_preview",
+            Action::Refresh => "refresh",
+            Action::Help => "help",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    /// Whether the action does anything in `context`.
+    pub fn applies_to(self, context: Context) -> bool {
+        match self {
+            Action::Quit
+            | Action::Open
+            | Action::Delete
+            | Action::Undo
+            | Action::Create
+            | Action::Search
+            | Action::Sort
+            | Action::TagFilter
+            | Action::TogglePreview
+            | Action::Refresh => context == Context::List,
+            Action::Back
+            | Action::HalfPageDown
+            | Action::HalfPageUp
+            | Action::PageDown
+            | Action::PageUp
+            | Action::Top
+            | Action::Bottom
+            | Action::ScrollLeft
+            | Action::ScrollRight
+            | Action::ToggleWrap
+            | Action::ToggleLineNumbers
+            | Action::CopyLine => context == Context::Content,
+            Action::MoveDown
+            | Action::MoveUp
+            | Action::Copy
+            | Action::CopyLink
+            | Action::CopyMarkdown
+            | Action::Edit
+            | Action::EditExternal
+            | Action::OpenInBrowser
+            | Action::Help => true,
+        }
+    }
+
+    /// The keys bound when the config doesn't mention the action.
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q", "esc"],
+            Action::Back => &["esc", "q", "h", "space"],
+            Action::Open => &["enter", "l"],
+            Action::MoveDown => &["j", "down"],
+            Action::MoveUp => &["k", "up"],
+            Action::HalfPageDown => &["ctrl+d"],
+            Action::HalfPageUp => &["ctrl+u"],
+            Action::PageDown => &["pgdn"],
+            Action::PageUp => &["pgup"],
+            Action::Top => &["g", "home"],
+            Action::Bottom => &["G", "end"],
+            Action::ScrollLeft => &["left"],
+            Action::ScrollRight => &["right"],
+            Action::ToggleWrap => &["w"],
+            Action::ToggleLineNumbers => &["n"],
+            Action::Copy => &["y"],
+            Action::CopyLink => &["Y"],
+            Action::CopyMarkdown => &["m"],
+            Action::CopyLine => &["L"],
+            Action::Edit => &["e"],
+            Action::EditExternal => &["E"],
+            Action::OpenInBrowser => &["o"],
+            Action::Delete => &["d"],
+            Action::Undo => &["u"],
+            Action::Create => &["c"],
+            Action::Search => &["/"],
+            Action::Sort => &["s"],
+            Action::TagFilter => &["t"],
+            Action::TogglePreview => &["p"],
+            Action::Refresh => &["r"],
+            Action::Help => &["?"],
+        }
+    }
+}
+
+/// A single key press with the modifiers that matter for matching.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// The key as bindings see it. Shift is dropped from characters, whose
+    /// case already carries it, so `G` matches however the terminal reports it.
+    pub fn from_event(event: &KeyEvent) -> Self {
+        let mut modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if let KeyCode::Char(_) = event.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Key { code: event.code, modifiers }
+    }
+
+    fn named(name: &str) -> Option<KeyCode> {
+        let code = match name.to_ascii_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pgup" | "pageup" => KeyCode::PageUp,
+            "pgdn" | "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            f if f.len() > 1 && f.starts_with('f') => KeyCode::F(f[1..].parse().ok().filter(|n| (1..=12).contains(n))?),
+            _ => return None,
+        };
+        Some(code)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) if self.modifiers.contains(KeyModifiers::CONTROL) => write!(f, "{}", c.to_ascii_uppercase()),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Delete => write!(f, "Del"),
+            KeyCode::Insert => write!(f, "Ins"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::Home => write!(f, "Home"),
+            KeyCode::End => write!(f, "End"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            _ => write!(f, "?"),
+        }
+    }
+}
+
+/// Parses a key string such as `j`, `G`, `ctrl+d`, `pgdn`, or `space`. A run
+/// of plain characters that isn't a key name, such as `gg`, is a sequence
+/// pressed one key after another.
+fn parse_keys(input: &str) -> Result<Vec<Key>, String> {
+    let mut rest = input.trim();
+    if rest.is_empty() {
+        return Err("empty key".to_string());
+    }
+    let mut modifiers = KeyModifiers::NONE;
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        let prefixes = [("ctrl+", KeyModifiers::CONTROL), ("alt+", KeyModifiers::ALT), ("shift+", KeyModifiers::SHIFT)];
+        let Some((prefix, modifier)) = prefixes.into_iter().find(|(p, _)| lower.starts_with(p) && rest.len() > p.len())
+        else {
+            break;
+        };
+        modifiers |= modifier;
+        rest = &rest[prefix.len()..];
+    }
+    if let Some(code) = Key::named(rest) {
+        return Ok(vec![Key { code, modifiers }]);
+    }
+    let chars: Vec<char> = rest.chars().collect();
+    if chars.iter().any(|c| c.is_whitespace()) {
+        return Err(format!("unknown key '{}'", input.trim()));
+    }
+    match chars.as_slice() {
+        [c] => {
+            // Shift is carried by the character's case, and Ctrl/Alt
+            // combinations arrive lowercase
+            let c = if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) { c.to_ascii_lowercase() } else { *c };
+            if modifiers.contains(KeyModifiers::SHIFT) && c.is_ascii_alphabetic() {
+                modifiers.remove(KeyModifiers::SHIFT);
+                return Ok(vec![Key { code: KeyCode::Char(c.to_ascii_uppercase()), modifiers }]);
+            }
+            Ok(vec![Key { code: KeyCode::Char(c), modifiers }])
+        }
+        _ if modifiers.is_empty() => Ok(chars.into_iter().map(|c| Key { code: KeyCode::Char(c), modifiers }).collect()),
+        _ => Err(format!("unknown key '{}'", input.trim())),
+    }
+}
+
+/// What the keys pressed so far add up to.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Lookup {
+    Action(Action),
+    /// The start of a longer binding; wait for the next key.
+    Pending,
+    None,
+}
+
+/// Which keys trigger which [`Action`]s, built from the defaults and the
+/// `[keybindings]` section of the config.
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<Vec<Key>>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let keys = action.default_keys().iter().filter_map(|k| parse_keys(k).ok()).collect();
+                (action, keys)
+            })
+            .collect();
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    /// Applies `config` on top of the defaults. Each entry replaces the
+    /// action's default keys with a key string or a list of them; an empty
+    /// list unbinds it. A configured key also comes off any other action it
+    /// was bound to by default. Problems are returned as warnings and the
+    /// offending entry or key is skipped.
+    pub fn from_config(config: &BTreeMap<String, toml::Value>) -> (Keymap, Vec<String>) {
+        let mut keymap = Keymap::default();
+        let mut warnings = Vec::new();
+        let mut configured: BTreeMap<Action, Vec<Vec<Key>>> = BTreeMap::new();
+        for (name, value) in config {
+            let Some(action) = Action::from_name(name) else {
+                warnings.push(format!("unknown keybinding action '{}'", name));
+                continue;
+            };
+            let strings: Vec<&str> = match value {
+                toml::Value::String(s) => vec![s.as_str()],
+                toml::Value::Array(items) => {
+                    let strings: Option<Vec<&str>> = items.iter().map(toml::Value::as_str).collect();
+                    match strings {
+                        Some(strings) => strings,
+                        None => {
+                            warnings.push(format!("keybinding '{}' must be a key or a list of keys", name));
+                            continue;
+                        }
+                    }
+                }
+                _ => {
+                    warnings.push(format!("keybinding '{}' must be a key or a list of keys", name));
+                    continue;
+                }
+            };
+            let mut keys = Vec::new();
+            for s in strings {
+                match parse_keys(s) {
+                    Ok(k) => keys.push(k),
+                    Err(e) => warnings.push(format!("keybinding '{}': {}", name, e)),
+                }
+            }
+            configured.insert(action, keys);
+        }
+
+        for (&action, keys) in &configured {
+            for (&other, other_keys) in keymap.bindings.iter_mut() {
+                if other != action && !configured.contains_key(&other) && shares_context(action, other) {
+                    other_keys.retain(|k| !keys.contains(k));
+                }
+            }
+        }
+        keymap.bindings.extend(configured.clone());
+
+        // Two configured actions on the same key: the one earlier in
+        // `Action::ALL` wins
+        for context in [Context::List, Context::Content] {
+            let mut seen: HashMap<Vec<Key>, Action> = HashMap::new();
+            for (&action, keys) in keymap.bindings.iter_mut() {
+                if !action.applies_to(context) {
+                    continue;
+                }
+                keys.retain(|k| match seen.get(k) {
+                    Some(&first) if first != action => {
+                        warnings.push(format!(
+                            "'{}' is bound to both {} and {}; keeping {}",
+                            label(k),
+                            first.name(),
+                            action.name(),
+                            first.name()
+                        ));
+                        false
+                    }
+                    _ => {
+                        seen.insert(k.clone(), action);
+                        true
+                    }
+                });
+            }
+            for (&action, keys) in keymap.bindings.iter_mut() {
+                if !action.applies_to(context) {
+                    continue;
+                }
+                keys.retain(|k| {
+                    let shadow = (1..k.len()).find_map(|n| seen.get(&k[..n]).map(|&a| (n, a)));
+                    match shadow {
+                        Some((n, other)) => {
+                            warnings.push(format!(
+                                "'{}' for {} can never be typed because '{}' is bound to {}",
+                                label(k),
+                                action.name(),
+                                label(&k[..n]),
+                                other.name()
+                            ));
+                            false
+                        }
+                        None => true,
+                    }
+                });
+            }
+        }
+        (keymap, warnings)
+    }
+
+    /// Looks up the keys pressed so far in `context`.
+    pub fn lookup(&self, context: Context, pressed: &[Key]) -> Lookup {
+        let mut pending = false;
+        for (&action, keys) in &self.bindings {
+            if !action.applies_to(context) {
+                continue;
+            }
+            for k in keys {
+                if k.as_slice() == pressed {
+                    return Lookup::Action(action);
+                }
+                pending |= k.len() > pressed.len() && k.starts_with(pressed);
+            }
+        }
+        if pending { Lookup::Pending } else { Lookup::None }
+    }
+
+    /// The keys bound to `action` joined with `/`, for help and hints, or
+    /// `None` when it is unbound.
+    pub fn label(&self, action: Action) -> Option<String> {
+        let keys = self.bindings.get(&action)?;
+        if keys.is_empty() {
+            return None;
+        }
+        Some(keys.iter().map(|k| label(k)).collect::<Vec<_>>().join("/"))
+    }
+
+    /// Like [`Keymap::label`] but with only the first key bound.
+    pub fn short_label(&self, action: Action) -> Option<String> {
+        self.bindings.get(&action)?.first().map(|k| label(k))
+    }
+}
+
+fn shares_context(a: Action, b: Action) -> bool {
+    [Context::List, Context::Content].into_iter().any(|c| a.applies_to(c) && b.applies_to(c))
+}
+
+fn label(keys: &[Key]) -> String {
+    keys.iter().map(Key::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        Key { code, modifiers }
+    }
+
+    fn char_key(c: char) -> Key {
+        key(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    fn configured(config: &str) -> (Keymap, Vec<String>) {
+        Keymap::from_config(&toml::from_str(config).unwrap())
+    }
+
+    #[test]
+    fn key_strings_parse_to_keys() {
+        assert_eq!(parse_keys("j"), Ok(vec![char_key('j')]));
+        assert_eq!(parse_keys(" G "), Ok(vec![char_key('G')]));
+        assert_eq!(parse_keys("shift+g"), Ok(vec![char_key('G')]));
+        assert_eq!(parse_keys("ctrl+d"), Ok(vec![key(KeyCode::Char('d'), KeyModifiers::CONTROL)]));
+        assert_eq!(parse_keys("Ctrl+D"), parse_keys("ctrl+d"));
+        assert_eq!(
+            parse_keys("ctrl+alt+x"),
+            Ok(vec![key(KeyCode::Char('x'), KeyModifiers::CONTROL | KeyModifiers::ALT)])
+        );
+        assert_eq!(parse_keys("space"), Ok(vec![char_key(' ')]));
+        assert_eq!(parse_keys("PageDown"), Ok(vec![key(KeyCode::PageDown, KeyModifiers::NONE)]));
+        assert_eq!(parse_keys("shift+tab"), Ok(vec![key(KeyCode::Tab, KeyModifiers::SHIFT)]));
+        assert_eq!(parse_keys("f12"), Ok(vec![key(KeyCode::F(12), KeyModifiers::NONE)]));
+        assert_eq!(parse_keys("gg"), Ok(vec![char_key('g'), char_key('g')]));
+    }
+
+    #[test]
+    fn invalid_key_strings_are_rejected() {
+        assert_eq!(parse_keys(""), Err("empty key".to_string()));
+        assert_eq!(parse_keys("   "), Err("empty key".to_string()));
+        for input in ["ctrl+f13", "alt+f0", "ctrl+gg", "alt+foo", "g g"] {
+            assert_eq!(parse_keys(input), Err(format!("unknown key '{}'", input)), "{}", input);
+        }
+    }
+
+    #[test]
+    fn events_match_regardless_of_reported_shift() {
+        let shifted = KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT);
+        assert_eq!(Key::from_event(&shifted), char_key('G'));
+        let tab = KeyEvent::new(KeyCode::Tab, KeyModifiers::SHIFT);
+        assert_eq!(Key::from_event(&tab), key(KeyCode::Tab, KeyModifiers::SHIFT));
+    }
+
+    #[test]
+    fn defaults_apply_per_context() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.lookup(Context::List, &[char_key('q')]), Lookup::Action(Action::Quit));
+        assert_eq!(keymap.lookup(Context::Content, &[char_key('q')]), Lookup::Action(Action::Back));
+        assert_eq!(keymap.lookup(Context::Content, &[char_key('G')]), Lookup::Action(Action::Bottom));
+        assert_eq!(keymap.lookup(Context::List, &[char_key('G')]), Lookup::None);
+        assert_eq!(keymap.label(Action::MoveDown).as_deref(), Some("j/↓"));
+        assert_eq!(keymap.short_label(Action::HalfPageDown).as_deref(), Some("Ctrl+D"));
+    }
+
+    #[test]
+    fn bad_entries_fall_back_to_the_defaults() {
+        let (keymap, warnings) = configured("jump = \"x\"\nmove_down = 5\nmove_up = [\"k\", 1]\n");
+        assert_eq!(
+            warnings,
+            [
+                "unknown keybinding action 'jump'",
+                "keybinding 'move_down' must be a key or a list of keys",
+                "keybinding 'move_up' must be a key or a list of keys",
+            ]
+        );
+        assert_eq!(keymap.label(Action::MoveDown), Keymap::default().label(Action::MoveDown));
+        assert_eq!(keymap.label(Action::MoveUp), Keymap::default().label(Action::MoveUp));
+
+        // Only the invalid key of an otherwise valid entry is dropped
+        let (keymap, warnings) = configured("copy = [\"x\", \"ctrl+f13\"]\n");
+        assert_eq!(warnings, ["keybinding 'copy': unknown key 'ctrl+f13'"]);
+        assert_eq!(keymap.label(Action::Copy).as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn configured_keys_replace_and_unbind() {
+        let (keymap, warnings) = configured("top = \"gg\"\nmove_up = \"j\"\nsort = []\n");
+        assert!(warnings.is_empty());
+        assert_eq!(keymap.lookup(Context::Content, &[char_key('g')]), Lookup::Pending);
+        assert_eq!(keymap.lookup(Context::Content, &[char_key('g'), char_key('g')]), Lookup::Action(Action::Top));
+        // The configured key comes off the action it was bound to by default
+        assert_eq!(keymap.lookup(Context::List, &[char_key('j')]), Lookup::Action(Action::MoveUp));
+        assert_eq!(keymap.label(Action::MoveDown).as_deref(), Some("↓"));
+        assert_eq!(keymap.label(Action::Sort), None);
+        assert_eq!(keymap.lookup(Context::List, &[char_key('s')]), Lookup::None);
+    }
+
+    #[test]
+    fn conflicting_bindings_keep_the_first_action() {
+        let (keymap, warnings) = configured("move_down = \"x\"\nmove_up = [\"x\", \"k\"]\n");
+        assert_eq!(warnings, ["'x' is bound to both move_down and move_up; keeping move_down"]);
+        assert_eq!(keymap.lookup(Context::List, &[char_key('x')]), Lookup::Action(Action::MoveDown));
+        assert_eq!(keymap.label(Action::MoveUp).as_deref(), Some("k"));
+
+        let (keymap, warnings) = configured("bottom = \"gx\"\n");
+        assert_eq!(warnings, ["'gx' for bottom can never be typed because 'g' is bound to top"]);
+        assert_eq!(keymap.label(Action::Bottom), None);
+    }
+}
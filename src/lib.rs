@@ -1,6 +1,15 @@
 pub mod backend;
+pub mod clipboard;
 pub mod config;
 pub mod db;
+pub mod diff;
+pub mod editor;
 pub mod highlight;
+pub mod highlight_cache;
+pub mod keymap;
+pub mod metrics;
+pub mod password;
+pub mod rate_limit;
 pub mod server;
+pub mod tls;
 pub mod tui;
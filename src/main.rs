@@ -1,8 +1,8 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser)]
-#[command(name = "sipp", about = "Snippet manager — TUI, server, and CLI")]
+#[command(name = "sipp", version, about = "Snippet manager — TUI, server, and CLI")]
 struct Cli {
     /// Remote server URL (e.g. http://localhost:3000)
     #[arg(short, long, env = "SIPP_REMOTE_URL")]
@@ -12,10 +12,34 @@ struct Cli {
     #[arg(short = 'k', long, env = "SIPP_API_KEY")]
     api_key: Option<String>,
 
-    /// File path to create a snippet from
+    /// Path to a local SQLite database [config: db_path, default: sipp.sqlite]
+    #[arg(long, env = "SIPP_DB_PATH")]
+    db_path: Option<PathBuf>,
+
+    /// Config profile to use for the remote URL and API key
+    #[arg(long, env = "SIPP_PROFILE")]
+    profile: Option<String>,
+
+    /// Accept invalid TLS certificates from the remote server (for self-signed test servers)
+    #[arg(long)]
+    insecure: bool,
+
+    /// File path to create a snippet from, or `-` to read stdin
     #[arg(value_name = "FILE")]
     file: Option<PathBuf>,
 
+    /// Snippet name when reading from stdin [default: stdin-<timestamp>.txt]
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Print the uploaded snippet as JSON instead of its link, and errors as JSON on stderr
+    #[arg(long)]
+    json: bool,
+
+    /// Upload a file that isn't UTF-8 text (e.g. Latin-1), replacing bytes that can't be read
+    #[arg(long)]
+    force: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -24,13 +48,13 @@ struct Cli {
 enum Commands {
     /// Start the web server
     Server {
-        /// Port to listen on
-        #[arg(short, long, default_value_t = 3000)]
-        port: u16,
+        /// Port to listen on [env: SIPP_PORT, default: 3000]
+        #[arg(short, long)]
+        port: Option<u16>,
 
-        /// Host to bind to
-        #[arg(long, default_value = "localhost")]
-        host: String,
+        /// Host to bind to [env: SIPP_HOST, default: 0.0.0.0]
+        #[arg(long)]
+        host: Option<String>,
     },
     /// Launch the interactive TUI
     Tui {
@@ -41,9 +65,57 @@ enum Commands {
         /// API key for authenticated operations
         #[arg(short = 'k', long, env = "SIPP_API_KEY")]
         api_key: Option<String>,
+
+        /// Path to a local SQLite database [config: db_path, default: sipp.sqlite]
+        #[arg(long, env = "SIPP_DB_PATH")]
+        db_path: Option<PathBuf>,
+
+        /// Config profile to use for the remote URL and API key
+        #[arg(long, env = "SIPP_PROFILE")]
+        profile: Option<String>,
+
+        /// Accept invalid TLS certificates from the remote server (for self-signed test servers)
+        #[arg(long)]
+        insecure: bool,
     },
     /// Save remote URL and API key to config file
-    Auth,
+    Auth {
+        /// Save into this named profile instead of the top-level settings
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Upload every text file in a directory as a snippet
+    Import {
+        /// Directory to upload
+        dir: PathBuf,
+
+        /// Also upload files in subdirectories
+        #[arg(short = 'R', long)]
+        recursive: bool,
+    },
+    /// Export every snippet to a directory or as JSON
+    Export {
+        /// Directory to write snippet files into
+        #[arg(value_name = "DIR")]
+        out: Option<PathBuf>,
+
+        /// Write one file per snippet, or a JSON array to stdout
+        #[arg(long, value_enum, default_value_t = ExportFormat::Files)]
+        format: ExportFormat,
+    },
+    /// Print a snippet's content to stdout
+    Get {
+        /// Short id of the snippet
+        short_id: String,
+    },
+    /// Print how many snippets exist
+    Count,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ExportFormat {
+    Files,
+    Json,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -52,22 +124,89 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     match cli.command {
         Some(Commands::Server { port, host }) => {
             let rt = tokio::runtime::Runtime::new()?;
-            rt.block_on(sipp_so::server::run(host, port));
+            rt.block_on(sipp_so::server::serve(host, port));
+        }
+        Some(Commands::Tui {
+            remote,
+            api_key,
+            db_path,
+            profile,
+            insecure,
+        }) => {
+            sipp_so::tui::run_interactive(remote, api_key, db_path, profile.as_deref(), insecure)?;
+        }
+        Some(Commands::Auth { profile }) => {
+            sipp_so::tui::run_auth(profile.as_deref())?;
         }
-        Some(Commands::Tui { remote, api_key }) => {
-            sipp_so::tui::run_interactive(remote, api_key)?;
+        Some(Commands::Import { dir, recursive }) => {
+            sipp_so::tui::run_dir_upload(
+                cli.remote,
+                cli.api_key,
+                cli.db_path,
+                cli.profile.as_deref(),
+                cli.insecure,
+                dir,
+                recursive,
+            )?;
         }
-        Some(Commands::Auth) => {
-            sipp_so::tui::run_auth()?;
+        Some(Commands::Export { out, format }) => {
+            sipp_so::tui::run_export(
+                cli.remote,
+                cli.api_key,
+                cli.db_path,
+                cli.profile.as_deref(),
+                cli.insecure,
+                out,
+                format == ExportFormat::Json,
+            )?;
+        }
+        Some(Commands::Get { short_id }) => {
+            sipp_so::tui::run_get(cli.remote, cli.api_key, cli.db_path, cli.profile.as_deref(), cli.insecure, &short_id)?;
+        }
+        Some(Commands::Count) => {
+            sipp_so::tui::run_count(cli.remote, cli.api_key, cli.db_path, cli.profile.as_deref(), cli.insecure)?;
         }
         None => {
-            if let Some(file) = cli.file {
-                sipp_so::tui::run_file_upload(cli.remote, cli.api_key, file)?;
+            let upload_options = sipp_so::tui::UploadOptions { json: cli.json, force: cli.force };
+            if cli.file.as_deref() == Some(std::path::Path::new("-")) {
+                let result = sipp_so::tui::run_stdin_upload(
+                    cli.remote,
+                    cli.api_key,
+                    cli.db_path,
+                    cli.profile.as_deref(),
+                    cli.insecure,
+                    cli.name,
+                    upload_options,
+                );
+                upload_result(result, cli.json)?;
+            } else if let Some(file) = cli.file {
+                let result = sipp_so::tui::run_file_upload(
+                    cli.remote,
+                    cli.api_key,
+                    cli.db_path,
+                    cli.profile.as_deref(),
+                    cli.insecure,
+                    file,
+                    upload_options,
+                );
+                upload_result(result, cli.json)?;
             } else {
-                sipp_so::tui::run_interactive(cli.remote, cli.api_key)?;
+                sipp_so::tui::run_interactive(cli.remote, cli.api_key, cli.db_path, cli.profile.as_deref(), cli.insecure)?;
             }
         }
     }
 
     Ok(())
 }
+
+/// Passes an upload's result through, except that with `--json` a failure is
+/// printed as `{"error": ...}` on stderr before exiting with status 1.
+fn upload_result(result: Result<(), Box<dyn std::error::Error>>, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    match result {
+        Err(e) if json => {
+            eprintln!("{}", serde_json::json!({"error": e.to_string()}));
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
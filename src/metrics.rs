@@ -0,0 +1,52 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Process-wide counters rendered by `/metrics` in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    snippets_created: AtomicU64,
+    snippets_viewed: AtomicU64,
+    requests_by_status: Mutex<BTreeMap<u16, u64>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_created(&self, count: u64) {
+        self.snippets_created.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn record_view(&self) {
+        self.snippets_viewed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_request(&self, status: u16) {
+        let mut requests = self.requests_by_status.lock().unwrap_or_else(|e| e.into_inner());
+        *requests.entry(status).or_insert(0) += 1;
+    }
+
+    /// Renders every metric, with `snippets_total` read from the database by the caller.
+    pub fn render(&self, snippets_total: i64) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP sipp_snippets_created_total Snippets created since the server started.");
+        let _ = writeln!(out, "# TYPE sipp_snippets_created_total counter");
+        let _ = writeln!(out, "sipp_snippets_created_total {}", self.snippets_created.load(Ordering::Relaxed));
+        let _ = writeln!(out, "# HELP sipp_snippets_viewed_total Snippet pages and raw contents served since the server started.");
+        let _ = writeln!(out, "# TYPE sipp_snippets_viewed_total counter");
+        let _ = writeln!(out, "sipp_snippets_viewed_total {}", self.snippets_viewed.load(Ordering::Relaxed));
+        let _ = writeln!(out, "# HELP sipp_http_requests_total HTTP requests handled, by response status.");
+        let _ = writeln!(out, "# TYPE sipp_http_requests_total counter");
+        let requests = self.requests_by_status.lock().unwrap_or_else(|e| e.into_inner());
+        for (status, count) in requests.iter() {
+            let _ = writeln!(out, "sipp_http_requests_total{{status=\"{}\"}} {}", status, count);
+        }
+        let _ = writeln!(out, "# HELP sipp_snippets_total Snippets currently stored.");
+        let _ = writeln!(out, "# TYPE sipp_snippets_total gauge");
+        let _ = writeln!(out, "sipp_snippets_total {}", snippets_total);
+        out
+    }
+}
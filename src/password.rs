@@ -0,0 +1,90 @@
+use argon2::Argon2;
+use argon2::password_hash::{PasswordHasher, PasswordVerifier};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+/// Hashes a view password with a random salt into an Argon2id PHC string
+/// (`$argon2id$v=19$...`), which records its own parameters.
+pub fn hash_password(password: &str) -> String {
+    Argon2::default()
+        .hash_password(password.as_bytes())
+        .expect("hashing with the default Argon2 parameters")
+        .to_string()
+}
+
+/// Checks `password` against a hash produced by [`hash_password`]. Malformed
+/// hashes never verify.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    Argon2::default().verify_password(password.as_bytes(), hash).is_ok()
+}
+
+/// Signs short-lived tokens that prove a snippet's password was entered.
+pub struct UnlockSigner {
+    key: [u8; 32],
+}
+
+impl UnlockSigner {
+    /// Creates a signer with a random key, so tokens do not survive a restart.
+    pub fn new() -> Self {
+        let mut key = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        Self { key }
+    }
+
+    fn mac(&self, short_id: &str, expires: u64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes keys of any length");
+        mac.update(format!("{}:{}", short_id, expires).as_bytes());
+        mac
+    }
+
+    /// Returns a `<expires>.<signature>` token for `short_id` valid until the
+    /// Unix time `expires`.
+    pub fn sign(&self, short_id: &str, expires: u64) -> String {
+        format!("{}.{}", expires, URL_SAFE_NO_PAD.encode(self.mac(short_id, expires).finalize().into_bytes()))
+    }
+
+    /// Checks that `token` was issued for `short_id` and has not expired at `now`.
+    pub fn verify(&self, short_id: &str, token: &str, now: u64) -> bool {
+        let Some((expires, signature)) = token.split_once('.') else {
+            return false;
+        };
+        let (Ok(expires), Ok(signature)) = (expires.parse::<u64>(), URL_SAFE_NO_PAD.decode(signature)) else {
+            return false;
+        };
+        expires > now && self.mac(short_id, expires).verify_slice(&signature).is_ok()
+    }
+}
+
+impl Default for UnlockSigner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_verify_only_their_password() {
+        let hash = hash_password("hunter2");
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("hunter2", &hash));
+        assert!(!verify_password("hunter3", &hash));
+        assert!(!verify_password("hunter2", "pbkdf2-sha256$1$abc$def"));
+    }
+
+    #[test]
+    fn unlock_tokens_are_bound_to_their_snippet_and_expiry() {
+        let signer = UnlockSigner::new();
+        let token = signer.sign("abc", 100);
+        assert!(signer.verify("abc", &token, 99));
+        assert!(!signer.verify("abc", &token, 100));
+        assert!(!signer.verify("xyz", &token, 99));
+        assert!(!signer.verify("abc", &token.replace("100.", "200."), 99));
+        assert!(!UnlockSigner::new().verify("abc", &token, 99));
+    }
+}
@@ -3,18 +3,39 @@ use askama_web::WebTemplate;
 use subtle::ConstantTimeEq;
 use axum::{
     Form, Json, Router,
-    extract::{Path, Request, State},
-    http::{HeaderMap, StatusCode, header},
+    extract::{ConnectInfo, DefaultBodyLimit, Path, Query, Request, State},
+    handler::Handler,
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri, header},
     middleware::{self, Next},
-    response::{Html, IntoResponse, Redirect, Response},
-    routing::{delete, get, post, put},
+    response::{AppendHeaders, Html, IntoResponse, Redirect, Response},
+    routing::{MethodRouter, delete, get, post, put},
+    serve::ListenerExt,
 };
 use rust_embed::Embed;
 use serde::Deserialize;
-use crate::db::{self, Db, Snippet};
-use crate::highlight::Highlighter;
+use crate::db::{self, Db, Snippet, SnippetMeta};
+use crate::highlight::{self, Highlighter};
+use crate::highlight_cache::{self, HighlightCache};
+use crate::metrics::Metrics;
+use crate::password::{self, UnlockSigner};
+use crate::rate_limit::RateLimiter;
+use crate::tls::{self, TlsListener};
 use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 use std::sync::Arc;
+use tower_http::compression::{
+    CompressionLayer,
+    predicate::{DefaultPredicate, NotForContentType, Predicate},
+};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::sensitive_headers::SetSensitiveRequestHeadersLayer;
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use tower_http::LatencyUnit;
+use tracing::Level;
 
 #[derive(Embed)]
 #[folder = "assets/"]
@@ -29,100 +50,1115 @@ struct ServerConfig {
     api_key: Option<String>,
     auth_endpoints: HashSet<String>,
     max_content_size: usize,
+    max_name_length: usize,
+    cors_origins: Vec<String>,
+    log_requests: bool,
+    host: String,
+    port: u16,
+    create_rate_per_min: u32,
+    /// Reverse proxies, from `SIPP_TRUSTED_PROXIES`, whose `X-Forwarded-For`
+    /// header is believed when rate limiting.
+    trusted_proxies: Vec<IpAddr>,
+    max_batch_size: usize,
+    theme: Option<String>,
+    highlight_cache_bytes: usize,
+    /// Serve existing snippets but refuse to create, edit, or delete any.
+    read_only: bool,
+    short_id_format: db::ShortIdFormat,
+    /// Most snippets stored at once, from `SIPP_MAX_SNIPPETS` and `SIPP_EVICT`.
+    snippet_cap: Option<db::SnippetCap>,
+    /// Lowercase product names whose user agents get raw snippet content.
+    cli_user_agents: Vec<String>,
+    /// Base URL the server is reached at, from `SIPP_PUBLIC_URL`, for
+    /// absolute links to snippets.
+    public_url: Option<String>,
+    /// Certificate and key PEM files; HTTPS is served when both are set.
+    tls: Option<(PathBuf, PathBuf)>,
 }
 
 impl ServerConfig {
-    fn from_env() -> Self {
-        let api_key = std::env::var("SIPP_API_KEY").ok();
-        let auth_endpoints = match std::env::var("SIPP_AUTH_ENDPOINTS") {
-            Ok(val) if val.trim().eq_ignore_ascii_case("none") => HashSet::new(),
-            Ok(val) => val.split(',').map(|s| s.trim().to_lowercase()).collect(),
-            Err(_) => ["api_delete", "api_list", "api_update"].iter().map(|s| s.to_string()).collect(),
+    fn from_env() -> Result<Self, String> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Builds the config from `var`, which returns a setting by its
+    /// environment variable name, so tests can supply settings directly.
+    fn from_lookup(var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let api_key = var("SIPP_API_KEY");
+        let auth_endpoints = match var("SIPP_AUTH_ENDPOINTS") {
+            Some(val) if val.trim().eq_ignore_ascii_case("none") => HashSet::new(),
+            Some(val) => val.split(',').map(|s| s.trim().to_lowercase()).collect(),
+            None => ["api_delete", "api_list", "api_update", "list_html", "edit_html", "delete_html"].iter().map(|s| s.to_string()).collect(),
         };
-        let max_content_size = std::env::var("SIPP_MAX_CONTENT_SIZE")
-            .ok()
+        let max_content_size = var("SIPP_MAX_CONTENT_SIZE")
             .and_then(|v| v.parse().ok())
             .unwrap_or(512_000);
-        ServerConfig { api_key, auth_endpoints, max_content_size }
+        let max_name_length = var("SIPP_MAX_NAME_LENGTH")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(256);
+        let cors_origins = var("SIPP_CORS_ORIGINS")
+            .map(|val| {
+                val.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let log_requests = !var("SIPP_LOG")
+            .map(|v| v.trim().eq_ignore_ascii_case("off"))
+            .unwrap_or(false);
+        let host = var("SIPP_HOST")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| "0.0.0.0".to_string());
+        let port = match var("SIPP_PORT") {
+            Some(val) => val.trim().parse::<u16>().map_err(|_| {
+                format!("SIPP_PORT must be a valid port number (0-65535), got '{}'", val)
+            })?,
+            None => 3000,
+        };
+        let create_rate_per_min = var("SIPP_CREATE_RATE_PER_MIN")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+        let trusted_proxies = var("SIPP_TRUSTED_PROXIES")
+            .iter()
+            .flat_map(|val| val.split(','))
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse().map_err(|_| format!("SIPP_TRUSTED_PROXIES: invalid IP address '{}'", s)))
+            .collect::<Result<Vec<IpAddr>, String>>()?;
+        let max_batch_size = var("SIPP_MAX_BATCH_SIZE")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2 * 1024 * 1024);
+        let theme = var("SIPP_THEME")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+        let highlight_cache_bytes = var("SIPP_HIGHLIGHT_CACHE_BYTES")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(16 * 1024 * 1024);
+        let read_only = var("SIPP_READ_ONLY")
+            .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+        let short_id_len = var("SIPP_SHORT_ID_LEN")
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .unwrap_or(db::DEFAULT_SHORT_ID_LEN)
+            .clamp(db::MIN_SHORT_ID_LEN, MAX_SHORT_ID_LEN);
+        let short_id_alphabet: &'static [char] = match var("SIPP_SHORT_ID_ALPHABET") {
+            Some(val) if val.trim().eq_ignore_ascii_case("no-ambiguous") => &db::UNAMBIGUOUS_SHORT_ID_ALPHABET,
+            Some(val) if val.trim().is_empty() || val.trim().eq_ignore_ascii_case("default") => &db::SHORT_ID_ALPHABET,
+            Some(val) => {
+                return Err(format!(
+                    "SIPP_SHORT_ID_ALPHABET must be 'default' or 'no-ambiguous', got '{}'",
+                    val
+                ));
+            }
+            None => &db::SHORT_ID_ALPHABET,
+        };
+        let overflow = match var("SIPP_EVICT") {
+            Some(val) if val.trim().eq_ignore_ascii_case("oldest") => db::Overflow::EvictOldest,
+            Some(val) if val.trim().is_empty() || val.trim().eq_ignore_ascii_case("none") => {
+                db::Overflow::Reject
+            }
+            Some(val) => return Err(format!("SIPP_EVICT must be 'none' or 'oldest', got '{}'", val)),
+            None => db::Overflow::Reject,
+        };
+        let snippet_cap = match var("SIPP_MAX_SNIPPETS") {
+            Some(val) => match val.trim().parse::<i64>() {
+                Ok(max) if max > 0 => Some(db::SnippetCap { max, overflow }),
+                _ => {
+                    return Err(format!(
+                        "SIPP_MAX_SNIPPETS must be a positive number, got '{}'",
+                        val
+                    ));
+                }
+            },
+            None => None,
+        };
+        let public_url = match var("SIPP_PUBLIC_URL").filter(|v| !v.trim().is_empty()) {
+            Some(val) => Some(
+                crate::config::normalize_remote_url(&val).map_err(|e| format!("SIPP_PUBLIC_URL: {}", e))?,
+            ),
+            None => None,
+        };
+        let cli_user_agents = match var("SIPP_CLI_USER_AGENTS") {
+            Some(val) => val
+                .split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            None => DEFAULT_CLI_USER_AGENTS.iter().map(|s| s.to_string()).collect(),
+        };
+        let tls = match (var("SIPP_TLS_CERT"), var("SIPP_TLS_KEY")) {
+            (Some(cert), Some(key)) => Some((PathBuf::from(cert), PathBuf::from(key))),
+            (None, None) => None,
+            _ => return Err("SIPP_TLS_CERT and SIPP_TLS_KEY must be set together".to_string()),
+        };
+        Ok(ServerConfig {
+            api_key,
+            auth_endpoints,
+            max_content_size,
+            max_name_length,
+            cors_origins,
+            log_requests,
+            host,
+            port,
+            create_rate_per_min,
+            trusted_proxies,
+            max_batch_size,
+            theme,
+            highlight_cache_bytes,
+            read_only,
+            short_id_format: db::ShortIdFormat { len: short_id_len, alphabet: short_id_alphabet },
+            snippet_cap,
+            cli_user_agents,
+            public_url,
+            tls,
+        })
+    }
+
+    /// Where a snippet can be viewed: under `public_url` when it is set, or
+    /// as a path on this server.
+    fn snippet_location(&self, short_id: &str) -> String {
+        format!("{}/s/{}", self.public_url.as_deref().unwrap_or(""), short_id)
+    }
+
+    /// Fills in the snippet's share link before it goes out in a response.
+    fn with_url(&self, mut snippet: Snippet) -> Snippet {
+        snippet.url = Some(self.snippet_location(&snippet.short_id));
+        snippet
+    }
+
+    /// Whether the request comes from a command-line tool that should get raw
+    /// content rather than HTML.
+    fn is_cli_user_agent(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ua| matches_user_agent(ua, &self.cli_user_agents))
     }
 
     fn requires_auth(&self, name: &str) -> bool {
         self.auth_endpoints.contains("all") || self.auth_endpoints.contains(name)
     }
+
+    /// Whether a page that changes snippets can be used at all: the server
+    /// takes writes, and a gated page has a key to sign in with.
+    fn page_usable(&self, name: &str) -> bool {
+        !self.read_only && (!self.requires_auth(name) || self.api_key.is_some())
+    }
+
+    /// Rejects snippet names that are blank or longer than `max_name_length` characters.
+    fn validate_name(&self, name: &str) -> Result<(), String> {
+        if name.trim().is_empty() {
+            return Err("name cannot be empty".to_string());
+        }
+        if name.chars().count() > self.max_name_length {
+            return Err(format!("name cannot be longer than {} characters", self.max_name_length));
+        }
+        Ok(())
+    }
+
+    /// Caps request bodies on routes that carry one snippet, so oversized
+    /// uploads are rejected with `413` while being read rather than after.
+    /// The headroom covers the other fields and encoding overhead, which for
+    /// urlencoded forms can triple the size of the content; the handlers still
+    /// check the content itself against `max_content_size`.
+    fn body_limit(&self) -> DefaultBodyLimit {
+        DefaultBodyLimit::max(self.max_content_size.saturating_mul(3).saturating_add(64 * 1024))
+    }
+
+    /// Builds a CORS layer for the API routes, or `None` when no origins are configured.
+    fn cors_layer(&self) -> Option<CorsLayer> {
+        if self.cors_origins.is_empty() {
+            return None;
+        }
+        let allow_origin = if self.cors_origins.iter().any(|o| o == "*") {
+            AllowOrigin::any()
+        } else {
+            let origins: Vec<HeaderValue> = self
+                .cors_origins
+                .iter()
+                .filter_map(|o| match HeaderValue::from_str(o) {
+                    Ok(v) => Some(v),
+                    Err(_) => {
+                        eprintln!("Warning: invalid origin '{}' in SIPP_CORS_ORIGINS", o);
+                        None
+                    }
+                })
+                .collect();
+            AllowOrigin::list(origins)
+        };
+        Some(
+            CorsLayer::new()
+                .allow_origin(allow_origin)
+                .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE])
+                .allow_headers([
+                    HeaderName::from_static("x-api-key"),
+                    HeaderName::from_static(SNIPPET_PASSWORD_HEADER),
+                    header::CONTENT_TYPE,
+                    header::AUTHORIZATION,
+                ])
+                .expose_headers([
+                    HeaderName::from_static("x-total-count"),
+                    HeaderName::from_static(REQUEST_ID_HEADER),
+                ]),
+        )
+    }
 }
 
 #[derive(Clone)]
 struct AppState {
     db: Db,
     highlighter: Arc<Highlighter>,
+    highlight_cache: Arc<HighlightCache>,
     server_config: ServerConfig,
+    create_limiter: Arc<RateLimiter>,
+    unlock_signer: Arc<UnlockSigner>,
+    /// Signs the cookies `/login` hands out; its own key, so an unlock token
+    /// can never pass as a session.
+    session_signer: Arc<UnlockSigner>,
+    metrics: Arc<Metrics>,
+}
+
+impl AppState {
+    fn new(server_config: ServerConfig, db: Db) -> Self {
+        AppState {
+            db,
+            highlighter: Arc::new(match &server_config.theme {
+                Some(theme) => Highlighter::with_theme(theme),
+                None => Highlighter::new(),
+            }),
+            highlight_cache: Arc::new(HighlightCache::new(server_config.highlight_cache_bytes)),
+            create_limiter: Arc::new(RateLimiter::new(server_config.create_rate_per_min)),
+            unlock_signer: Arc::new(UnlockSigner::new()),
+            session_signer: Arc::new(UnlockSigner::new()),
+            metrics: Arc::new(Metrics::new()),
+            server_config,
+        }
+    }
 }
 
 #[derive(Template)]
 #[template(path = "index.html")]
-struct IndexTemplate;
+struct IndexTemplate {
+    read_only: bool,
+}
 
 #[derive(Template)]
 #[template(path = "admin.html")]
 struct AdminTemplate;
 
+#[derive(Template)]
+#[template(path = "about.html")]
+struct AboutTemplate;
+
+#[derive(Template)]
+#[template(path = "not_found.html")]
+struct NotFoundTemplate;
+
+#[derive(Template)]
+#[template(path = "edit.html")]
+struct EditTemplate {
+    short_id: String,
+    name: String,
+    language: String,
+    content: String,
+}
+
+#[derive(Template)]
+#[template(path = "delete.html")]
+struct DeleteTemplate {
+    short_id: String,
+    name: String,
+}
+
+#[derive(Template)]
+#[template(path = "unlock.html")]
+struct UnlockTemplate {
+    short_id: String,
+    name: String,
+    failed: bool,
+}
+
+#[derive(Template)]
+#[template(path = "login.html")]
+struct LoginTemplate {
+    /// Where to go once signed in.
+    next: String,
+    failed: bool,
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    key: String,
+    #[serde(default)]
+    next: String,
+}
+
+#[derive(Deserialize)]
+struct LoginQuery {
+    next: Option<String>,
+}
+
+/// Snippets shown per page on `/list`.
+const LIST_PAGE_SIZE: i64 = 50;
+
+/// A row on `/list`.
+struct ListItem {
+    short_id: String,
+    name: String,
+    created: String,
+}
+
+#[derive(Template)]
+#[template(path = "list.html")]
+struct ListTemplate {
+    snippets: Vec<ListItem>,
+    page: i64,
+    page_count: i64,
+}
+
 #[derive(Template)]
 #[template(path = "snippet.html")]
 struct SnippetTemplate {
     name: String,
     content: String,
     highlighted_content: String,
+    read_only: bool,
+    /// Whether to offer the Edit button.
+    can_edit: bool,
+    /// Whether to offer the Delete button.
+    can_delete: bool,
+    /// Base64 content isn't shown, only offered as a download.
+    binary: bool,
+    short_id: String,
 }
 
 #[derive(Deserialize)]
 struct CreateSnippetForm {
     name: String,
     content: String,
+    #[serde(default)]
+    short_id: String,
+    #[serde(default)]
+    view_password: String,
 }
 
-async fn index() -> WebTemplate<IndexTemplate> {
-    WebTemplate(IndexTemplate)
+#[derive(Deserialize)]
+struct UnlockForm {
+    password: String,
+}
+
+/// How long entering a snippet's password keeps it unlocked in the browser.
+const UNLOCK_TTL_SECS: u64 = 60 * 60;
+const UNLOCK_COOKIE: &str = "sipp_unlock";
+const SNIPPET_PASSWORD_HEADER: &str = "x-snippet-password";
+/// How long signing in at `/login` lasts in the browser.
+const SESSION_TTL_SECS: u64 = 12 * 60 * 60;
+const SESSION_COOKIE: &str = "sipp_session";
+const SESSION_SUBJECT: &str = "session";
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Hashes a view password off the async runtime, treating a blank one as absent.
+async fn hash_view_password(view_password: Option<&str>) -> Option<String> {
+    let view_password = view_password.filter(|p| !p.is_empty())?.to_string();
+    tokio::task::spawn_blocking(move || password::hash_password(&view_password))
+        .await
+        .ok()
+}
+
+/// Checks the `x-snippet-password` header against a protected snippet's hash.
+async fn password_header_matches(headers: &HeaderMap, hash: &str) -> bool {
+    let Some(given) = headers.get(SNIPPET_PASSWORD_HEADER).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let (given, hash) = (given.to_string(), hash.to_string());
+    tokio::task::spawn_blocking(move || password::verify_password(&given, &hash))
+        .await
+        .unwrap_or(false)
+}
+
+/// Clears a protected snippet's content unless the request carries its
+/// password, so write responses can't be used to read past the password.
+async fn hide_protected_content(mut snippet: Snippet, headers: &HeaderMap) -> Snippet {
+    if let Some(hash) = &snippet.password_hash
+        && !password_header_matches(headers, hash).await
+    {
+        snippet.content.clear();
+    }
+    snippet
+}
+
+/// The values of every cookie named `name` on the request.
+fn cookie_values<'a>(headers: &'a HeaderMap, name: &'a str) -> impl Iterator<Item = &'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .filter(move |(cookie, _)| *cookie == name)
+        .map(|(_, value)| value)
+}
+
+/// Whether the request carries a valid unlock cookie for `short_id`.
+fn has_unlock_cookie(state: &AppState, headers: &HeaderMap, short_id: &str) -> bool {
+    let now = unix_now();
+    cookie_values(headers, UNLOCK_COOKIE).any(|token| state.unlock_signer.verify(short_id, token, now))
+}
+
+/// Whether the request carries a valid session cookie from `/login`.
+fn has_session_cookie(state: &AppState, headers: &HeaderMap) -> bool {
+    let now = unix_now();
+    cookie_values(headers, SESSION_COOKIE).any(|token| state.session_signer.verify(SESSION_SUBJECT, token, now))
+}
+
+/// Short ids that would be shadowed by fixed routes such as `/api/snippets/batch`
+/// and `/api/snippets/stream`.
+const RESERVED_SHORT_IDS: &[&str] = &["batch", "stream", "validate"];
+
+/// Longest short id, custom or generated.
+const MAX_SHORT_ID_LEN: usize = 64;
+
+/// Products whose user agents get raw snippet content unless
+/// `SIPP_CLI_USER_AGENTS` says otherwise.
+const DEFAULT_CLI_USER_AGENTS: &[&str] = &[
+    "curl",
+    "wget",
+    "httpie",
+    "powershell",
+    "windowspowershell",
+    "python-requests",
+    "fetch",
+    "aria2",
+];
+
+/// Checks a caller-chosen short id: 3 to 64 ASCII letters, digits, `-` or `_`.
+fn validate_short_id(short_id: &str) -> Result<(), String> {
+    if RESERVED_SHORT_IDS.iter().any(|r| r.eq_ignore_ascii_case(short_id)) {
+        return Err(format!("Short id '{}' is reserved", short_id));
+    }
+    if !(3..=MAX_SHORT_ID_LEN).contains(&short_id.len()) {
+        return Err(format!("Short id must be between 3 and {} characters", MAX_SHORT_ID_LEN));
+    }
+    if !short_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("Short id may only contain letters, digits, '-' and '_'".to_string());
+    }
+    Ok(())
+}
+
+/// Trims an optional custom short id, treating a blank one as absent.
+fn custom_short_id(short_id: Option<&str>) -> Option<&str> {
+    short_id.map(str::trim).filter(|id| !id.is_empty())
+}
+
+#[derive(Deserialize)]
+struct EditSnippetForm {
+    name: String,
+    content: String,
+    #[serde(default)]
+    language: String,
+}
+
+async fn index(State(state): State<AppState>) -> WebTemplate<IndexTemplate> {
+    WebTemplate(IndexTemplate { read_only: state.server_config.read_only })
+}
+
+const READ_ONLY_MESSAGE: &str = "This server is read-only; snippets cannot be created, edited, or deleted";
+
+/// Stands in for every API write handler when `SIPP_READ_ONLY` is set.
+async fn read_only_api() -> (StatusCode, Json<serde_json::Value>) {
+    (StatusCode::FORBIDDEN, Json(serde_json::json!({"error": READ_ONLY_MESSAGE})))
+}
+
+/// Stands in for the create, edit, and delete pages when `SIPP_READ_ONLY` is set.
+async fn read_only_html() -> (StatusCode, Html<String>) {
+    (StatusCode::FORBIDDEN, Html(format!("<h1>Read-only</h1><p>{}</p>", READ_ONLY_MESSAGE)))
 }
 
 async fn admin() -> WebTemplate<AdminTemplate> {
     WebTemplate(AdminTemplate)
 }
 
-fn is_cli_user_agent(headers: &HeaderMap) -> bool {
+async fn about() -> WebTemplate<AboutTemplate> {
+    WebTemplate(AboutTemplate)
+}
+
+#[derive(Deserialize)]
+struct ListPageQuery {
+    page: Option<i64>,
+}
+
+async fn list_snippets_html(
+    State(state): State<AppState>,
+    Query(query): Query<ListPageQuery>,
+) -> Result<WebTemplate<ListTemplate>, (StatusCode, Html<String>)> {
+    let internal_error = || {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Html("<h1>Internal server error</h1>".to_string()),
+        )
+    };
+    let total = db::count_snippets(&state.db).map_err(|_| internal_error())?;
+    let page_count = ((total + LIST_PAGE_SIZE - 1) / LIST_PAGE_SIZE).max(1);
+    let page = query.page.unwrap_or(1).clamp(1, page_count);
+    let snippets = db::get_snippet_metas_page(&state.db, LIST_PAGE_SIZE, (page - 1) * LIST_PAGE_SIZE)
+        .map_err(|_| internal_error())?;
+    let snippets = snippets
+        .into_iter()
+        .map(|s: SnippetMeta| ListItem {
+            created: s.created_at.map(format_timestamp).unwrap_or_default(),
+            short_id: s.short_id,
+            name: s.name,
+        })
+        .collect();
+    Ok(WebTemplate(ListTemplate {
+        snippets,
+        page,
+        page_count,
+    }))
+}
+
+/// Formats a Unix timestamp as `YYYY-MM-DD HH:MM UTC`.
+fn format_timestamp(secs: i64) -> String {
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    // Days since the epoch to a civil date, from Howard Hinnant's date algorithms
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, rem / 3600, rem % 3600 / 60)
+}
+
+/// A 404 response: the styled page for browsers, plain text for CLI tools.
+fn not_found_page(config: &ServerConfig, headers: &HeaderMap) -> Response {
+    if config.is_cli_user_agent(headers) {
+        (StatusCode::NOT_FOUND, "not found\n").into_response()
+    } else {
+        (StatusCode::NOT_FOUND, WebTemplate(NotFoundTemplate)).into_response()
+    }
+}
+
+async fn fallback(State(state): State<AppState>, uri: Uri, headers: HeaderMap) -> Response {
+    if uri.path().starts_with("/api/") {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "Not found"})),
+        )
+            .into_response();
+    }
+    not_found_page(&state.server_config, &headers)
+}
+
+async fn version() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "name": "sipp",
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+async fn metrics(State(state): State<AppState>) -> Result<Response, StatusCode> {
+    let total = db::count_snippets(&state.db).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        state.metrics.render(total),
+    )
+        .into_response())
+}
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Error bodies larger than this are passed through without a request id.
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+/// Adds the request id to JSON error bodies as `request_id`, so clients can
+/// quote it in bug reports. Every other response passes through untouched.
+async fn add_request_id_to_errors(request: Request, next: Next) -> Response {
+    let request_id = request.headers().get(REQUEST_ID_HEADER).cloned();
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    let Some(request_id) = request_id.filter(|_| is_json && response.status().as_u16() >= 400) else {
+        return response;
+    };
+    let (mut parts, body) = response.into_parts();
+    // Checked before reading, since a body cut off at the limit couldn't be
+    // put back together
+    if axum::body::HttpBody::size_hint(&body).upper().is_none_or(|len| len > MAX_ERROR_BODY as u64) {
+        return Response::from_parts(parts, body);
+    }
+    let Ok(bytes) = axum::body::to_bytes(body, MAX_ERROR_BODY).await else {
+        // The body failed partway, so only the status and headers are left
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, axum::body::Body::empty());
+    };
+    let mut value: serde_json::Value = match serde_json::from_slice(&bytes) {
+        Ok(value) => value,
+        Err(_) => return Response::from_parts(parts, axum::body::Body::from(bytes)),
+    };
+    if let (Some(object), Ok(id)) = (value.as_object_mut(), request_id.to_str()) {
+        object.insert("request_id".to_string(), serde_json::Value::from(id));
+    }
+    parts.headers.remove(header::CONTENT_LENGTH);
+    (parts, Json(value)).into_response()
+}
+
+/// Counts every response by status for `/metrics`.
+async fn count_requests(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    state.metrics.record_request(response.status().as_u16());
+    response
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn readyz(State(state): State<AppState>) -> (StatusCode, &'static str) {
+    match db::ping(&state.db) {
+        Ok(()) => (StatusCode::OK, "ok"),
+        Err(_) => (StatusCode::SERVICE_UNAVAILABLE, "database unavailable"),
+    }
+}
+
+/// Whether any product in the user agent `ua` (the `name` of each
+/// `name/version` token) is one of `products`, ignoring case.
+fn matches_user_agent(ua: &str, products: &[String]) -> bool {
+    ua.split_whitespace()
+        .map(|token| token.split('/').next().unwrap_or("").to_lowercase())
+        .any(|product| products.contains(&product))
+}
+
+/// A weak ETag covering everything a snippet response is built from.
+/// `variant` tells apart representations of the same snippet (raw text,
+/// HTML per theme, JSON).
+fn snippet_etag(snippet: &Snippet, variant: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    // Templates change between releases, so cached pages shouldn't outlive them
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    variant.hash(&mut hasher);
+    snippet.short_id.hash(&mut hasher);
+    snippet.name.hash(&mut hasher);
+    snippet.language.hash(&mut hasher);
+    snippet.content.hash(&mut hasher);
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+/// Whether the request's `If-None-Match` matches `etag`, using the weak
+/// comparison that conditional GETs call for.
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
     headers
-        .get(header::USER_AGENT)
+        .get(header::IF_NONE_MATCH)
         .and_then(|v| v.to_str().ok())
-        .map(|ua| {
-            let ua = ua.to_lowercase();
-            ua.starts_with("curl/") || ua.starts_with("wget/") || ua.starts_with("httpie/")
-        })
-        .unwrap_or(false)
+        .is_some_and(|v| v.split(',').any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag)))
+}
+
+/// The `Last-Modified` value for a snippet, or `None` when its modification
+/// time is unknown.
+fn last_modified(snippet: &Snippet) -> Option<String> {
+    let secs = u64::try_from(snippet.updated_at).ok().filter(|&s| s > 0)?;
+    Some(httpdate::fmt_http_date(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs)))
+}
+
+/// Whether the request's `If-Modified-Since` is no older than the snippet's
+/// last change. Ignored when `If-None-Match` is present, as the ETag is the
+/// more precise validator.
+fn unmodified_since(headers: &HeaderMap, snippet: &Snippet) -> bool {
+    if headers.contains_key(header::IF_NONE_MATCH) || snippet.updated_at <= 0 {
+        return false;
+    }
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok())
+        .and_then(|since| since.duration_since(std::time::UNIX_EPOCH).ok())
+        .is_some_and(|since| snippet.updated_at as u64 <= since.as_secs())
+}
+
+/// Whether a conditional GET for `snippet` can be answered with `304`.
+fn is_fresh(headers: &HeaderMap, etag: &str, snippet: &Snippet) -> bool {
+    etag_matches(headers, etag) || unmodified_since(headers, snippet)
+}
+
+fn not_modified(etag: String, last_modified: Option<String>) -> Response {
+    (
+        StatusCode::NOT_MODIFIED,
+        [(header::ETAG, etag)],
+        AppendHeaders(last_modified.map(|v| (header::LAST_MODIFIED, v))),
+    )
+        .into_response()
+}
+
+/// Content type for the raw CLI view. Only data formats get their real type;
+/// anything a browser might render (HTML, SVG, ...) stays `text/plain`.
+fn raw_content_type(name: &str) -> &'static str {
+    match name.rsplit('.').next().unwrap_or("").to_ascii_lowercase().as_str() {
+        "json" => "application/json",
+        "csv" => "text/csv; charset=utf-8",
+        "tsv" => "text/tab-separated-values; charset=utf-8",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        _ => "text/plain; charset=utf-8",
+    }
+}
+
+/// `name` reduced to characters that are safe in a quoted header value.
+fn download_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect()
+}
+
+/// The snippet's raw content. Base64 snippets are sent as their decoded
+/// bytes, as a download since there is no telling what they hold.
+fn raw_response(snippet: &Snippet, etag: String, modified: Option<String>) -> Response {
+    let modified = AppendHeaders(modified.map(|v| (header::LAST_MODIFIED, v)));
+    if snippet.is_binary() {
+        let disposition = format!("attachment; filename=\"{}\"", download_filename(&snippet.name));
+        return (
+            [
+                (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+                (header::CONTENT_DISPOSITION, disposition),
+                (header::ETAG, etag),
+            ],
+            modified,
+            snippet.raw_bytes(),
+        )
+            .into_response();
+    }
+    (
+        [
+            (header::CONTENT_TYPE, raw_content_type(&snippet.name).to_string()),
+            (header::ETAG, etag),
+        ],
+        modified,
+        snippet.content.clone(),
+    )
+        .into_response()
 }
 
+#[derive(Deserialize)]
+struct ViewQuery {
+    theme: Option<String>,
+    /// Highlights as this language for this view only, overriding the stored one.
+    lang: Option<String>,
+}
+
+/// Serves a snippet as raw text to CLI tools and as a highlighted page to
+/// everyone else. The body depends on `User-Agent`, so every response says so
+/// in `Vary` to keep shared caches from mixing the two up.
 async fn view_snippet(
+    state: State<AppState>,
+    short_id: Path<String>,
+    query: Query<ViewQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let mut response = render_snippet(state, short_id, query, headers).await.into_response();
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("user-agent"));
+    response
+}
+
+async fn render_snippet(
     State(state): State<AppState>,
     Path(short_id): Path<String>,
+    Query(query): Query<ViewQuery>,
     headers: HeaderMap,
 ) -> Result<Response, (StatusCode, Html<String>)> {
     match db::get_snippet_by_short_id(&state.db, &short_id) {
         Ok(Some(snippet)) => {
-            if is_cli_user_agent(&headers) {
+            if let Some(hash) = &snippet.password_hash
+                && !has_unlock_cookie(&state, &headers, &snippet.short_id)
+                && !password_header_matches(&headers, hash).await
+            {
+                return Ok(if state.server_config.is_cli_user_agent(&headers) {
+                    (StatusCode::UNAUTHORIZED, "password required\n").into_response()
+                } else {
+                    (
+                        StatusCode::UNAUTHORIZED,
+                        WebTemplate(UnlockTemplate {
+                            short_id: snippet.short_id,
+                            name: snippet.name,
+                            failed: false,
+                        }),
+                    )
+                        .into_response()
+                });
+            }
+            state.metrics.record_view();
+            if let Err(e) = db::record_view(&state.db, &snippet.short_id) {
+                tracing::warn!("Failed to record view of {}: {}", snippet.short_id, e);
+            }
+            let modified = last_modified(&snippet);
+            if state.server_config.is_cli_user_agent(&headers) {
+                let etag = snippet_etag(&snippet, "raw");
+                if is_fresh(&headers, &etag, &snippet) {
+                    return Ok(not_modified(etag, modified));
+                }
+                Ok(raw_response(&snippet, etag, modified))
+            } else if snippet.is_binary() {
+                let etag = snippet_etag(&snippet, "html:binary");
+                if is_fresh(&headers, &etag, &snippet) {
+                    return Ok(not_modified(etag, modified));
+                }
                 Ok((
-                    [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
-                    snippet.content,
+                    [(header::ETAG, etag)],
+                    AppendHeaders(modified.map(|v| (header::LAST_MODIFIED, v))),
+                    WebTemplate(SnippetTemplate {
+                        name: snippet.name,
+                        content: String::new(),
+                        highlighted_content: String::new(),
+                        read_only: state.server_config.read_only,
+                        can_edit: state.server_config.page_usable("edit_html"),
+                        can_delete: state.server_config.page_usable("delete_html"),
+                        binary: true,
+                        short_id: snippet.short_id,
+                    }),
                 )
                     .into_response())
             } else {
-                let highlighted_content =
-                    state.highlighter.highlight(&snippet.name, &snippet.content);
-                Ok(WebTemplate(SnippetTemplate {
-                    name: snippet.name,
-                    content: snippet.content,
-                    highlighted_content,
-                })
-                .into_response())
+                let theme = match query.theme.as_deref() {
+                    Some(name) => state.highlighter.resolve_theme(name),
+                    None => state.highlighter.default_theme(),
+                };
+                let language = match query.lang.as_deref() {
+                    Some(token) => Some(state.highlighter.resolve_language(token)),
+                    None => snippet.language.as_deref(),
+                };
+                let etag = snippet_etag(
+                    &snippet,
+                    &format!("html:{}:{}", theme, language.unwrap_or_default()),
+                );
+                if is_fresh(&headers, &etag, &snippet) {
+                    return Ok(not_modified(etag, modified));
+                }
+                let source_hash =
+                    highlight_cache::source_hash(&snippet.name, language, &snippet.content);
+                let highlighted_content = state.highlight_cache.get_or_render(
+                    &snippet.short_id,
+                    theme,
+                    source_hash,
+                    || {
+                        state.highlighter.highlight_with_line_numbers(
+                            &snippet.name,
+                            language,
+                            &snippet.content,
+                            theme,
+                        )
+                    },
+                );
+                Ok((
+                    [(header::ETAG, etag)],
+                    AppendHeaders(modified.map(|v| (header::LAST_MODIFIED, v))),
+                    WebTemplate(SnippetTemplate {
+                        name: snippet.name,
+                        content: snippet.content,
+                        highlighted_content,
+                        read_only: state.server_config.read_only,
+                        can_edit: state.server_config.page_usable("edit_html"),
+                        can_delete: state.server_config.page_usable("delete_html"),
+                        binary: false,
+                        short_id: snippet.short_id,
+                    }),
+                )
+                    .into_response())
             }
         }
+        Ok(None) => Ok(not_found_page(&state.server_config, &headers)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Html("<h1>Internal server error</h1>".to_string()),
+        )),
+    }
+}
+
+/// The snippet's raw content for browsers, which is how binary snippets are
+/// downloaded from their page. Protected snippets need the unlock cookie or
+/// the password header, as on the page itself.
+async fn raw_snippet(
+    State(state): State<AppState>,
+    Path(short_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Html<String>)> {
+    match db::get_snippet_by_short_id(&state.db, &short_id) {
+        Ok(Some(snippet)) => {
+            if let Some(hash) = &snippet.password_hash
+                && !has_unlock_cookie(&state, &headers, &snippet.short_id)
+                && !password_header_matches(&headers, hash).await
+            {
+                return Ok((StatusCode::UNAUTHORIZED, "password required\n").into_response());
+            }
+            state.metrics.record_view();
+            if let Err(e) = db::record_view(&state.db, &snippet.short_id) {
+                tracing::warn!("Failed to record view of {}: {}", snippet.short_id, e);
+            }
+            let etag = snippet_etag(&snippet, "raw");
+            let modified = last_modified(&snippet);
+            if is_fresh(&headers, &etag, &snippet) {
+                return Ok(not_modified(etag, modified));
+            }
+            Ok(raw_response(&snippet, etag, modified))
+        }
+        Ok(None) => Ok(not_found_page(&state.server_config, &headers)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Html("<h1>Internal server error</h1>".to_string()),
+        )),
+    }
+}
+
+async fn unlock_snippet(
+    State(state): State<AppState>,
+    Path(short_id): Path<String>,
+    headers: HeaderMap,
+    Form(form): Form<UnlockForm>,
+) -> Result<Response, (StatusCode, Html<String>)> {
+    let snippet = match db::get_snippet_by_short_id(&state.db, &short_id) {
+        Ok(Some(snippet)) => snippet,
+        Ok(None) => return Ok(not_found_page(&state.server_config, &headers)),
+        Err(_) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Html("<h1>Internal server error</h1>".to_string()),
+            ));
+        }
+    };
+    let redirect = Redirect::to(&format!("/s/{}", snippet.short_id));
+    let Some(hash) = snippet.password_hash else {
+        return Ok(redirect.into_response());
+    };
+    let password = form.password;
+    let verified = tokio::task::spawn_blocking(move || password::verify_password(&password, &hash))
+        .await
+        .unwrap_or(false);
+    if !verified {
+        return Ok((
+            StatusCode::UNAUTHORIZED,
+            WebTemplate(UnlockTemplate {
+                short_id: snippet.short_id,
+                name: snippet.name,
+                failed: true,
+            }),
+        )
+            .into_response());
+    }
+    let token = state.unlock_signer.sign(&snippet.short_id, unix_now() + UNLOCK_TTL_SECS);
+    let cookie = format!(
+        "{}={}; Path=/s/{}; Max-Age={}; HttpOnly; SameSite=Lax",
+        UNLOCK_COOKIE, token, snippet.short_id, UNLOCK_TTL_SECS
+    );
+    Ok(([(header::SET_COOKIE, cookie)], redirect).into_response())
+}
+
+async fn create_snippet(
+    State(state): State<AppState>,
+    Form(form): Form<CreateSnippetForm>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    if form.content.len() > state.server_config.max_content_size {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Html(format!(
+                "<h1>Content too large</h1><p>Maximum size is {} bytes</p>",
+                state.server_config.max_content_size
+            )),
+        ));
+    }
+    state.server_config.validate_name(&form.name).map_err(|e| {
+        (StatusCode::BAD_REQUEST, Html(format!("<h1>Invalid name</h1><p>{}</p>", e)))
+    })?;
+    let short_id = custom_short_id(Some(&form.short_id));
+    if let Some(short_id) = short_id {
+        validate_short_id(short_id).map_err(|e| {
+            (StatusCode::BAD_REQUEST, Html(format!("<h1>Invalid short id</h1><p>{}</p>", e)))
+        })?;
+    }
+    let password_hash = hash_view_password(Some(&form.view_password)).await;
+    let created = db::create_snippet_capped(
+        &state.db,
+        &db::NewSnippet {
+            short_id,
+            short_id_format: state.server_config.short_id_format,
+            name: &form.name,
+            content: &form.content,
+            language: None,
+            tags: &[],
+            password_hash: password_hash.as_deref(),
+            encoding: db::ContentEncoding::Utf8,
+        },
+        state.server_config.snippet_cap,
+    );
+    match created {
+        Ok(snippet) => {
+            state.metrics.record_created(1);
+            Ok(Redirect::to(&format!("/s/{}", snippet.short_id)))
+        }
+        Err(db::DbError::ShortIdTaken(id)) => Err((
+            StatusCode::CONFLICT,
+            Html(format!("<h1>Short id taken</h1><p>'{}' is already in use</p>", id)),
+        )),
+        Err(db::DbError::StoreFull(max)) => Err((
+            StatusCode::INSUFFICIENT_STORAGE,
+            Html(format!(
+                "<h1>Storage full</h1><p>This server holds at most {} snippets</p>",
+                max
+            )),
+        )),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Html("<h1>Internal server error</h1>".to_string()),
+        )),
+    }
+}
+
+async fn edit_snippet_form(
+    State(state): State<AppState>,
+    Path(short_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Html<String>)> {
+    match db::get_snippet_by_short_id(&state.db, &short_id) {
+        Ok(Some(snippet)) if snippet.is_binary() => Err((
+            StatusCode::BAD_REQUEST,
+            Html("<h1>Binary snippet</h1><p>Binary content can't be edited in the browser</p>".to_string()),
+        )),
+        Ok(Some(snippet)) => {
+            // The form shows the content, so it asks for the password like the page does
+            if let Some(hash) = &snippet.password_hash
+                && !has_unlock_cookie(&state, &headers, &snippet.short_id)
+                && !password_header_matches(&headers, hash).await
+            {
+                return Ok((
+                    StatusCode::UNAUTHORIZED,
+                    WebTemplate(UnlockTemplate {
+                        short_id: snippet.short_id,
+                        name: snippet.name,
+                        failed: false,
+                    }),
+                )
+                    .into_response());
+            }
+            Ok(WebTemplate(EditTemplate {
+                short_id: snippet.short_id,
+                name: snippet.name,
+                language: snippet.language.unwrap_or_default(),
+                content: snippet.content,
+            })
+            .into_response())
+        }
         Ok(None) => Err((
             StatusCode::NOT_FOUND,
             Html("<h1>Snippet not found</h1>".to_string()),
@@ -134,9 +1170,10 @@ async fn view_snippet(
     }
 }
 
-async fn create_snippet(
+async fn edit_snippet(
     State(state): State<AppState>,
-    Form(form): Form<CreateSnippetForm>,
+    Path(short_id): Path<String>,
+    Form(form): Form<EditSnippetForm>,
 ) -> Result<Redirect, (StatusCode, Html<String>)> {
     if form.content.len() > state.server_config.max_content_size {
         return Err((
@@ -147,8 +1184,67 @@ async fn create_snippet(
             )),
         ));
     }
-    match db::create_snippet(&state.db, &form.name, &form.content) {
-        Ok(snippet) => Ok(Redirect::to(&format!("/s/{}", snippet.short_id))),
+    state.server_config.validate_name(&form.name).map_err(|e| {
+        (StatusCode::BAD_REQUEST, Html(format!("<h1>Invalid name</h1><p>{}</p>", e)))
+    })?;
+    let language = Some(form.language.trim()).filter(|l| !l.is_empty());
+    match db::update_snippet_by_short_id(
+        &state.db,
+        &short_id,
+        &form.name,
+        &form.content,
+        db::ContentEncoding::Utf8,
+        language,
+        None,
+    ) {
+        Ok(Some(snippet)) => {
+            state.highlight_cache.invalidate(&short_id);
+            Ok(Redirect::to(&format!("/s/{}", snippet.short_id)))
+        }
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Html("<h1>Snippet not found</h1>".to_string()),
+        )),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Html("<h1>Internal server error</h1>".to_string()),
+        )),
+    }
+}
+
+async fn delete_snippet_form(
+    State(state): State<AppState>,
+    Path(short_id): Path<String>,
+) -> Result<WebTemplate<DeleteTemplate>, (StatusCode, Html<String>)> {
+    match db::get_snippet_by_short_id(&state.db, &short_id) {
+        Ok(Some(snippet)) => Ok(WebTemplate(DeleteTemplate {
+            short_id: snippet.short_id,
+            name: snippet.name,
+        })),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Html("<h1>Snippet not found</h1>".to_string()),
+        )),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Html("<h1>Internal server error</h1>".to_string()),
+        )),
+    }
+}
+
+async fn delete_snippet(
+    State(state): State<AppState>,
+    Path(short_id): Path<String>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    match db::delete_snippet_by_short_id(&state.db, &short_id) {
+        Ok(true) => {
+            state.highlight_cache.invalidate(&short_id);
+            Ok(Redirect::to("/list"))
+        }
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Html("<h1>Snippet not found</h1>".to_string()),
+        )),
         Err(_) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             Html("<h1>Internal server error</h1>".to_string()),
@@ -169,33 +1265,307 @@ async fn require_api_key(
             Json(serde_json::json!({"error": "No API key configured on server"})),
         )),
     };
-    let provided = headers
-        .get("x-api-key")
-        .and_then(|v| v.to_str().ok());
-    match provided {
-        Some(k) if k.as_bytes().ct_eq(server_key.as_bytes()).into() => Ok(next.run(request).await),
-        _ => Err((
+    if has_api_key(&headers, server_key) {
+        Ok(next.run(request).await)
+    } else {
+        Err((
             StatusCode::UNAUTHORIZED,
             Json(serde_json::json!({"error": "Invalid or missing API key"})),
-        )),
+        ))
+    }
+}
+
+/// Whether the request sends `server_key`. `x-api-key` is preferred for
+/// backward compatibility, falling back to a Bearer token for proxies that
+/// strip custom headers.
+fn has_api_key(headers: &HeaderMap, server_key: &str) -> bool {
+    let provided = headers
+        .get("x-api-key")
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+        });
+    provided.is_some_and(|k| k.as_bytes().ct_eq(server_key.as_bytes()).into())
+}
+
+/// [`require_api_key`] for pages, which browsers can't send the key header
+/// to: a session cookie from `/login` also passes, and a request without
+/// either gets the sign-in form rather than a JSON error.
+async fn require_browser_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(server_key) = &state.server_config.api_key else {
+        return (
+            StatusCode::FORBIDDEN,
+            Html("<h1>Forbidden</h1><p>No API key configured on server</p>".to_string()),
+        )
+            .into_response();
+    };
+    if has_api_key(&headers, server_key) || has_session_cookie(&state, &headers) {
+        return next.run(request).await;
+    }
+    let next = request.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/").to_string();
+    (StatusCode::UNAUTHORIZED, WebTemplate(LoginTemplate { next, failed: false })).into_response()
+}
+
+/// Only paths on this server are followed after signing in, so `/login`
+/// can't be used to bounce visitors elsewhere.
+fn local_path(next: &str) -> &str {
+    if next.starts_with('/') && !next.starts_with("//") && !next.starts_with("/\\") {
+        next
+    } else {
+        "/"
+    }
+}
+
+async fn login_form(Query(query): Query<LoginQuery>) -> WebTemplate<LoginTemplate> {
+    WebTemplate(LoginTemplate {
+        next: local_path(query.next.as_deref().unwrap_or("/")).to_string(),
+        failed: false,
+    })
+}
+
+/// Signs the browser in with the API key, for the pages listed in
+/// `SIPP_AUTH_ENDPOINTS`.
+async fn login(State(state): State<AppState>, Form(form): Form<LoginForm>) -> Response {
+    let Some(server_key) = &state.server_config.api_key else {
+        return (
+            StatusCode::FORBIDDEN,
+            Html("<h1>Forbidden</h1><p>No API key configured on server</p>".to_string()),
+        )
+            .into_response();
+    };
+    let next = local_path(&form.next).to_string();
+    if !bool::from(form.key.as_bytes().ct_eq(server_key.as_bytes())) {
+        return (StatusCode::UNAUTHORIZED, WebTemplate(LoginTemplate { next, failed: true })).into_response();
+    }
+    let token = state.session_signer.sign(SESSION_SUBJECT, unix_now() + SESSION_TTL_SECS);
+    let cookie = format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+        SESSION_COOKIE, token, SESSION_TTL_SECS
+    );
+    ([(header::SET_COOKIE, cookie)], Redirect::to(&next)).into_response()
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    include_content: Option<bool>,
+    since: Option<String>,
+    before: Option<String>,
+    /// Only lists snippets with exactly this tag.
+    tag: Option<String>,
+}
+
+/// Parses an optional `since`/`before` bound given in Unix seconds.
+fn parse_timestamp(param: &str, value: Option<&str>) -> Result<Option<i64>, String> {
+    value
+        .map(|v| {
+            v.trim()
+                .parse::<i64>()
+                .map_err(|_| format!("{} must be a Unix timestamp in seconds, got '{}'", param, v))
+        })
+        .transpose()
+}
+
+/// Resolves the client IP from the socket address. When that is one of the
+/// `trusted` proxies, `X-Forwarded-For` is followed back from its last entry
+/// past any other trusted proxies; anyone else could put anything in it.
+fn client_ip(request: &Request, trusted: &[IpAddr]) -> Option<IpAddr> {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())?;
+    if !trusted.contains(&peer) {
+        return Some(peer);
+    }
+    let forwarded: Vec<&str> = request
+        .headers()
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .collect();
+    let mut client = peer;
+    for entry in forwarded.into_iter().rev() {
+        let Ok(ip) = entry.trim().parse::<IpAddr>() else {
+            break;
+        };
+        client = ip;
+        if !trusted.contains(&ip) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+async fn rate_limit_create(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(ip) = client_ip(&request, &state.server_config.trusted_proxies) else {
+        return next.run(request).await;
+    };
+    match state.create_limiter.check(ip) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            let retry_after = [(header::RETRY_AFTER, wait.as_secs().max(1).to_string())];
+            if request.uri().path().starts_with("/api/") {
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    retry_after,
+                    Json(serde_json::json!({"error": "Too many snippets created, try again later"})),
+                )
+                    .into_response()
+            } else {
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    retry_after,
+                    Html("<h1>Too many requests</h1><p>Please wait a moment before creating another snippet</p>".to_string()),
+                )
+                    .into_response()
+            }
+        }
     }
 }
 
 async fn api_list_snippets(
     State(state): State<AppState>,
-) -> Result<Json<Vec<Snippet>>, (StatusCode, Json<serde_json::Value>)> {
-    match db::get_all_snippets(&state.db) {
-        Ok(snippets) => Ok(Json(snippets)),
-        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": "Internal server error"})))),
+    Query(query): Query<ListQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let internal_error = || (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": "Internal server error"})));
+    let limit = query.limit.filter(|l| *l >= 0).unwrap_or(-1);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let bad_request = |e: String| (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e})));
+    let since = parse_timestamp("since", query.since.as_deref()).map_err(bad_request)?;
+    let before = parse_timestamp("before", query.before.as_deref()).map_err(bad_request)?;
+    let tag = query.tag.as_deref().map(str::trim).filter(|t| !t.is_empty());
+    let total = db::count_snippets_in_range(&state.db, since, before, tag).map_err(|_| internal_error())?;
+    let total_header = [("x-total-count", total.to_string())];
+    if query.include_content.unwrap_or(true) {
+        match db::get_snippets_in_range(&state.db, since, before, tag, limit, offset) {
+            Ok(mut snippets) => {
+                // Listing never reveals protected content; fetch those one at a time
+                snippets.iter_mut().filter(|s| s.is_protected()).for_each(|s| s.content.clear());
+                let snippets: Vec<Snippet> =
+                    snippets.into_iter().map(|s| state.server_config.with_url(s)).collect();
+                Ok((total_header, Json(snippets)).into_response())
+            }
+            Err(_) => Err(internal_error()),
+        }
+    } else {
+        match db::get_snippet_metas_in_range(&state.db, since, before, tag, limit, offset) {
+            Ok(mut metas) => {
+                for meta in &mut metas {
+                    meta.url = Some(state.server_config.snippet_location(&meta.short_id));
+                }
+                Ok((total_header, Json(metas)).into_response())
+            }
+            Err(_) => Err(internal_error()),
+        }
     }
 }
 
+/// Streams every matching snippet as newline-delimited JSON, reading rows as
+/// the client consumes them so large stores can be exported without
+/// buffering them in memory. Takes the same filters as `api_list_snippets`
+/// except `limit`, `offset`, and `include_content`.
+async fn api_stream_snippets(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let bad_request = |e: String| (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e})));
+    let since = parse_timestamp("since", query.since.as_deref()).map_err(bad_request)?;
+    let before = parse_timestamp("before", query.before.as_deref()).map_err(bad_request)?;
+    let tag = query.tag.as_deref().map(str::trim).filter(|t| !t.is_empty()).map(str::to_string);
+
+    // A small buffer keeps the reader at most a few rows ahead of the client
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Vec<u8>, std::io::Error>>(16);
+    let db = state.db.clone();
+    let config = state.server_config.clone();
+    tokio::task::spawn_blocking(move || {
+        let result = db::for_each_snippet_in_range(&db, since, before, tag.as_deref(), |mut snippet| {
+            // Like listing, streaming never reveals protected content
+            if snippet.is_protected() {
+                snippet.content.clear();
+            }
+            let mut line = serde_json::to_vec(&config.with_url(snippet)).unwrap_or_default();
+            line.push(b'\n');
+            // A closed channel means the client went away
+            tx.blocking_send(Ok(line)).is_ok()
+        });
+        if let Err(e) = result {
+            tracing::error!("Streaming snippets failed: {}", e);
+            // Aborts the response so the client can tell the export is incomplete
+            let _ = tx.blocking_send(Err(std::io::Error::other(e.to_string())));
+        }
+    });
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|item| (item, rx)) });
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], axum::body::Body::from_stream(stream)).into_response())
+}
+
 async fn api_get_snippet(
     State(state): State<AppState>,
     Path(short_id): Path<String>,
-) -> Result<Json<Snippet>, (StatusCode, Json<serde_json::Value>)> {
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
     match db::get_snippet_by_short_id(&state.db, &short_id) {
-        Ok(Some(snippet)) => Ok(Json(snippet)),
+        Ok(Some(snippet)) => {
+            if let Some(hash) = &snippet.password_hash
+                && !password_header_matches(&headers, hash).await
+            {
+                return Err((StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Password required"}))));
+            }
+            // The JSON body carries the view count, so its tag has to change with
+            // it. `If-Modified-Since` only tracks edits and may serve a stale count
+            let etag = snippet_etag(&snippet, &format!("json:{}", snippet.view_count));
+            let modified = last_modified(&snippet);
+            if is_fresh(&headers, &etag, &snippet) {
+                return Ok(not_modified(etag, modified));
+            }
+            Ok((
+                [(header::ETAG, etag)],
+                AppendHeaders(modified.map(|v| (header::LAST_MODIFIED, v))),
+                Json(state.server_config.with_url(snippet)),
+            )
+                .into_response())
+        }
+        Ok(None) => Err((StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Snippet not found"})))),
+        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": "Internal server error"})))),
+    }
+}
+
+async fn api_get_raw_snippet(
+    State(state): State<AppState>,
+    Path(short_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    match db::get_snippet_by_short_id(&state.db, &short_id) {
+        Ok(Some(snippet)) => {
+            if let Some(hash) = &snippet.password_hash
+                && !password_header_matches(&headers, hash).await
+            {
+                return Err((StatusCode::UNAUTHORIZED, Json(serde_json::json!({"error": "Password required"}))));
+            }
+            state.metrics.record_view();
+            if let Err(e) = db::record_view(&state.db, &snippet.short_id) {
+                tracing::warn!("Failed to record view of {}: {}", snippet.short_id, e);
+            }
+            let etag = snippet_etag(&snippet, "raw");
+            let modified = last_modified(&snippet);
+            if is_fresh(&headers, &etag, &snippet) {
+                return Ok(not_modified(etag, modified));
+            }
+            Ok(raw_response(&snippet, etag, modified))
+        }
         Ok(None) => Err((StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Snippet not found"})))),
         Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": "Internal server error"})))),
     }
@@ -205,13 +1575,266 @@ async fn api_get_snippet(
 struct ApiCreateSnippet {
     name: String,
     content: String,
+    #[serde(default)]
+    language: Option<String>,
+    #[serde(default)]
+    short_id: Option<String>,
+    #[serde(default)]
+    view_password: Option<String>,
+    /// Left unchanged by an update when absent.
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+    /// Remove ANSI escape sequences from the content before storing it.
+    #[serde(default)]
+    strip_ansi: bool,
+    /// `base64` sends arbitrary bytes, which are stored and served as-is.
+    #[serde(default)]
+    encoding: db::ContentEncoding,
+}
+
+impl ApiCreateSnippet {
+    fn language(&self) -> Option<&str> {
+        self.language.as_deref().map(str::trim).filter(|l| !l.is_empty())
+    }
+
+    fn short_id(&self) -> Option<&str> {
+        custom_short_id(self.short_id.as_deref())
+    }
+
+    fn tags(&self) -> &[String] {
+        self.tags.as_deref().unwrap_or_default()
+    }
+
+    /// How many bytes will be stored: the decoded length of base64 content.
+    /// `None` when base64 content doesn't decode.
+    fn stored_len(&self) -> Option<usize> {
+        match self.encoding {
+            db::ContentEncoding::Utf8 => Some(self.content.len()),
+            db::ContentEncoding::Base64 => db::decode_base64(&self.content).map(|bytes| bytes.len()),
+        }
+    }
+
+    /// Cleans the content when the request asked for it, before any size
+    /// checks so those see what will be stored. Base64 content is left alone.
+    fn apply_strip_ansi(&mut self) {
+        if self.strip_ansi && self.encoding.is_utf8() {
+            self.content = highlight::strip_ansi(&self.content);
+        }
+    }
+}
+
+fn create_error(e: db::DbError) -> (StatusCode, Json<serde_json::Value>) {
+    match e {
+        db::DbError::ShortIdTaken(id) => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({"error": format!("Short id '{}' is already taken", id)})),
+        ),
+        db::DbError::StoreFull(max) => (
+            StatusCode::INSUFFICIENT_STORAGE,
+            Json(serde_json::json!({
+                "error": format!("Snippet storage is full; this server holds at most {} snippets", max)
+            })),
+        ),
+        db::DbError::InvalidBase64 => (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e.to_string()}))),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": "Internal server error"}))),
+    }
+}
+
+/// The checks a create body must pass before anything is stored.
+fn validate_create(config: &ServerConfig, body: &ApiCreateSnippet) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let Some(len) = body.stored_len() else {
+        return Err(create_error(db::DbError::InvalidBase64));
+    };
+    if len > config.max_content_size {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(serde_json::json!({
+                "error": format!("Content too large. Maximum size is {} bytes", config.max_content_size)
+            })),
+        ));
+    }
+    config
+        .validate_name(&body.name)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))))?;
+    if let Some(short_id) = body.short_id() {
+        validate_short_id(short_id)
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))))?;
+    }
+    Ok(())
+}
+
+/// Runs the create checks without storing anything, so clients can find out
+/// whether a large upload would be accepted before sending it for real.
+async fn api_validate_snippet(
+    State(state): State<AppState>,
+    Json(mut body): Json<ApiCreateSnippet>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    body.apply_strip_ansi();
+    validate_create(&state.server_config, &body)?;
+    Ok(Json(serde_json::json!({"ok": true})))
+}
+
+async fn api_create_snippet(
+    State(state): State<AppState>,
+    Json(mut body): Json<ApiCreateSnippet>,
+) -> Result<(StatusCode, [(HeaderName, String); 1], Json<Snippet>), (StatusCode, Json<serde_json::Value>)> {
+    body.apply_strip_ansi();
+    validate_create(&state.server_config, &body)?;
+    let password_hash = hash_view_password(body.view_password.as_deref()).await;
+    let created = db::create_snippet_capped(
+        &state.db,
+        &db::NewSnippet {
+            short_id: body.short_id(),
+            short_id_format: state.server_config.short_id_format,
+            name: &body.name,
+            content: &body.content,
+            language: body.language(),
+            tags: body.tags(),
+            password_hash: password_hash.as_deref(),
+            encoding: body.encoding,
+        },
+        state.server_config.snippet_cap,
+    );
+    let snippet = created.map_err(create_error)?;
+    state.metrics.record_created(1);
+    let location = state.server_config.snippet_location(&snippet.short_id);
+    Ok((StatusCode::CREATED, [(header::LOCATION, location)], Json(state.server_config.with_url(snippet))))
+}
+
+async fn api_batch_create_snippets(
+    State(state): State<AppState>,
+    Json(mut body): Json<Vec<ApiCreateSnippet>>,
+) -> Result<(StatusCode, Json<Vec<Snippet>>), (StatusCode, Json<serde_json::Value>)> {
+    let config = &state.server_config;
+    body.iter_mut().for_each(ApiCreateSnippet::apply_strip_ansi);
+    if body.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": "Batch must contain at least one snippet"}))));
+    }
+    if let Some(index) = body.iter().position(|s| s.stored_len().is_none()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": format!("Item {}: {}", index, db::DbError::InvalidBase64)})),
+        ));
+    }
+    if let Some(index) = body.iter().position(|s| s.stored_len().unwrap_or_default() > config.max_content_size) {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(serde_json::json!({
+                "error": format!("Content of item {} too large. Maximum size is {} bytes", index, config.max_content_size)
+            })),
+        ));
+    }
+    for (index, snippet) in body.iter().enumerate() {
+        config.validate_name(&snippet.name).map_err(|e| {
+            (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": format!("Item {}: {}", index, e)})))
+        })?;
+    }
+    let total: usize = body.iter().map(|s| s.content.len()).sum();
+    if total > config.max_batch_size {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(serde_json::json!({
+                "error": format!("Batch too large. Maximum total size is {} bytes", config.max_batch_size)
+            })),
+        ));
+    }
+    for (index, short_id) in body.iter().enumerate().filter_map(|(i, s)| Some((i, s.short_id()?))) {
+        validate_short_id(short_id).map_err(|e| {
+            (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": format!("Item {}: {}", index, e)})))
+        })?;
+    }
+    let mut password_hashes = Vec::with_capacity(body.len());
+    for snippet in &body {
+        password_hashes.push(hash_view_password(snippet.view_password.as_deref()).await);
+    }
+    let snippets: Vec<db::NewSnippet> = body
+        .iter()
+        .zip(&password_hashes)
+        .map(|(s, password_hash)| db::NewSnippet {
+            short_id: s.short_id(),
+            short_id_format: config.short_id_format,
+            name: &s.name,
+            content: &s.content,
+            language: s.language(),
+            tags: s.tags(),
+            password_hash: password_hash.as_deref(),
+            encoding: s.encoding,
+        })
+        .collect();
+    let created = db::create_snippets_capped(&state.db, &snippets, config.snippet_cap)
+        .map_err(create_error)?;
+    state.metrics.record_created(created.len() as u64);
+    let created: Vec<Snippet> = created.into_iter().map(|s| config.with_url(s)).collect();
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+async fn api_delete_snippet(
+    State(state): State<AppState>,
+    Path(short_id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    match db::delete_snippet_by_short_id(&state.db, &short_id) {
+        Ok(true) => {
+            state.highlight_cache.invalidate(&short_id);
+            Ok(Json(serde_json::json!({"deleted": true})))
+        }
+        Ok(false) => Err((StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Snippet not found"})))),
+        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": "Internal server error"})))),
+    }
+}
+
+async fn api_update_snippet(
+    State(state): State<AppState>,
+    Path(short_id): Path<String>,
+    Json(mut body): Json<ApiCreateSnippet>,
+) -> Result<Json<Snippet>, (StatusCode, Json<serde_json::Value>)> {
+    body.apply_strip_ansi();
+    let Some(len) = body.stored_len() else {
+        return Err(create_error(db::DbError::InvalidBase64));
+    };
+    if len > state.server_config.max_content_size {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(serde_json::json!({
+                "error": format!("Content too large. Maximum size is {} bytes", state.server_config.max_content_size)
+            })),
+        ));
+    }
+    state.server_config
+        .validate_name(&body.name)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))))?;
+    match db::update_snippet_by_short_id(
+        &state.db,
+        &short_id,
+        &body.name,
+        &body.content,
+        body.encoding,
+        body.language(),
+        body.tags.as_deref(),
+    ) {
+        Ok(Some(snippet)) => {
+            state.highlight_cache.invalidate(&short_id);
+            Ok(Json(state.server_config.with_url(snippet)))
+        }
+        Ok(None) => Err((StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Snippet not found"})))),
+        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": "Internal server error"})))),
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiPatchSnippet {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    content: Option<String>,
 }
 
-async fn api_create_snippet(
+async fn api_patch_snippet(
     State(state): State<AppState>,
-    Json(body): Json<ApiCreateSnippet>,
-) -> Result<(StatusCode, Json<Snippet>), (StatusCode, Json<serde_json::Value>)> {
-    if body.content.len() > state.server_config.max_content_size {
+    Path(short_id): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<ApiPatchSnippet>,
+) -> Result<Json<Snippet>, (StatusCode, Json<serde_json::Value>)> {
+    if body.content.as_ref().is_some_and(|c| c.len() > state.server_config.max_content_size) {
         return Err((
             StatusCode::PAYLOAD_TOO_LARGE,
             Json(serde_json::json!({
@@ -219,95 +1842,176 @@ async fn api_create_snippet(
             })),
         ));
     }
-    match db::create_snippet(&state.db, &body.name, &body.content) {
-        Ok(snippet) => Ok((StatusCode::CREATED, Json(snippet))),
+    if let Some(name) = &body.name {
+        state.server_config
+            .validate_name(name)
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))))?;
+    }
+    match db::patch_snippet_by_short_id(&state.db, &short_id, body.name.as_deref(), body.content.as_deref()) {
+        Ok(Some(snippet)) => {
+            state.highlight_cache.invalidate(&short_id);
+            let snippet = hide_protected_content(snippet, &headers).await;
+            Ok(Json(state.server_config.with_url(snippet)))
+        }
+        Ok(None) => Err((StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Snippet not found"})))),
         Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": "Internal server error"})))),
     }
 }
 
-async fn api_delete_snippet(
-    State(state): State<AppState>,
-    Path(short_id): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
-    match db::delete_snippet_by_short_id(&state.db, &short_id) {
-        Ok(true) => Ok(Json(serde_json::json!({"deleted": true}))),
-        Ok(false) => Err((StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Snippet not found"})))),
-        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": "Internal server error"})))),
-    }
+#[derive(Deserialize)]
+struct ApiRenameSnippet {
+    name: String,
 }
 
-async fn api_update_snippet(
+async fn api_rename_snippet(
     State(state): State<AppState>,
     Path(short_id): Path<String>,
-    Json(body): Json<ApiCreateSnippet>,
+    headers: HeaderMap,
+    Json(body): Json<ApiRenameSnippet>,
 ) -> Result<Json<Snippet>, (StatusCode, Json<serde_json::Value>)> {
-    if body.content.len() > state.server_config.max_content_size {
-        return Err((
-            StatusCode::PAYLOAD_TOO_LARGE,
-            Json(serde_json::json!({
-                "error": format!("Content too large. Maximum size is {} bytes", state.server_config.max_content_size)
-            })),
-        ));
-    }
-    match db::update_snippet_by_short_id(&state.db, &short_id, &body.name, &body.content) {
-        Ok(Some(snippet)) => Ok(Json(snippet)),
+    state.server_config
+        .validate_name(&body.name)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))))?;
+    match db::rename_snippet_by_short_id(&state.db, &short_id, &body.name) {
+        Ok(Some(snippet)) => {
+            // The name picks the syntax when no language is set
+            state.highlight_cache.invalidate(&short_id);
+            let snippet = hide_protected_content(snippet, &headers).await;
+            Ok(Json(state.server_config.with_url(snippet)))
+        }
         Ok(None) => Err((StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Snippet not found"})))),
         Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": "Internal server error"})))),
     }
 }
 
+/// Puts `route` behind the API key check when `name` is listed in
+/// `SIPP_AUTH_ENDPOINTS`.
+fn with_auth_if(
+    state: &AppState,
+    name: &str,
+    route: MethodRouter<AppState>,
+) -> MethodRouter<AppState> {
+    if state.server_config.requires_auth(name) {
+        route.route_layer(middleware::from_fn_with_state(state.clone(), require_api_key))
+    } else {
+        route
+    }
+}
+
 fn build_api_routes(state: &AppState) -> Router<AppState> {
     let config = &state.server_config;
 
     let auth_layer = middleware::from_fn_with_state(state.clone(), require_api_key);
+    let rate_limit_layer = middleware::from_fn_with_state(state.clone(), rate_limit_create);
 
     // /api/snippets — GET (api_list) and POST (api_create)
+    // /api/snippets/stream — GET (api_list)
+    // /api/snippets/validate — POST (api_create)
     let list_authed = config.requires_auth("api_list");
     let create_authed = config.requires_auth("api_create");
+    let batch_authed = config.requires_auth("api_batch_create");
+    // In read-only mode the write handlers are never registered
+    let (create_route, validate_route, batch_route) = if config.read_only {
+        (post(read_only_api), post(read_only_api), post(read_only_api))
+    } else {
+        (
+            post(api_create_snippet.layer(config.body_limit())).layer(rate_limit_layer.clone()),
+            post(api_validate_snippet.layer(config.body_limit())),
+            // JSON escaping can make the request body larger than the content it carries
+            post(api_batch_create_snippets.layer(DefaultBodyLimit::max(config.max_batch_size.saturating_mul(2))))
+                .layer(rate_limit_layer),
+        )
+    };
 
-    // /api/snippets/{short_id} — GET (api_get), PUT (api_update), and DELETE (api_delete)
+    // /api/snippets/{short_id} — GET (api_get), PUT and PATCH (api_update), and DELETE (api_delete)
+    // /api/snippets/{short_id}/raw — GET (api_raw)
+    // /api/snippets/{short_id}/rename — POST (api_update)
     let get_authed = config.requires_auth("api_get");
+    let raw_authed = config.requires_auth("api_raw");
     let update_authed = config.requires_auth("api_update");
     let delete_authed = config.requires_auth("api_delete");
 
+    let (update_route, rename_route, delete_route) = if config.read_only {
+        (put(read_only_api).patch(read_only_api), post(read_only_api), delete(read_only_api))
+    } else {
+        (
+            put(api_update_snippet).patch(api_patch_snippet).layer(config.body_limit()),
+            post(api_rename_snippet),
+            delete(api_delete_snippet),
+        )
+    };
+
     // Build authed router
     let mut authed = Router::new();
     if list_authed {
-        authed = authed.route("/api/snippets", get(api_list_snippets));
+        authed = authed
+            .route("/api/snippets", get(api_list_snippets))
+            .route("/api/snippets/stream", get(api_stream_snippets));
     }
     if create_authed {
-        authed = authed.route("/api/snippets", post(api_create_snippet));
+        authed = authed
+            .route("/api/snippets", create_route.clone())
+            .route("/api/snippets/validate", validate_route.clone());
+    }
+    if batch_authed {
+        authed = authed.route("/api/snippets/batch", batch_route.clone());
     }
     if get_authed {
         authed = authed.route("/api/snippets/{short_id}", get(api_get_snippet));
     }
+    if raw_authed {
+        authed = authed.route("/api/snippets/{short_id}/raw", get(api_get_raw_snippet));
+    }
     if update_authed {
-        authed = authed.route("/api/snippets/{short_id}", put(api_update_snippet));
+        authed = authed
+            .route("/api/snippets/{short_id}", update_route.clone())
+            .route("/api/snippets/{short_id}/rename", rename_route.clone());
     }
     if delete_authed {
-        authed = authed.route("/api/snippets/{short_id}", delete(api_delete_snippet));
+        authed = authed.route("/api/snippets/{short_id}", delete_route.clone());
     }
-    let authed = authed.route_layer(auth_layer);
+    // `route_layer` panics on a router without routes, e.g. SIPP_AUTH_ENDPOINTS=none
+    let authed = if authed.has_routes() {
+        authed.route_layer(auth_layer)
+    } else {
+        authed
+    };
 
     // Build open router
     let mut open = Router::new();
     if !list_authed {
-        open = open.route("/api/snippets", get(api_list_snippets));
+        open = open
+            .route("/api/snippets", get(api_list_snippets))
+            .route("/api/snippets/stream", get(api_stream_snippets));
     }
     if !create_authed {
-        open = open.route("/api/snippets", post(api_create_snippet));
+        open = open
+            .route("/api/snippets", create_route)
+            .route("/api/snippets/validate", validate_route);
+    }
+    if !batch_authed {
+        open = open.route("/api/snippets/batch", batch_route);
     }
     if !get_authed {
         open = open.route("/api/snippets/{short_id}", get(api_get_snippet));
     }
+    if !raw_authed {
+        open = open.route("/api/snippets/{short_id}/raw", get(api_get_raw_snippet));
+    }
     if !update_authed {
-        open = open.route("/api/snippets/{short_id}", put(api_update_snippet));
+        open = open
+            .route("/api/snippets/{short_id}", update_route)
+            .route("/api/snippets/{short_id}/rename", rename_route);
     }
     if !delete_authed {
-        open = open.route("/api/snippets/{short_id}", delete(api_delete_snippet));
+        open = open.route("/api/snippets/{short_id}", delete_route);
     }
 
-    authed.merge(open)
+    let api = authed.merge(open);
+    match config.cors_layer() {
+        Some(cors) => api.layer(cors),
+        None => api,
+    }
 }
 
 fn mime_from_path(path: &str) -> &'static str {
@@ -328,33 +2032,266 @@ fn mime_from_path(path: &str) -> &'static str {
     }
 }
 
-async fn serve_assets(Path(path): Path<String>) -> Response {
-    match Assets::get(&path) {
-        Some(file) => {
-            let mime = mime_from_path(&path);
-            ([(header::CONTENT_TYPE, mime)], file.data).into_response()
+/// Turns a captured asset path into an embed lookup key, rejecting absolute
+/// paths and `..` components and dropping empty or `.` segments.
+fn normalize_asset_path(path: &str) -> Option<String> {
+    if path.starts_with('/') || path.contains('\\') {
+        return None;
+    }
+    let mut segments = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            other => segments.push(other),
         }
+    }
+    if segments.is_empty() {
+        return None;
+    }
+    Some(segments.join("/"))
+}
+
+/// Serves an embedded file with a content-hash ETag, answering `304` when the
+/// client already has it.
+fn embedded_response(
+    file: rust_embed::EmbeddedFile,
+    path: &str,
+    headers: &HeaderMap,
+    cache_control: &'static str,
+) -> Response {
+    let hash: String = file.metadata.sha256_hash()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let etag = format!("\"{}\"", hash);
+    if etag_matches(headers, &etag) {
+        return (
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control.to_string())],
+        )
+            .into_response();
+    }
+    let mime = mime_from_path(path);
+    (
+        [
+            (header::CONTENT_TYPE, mime.to_string()),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, cache_control.to_string()),
+        ],
+        file.data,
+    )
+        .into_response()
+}
+
+async fn serve_assets(Path(path): Path<String>, headers: HeaderMap) -> Response {
+    let Some(path) = normalize_asset_path(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match Assets::get(&path) {
+        // Images and fonts never change under the same name
+        Some(file) => embedded_response(file, &path, &headers, "public, max-age=31536000, immutable"),
         None => StatusCode::NOT_FOUND.into_response(),
     }
 }
 
-async fn serve_static(Path(path): Path<String>) -> Response {
+async fn serve_static(Path(path): Path<String>, headers: HeaderMap) -> Response {
+    let Some(path) = normalize_asset_path(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
     match Static::get(&path) {
-        Some(file) => {
-            let mime = mime_from_path(&path);
-            ([(header::CONTENT_TYPE, mime)], file.data).into_response()
-        }
+        // The stylesheet changes between releases, so clients revalidate it
+        Some(file) => embedded_response(file, &path, &headers, "public, no-cache"),
         None => StatusCode::NOT_FOUND.into_response(),
     }
 }
 
+/// Compresses responses according to `Accept-Encoding`. The default predicate
+/// already skips raster images; fonts are compressed formats too.
+fn compression_layer() -> CompressionLayer<impl Predicate> {
+    let predicate = DefaultPredicate::new()
+        .and(NotForContentType::const_new("font/woff"))
+        .and(NotForContentType::const_new("font/woff2"));
+    CompressionLayer::new()
+        .gzip(true)
+        .deflate(true)
+        .compress_when(predicate)
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    println!("Shutting down");
+}
+
+/// Puts a page behind [`require_browser_key`] when `name` is listed in
+/// `SIPP_AUTH_ENDPOINTS`.
+fn with_browser_auth_if(
+    state: &AppState,
+    name: &str,
+    route: MethodRouter<AppState>,
+) -> MethodRouter<AppState> {
+    if state.server_config.requires_auth(name) {
+        route.route_layer(middleware::from_fn_with_state(state.clone(), require_browser_key))
+    } else {
+        route
+    }
+}
+
+/// Builds the whole app: pages, API routes, and the middleware around them.
+fn build_app(state: AppState) -> Router {
+    let api_routes = build_api_routes(&state);
+    let list_route = with_browser_auth_if(&state, "list_html", get(list_snippets_html));
+    let metrics_route = with_auth_if(&state, "metrics", get(metrics));
+    let (edit_route, delete_route, create_route) = if state.server_config.read_only {
+        (
+            get(read_only_html).post(read_only_html),
+            get(read_only_html).post(read_only_html),
+            post(read_only_html),
+        )
+    } else {
+        (
+            with_browser_auth_if(
+                &state,
+                "edit_html",
+                get(edit_snippet_form).post(edit_snippet.layer(state.server_config.body_limit())),
+            ),
+            with_browser_auth_if(&state, "delete_html", get(delete_snippet_form).post(delete_snippet)),
+            post(create_snippet.layer(state.server_config.body_limit()))
+                .layer(middleware::from_fn_with_state(state.clone(), rate_limit_create)),
+        )
+    };
+    let log_requests = state.server_config.log_requests;
+
+    let mut app = Router::new()
+        .route("/", get(index))
+        .route("/admin", get(admin))
+        .route("/about", get(about))
+        .route("/list", list_route)
+        .route("/login", get(login_form).post(login))
+        .route("/version", get(version))
+        .route("/metrics", metrics_route)
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/s/{short_id}", get(view_snippet))
+        .route("/s/{short_id}/raw", get(raw_snippet))
+        .route("/s/{short_id}/edit", edit_route)
+        .route("/s/{short_id}/delete", delete_route)
+        .route("/s/{short_id}/unlock", post(unlock_snippet))
+        .route("/snippets", create_route)
+        .merge(api_routes)
+        .route("/assets/{*path}", get(serve_assets))
+        .route("/static/{*path}", get(serve_static))
+        .fallback(fallback)
+        .layer(middleware::from_fn(add_request_id_to_errors))
+        .layer(compression_layer())
+        .layer(middleware::from_fn_with_state(state.clone(), count_requests))
+        .with_state(state);
+
+    if log_requests {
+        // Spans only record the method and path; the auth headers are also marked
+        // sensitive so they are never printed if a layer formats the request.
+        app = app
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(|request: &Request| {
+                        let request_id = request
+                            .headers()
+                            .get(REQUEST_ID_HEADER)
+                            .and_then(|v| v.to_str().ok())
+                            .unwrap_or_default();
+                        tracing::info_span!(
+                            "request",
+                            method = %request.method(),
+                            path = %request.uri().path(),
+                            request_id = %request_id,
+                        )
+                    })
+                    .on_response(
+                        DefaultOnResponse::new()
+                            .level(Level::INFO)
+                            .latency_unit(LatencyUnit::Millis),
+                    ),
+            )
+            .layer(SetSensitiveRequestHeadersLayer::new([
+                HeaderName::from_static("x-api-key"),
+                HeaderName::from_static(SNIPPET_PASSWORD_HEADER),
+                header::AUTHORIZATION,
+                header::COOKIE,
+            ]));
+    }
+    // Outermost, so the id is set before the trace span is made and an
+    // incoming `X-Request-Id` is kept as is
+    let request_id = HeaderName::from_static(REQUEST_ID_HEADER);
+    app.layer(PropagateRequestIdLayer::new(request_id.clone()))
+        .layer(SetRequestIdLayer::new(request_id, MakeRequestUuid))
+}
+
 pub async fn run(host: String, port: u16) {
+    serve(Some(host), Some(port)).await
+}
+
+/// Starts the server, falling back to `SIPP_HOST`/`SIPP_PORT` for any bind
+/// setting that isn't given explicitly.
+pub async fn serve(host: Option<String>, port: Option<u16>) {
     dotenvy::dotenv().ok();
 
-    let server_config = ServerConfig::from_env();
+    let mut server_config = match ServerConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(host) = host {
+        server_config.host = host;
+    }
+    if let Some(port) = port {
+        server_config.port = port;
+    }
+
+    let tls_config = match &server_config.tls {
+        Some((cert, key)) => match tls::load_config(cert, key) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    if server_config.log_requests {
+        tracing_subscriber::fmt()
+            .with_env_filter(
+                tracing_subscriber::EnvFilter::try_from_default_env()
+                    .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+            )
+            .init();
+    }
 
     // Validate endpoint names
-    let known = ["api_list", "api_create", "api_get", "api_update", "api_delete", "all", "none"];
+    let known = ["api_list", "api_create", "api_batch_create", "api_get", "api_raw", "api_update", "api_delete", "list_html", "edit_html", "delete_html", "metrics", "all", "none"];
     for name in &server_config.auth_endpoints {
         if !known.contains(&name.as_str()) {
             eprintln!("Warning: unknown auth endpoint name '{}' in SIPP_AUTH_ENDPOINTS", name);
@@ -372,34 +2309,742 @@ pub async fn run(host: String, port: u16) {
         println!("Auth: enabled for endpoints: {}", names.join(", "));
     }
 
+    if server_config.read_only {
+        println!("Read-only: creating, editing, and deleting snippets is disabled");
+    }
+
+    if let Some(url) = &server_config.public_url {
+        println!("Public URL: {}", url);
+    }
     println!("Max content size: {} bytes", server_config.max_content_size);
+    println!("Short id length: {} characters", server_config.short_id_format.len);
+    if let Some(cap) = server_config.snippet_cap {
+        match cap.overflow {
+            db::Overflow::Reject => println!("Max snippets: {}", cap.max),
+            db::Overflow::EvictOldest => println!("Max snippets: {} (evicting the oldest)", cap.max),
+        }
+    }
 
-    let state = AppState {
-        db: db::init_db().expect("Failed to initialize database"),
-        highlighter: Arc::new(Highlighter::new()),
-        server_config,
-    };
+    if server_config.create_rate_per_min > 0 {
+        println!("Create rate limit: {} per minute per client", server_config.create_rate_per_min);
+    }
 
-    let api_routes = build_api_routes(&state);
+    if !server_config.cors_origins.is_empty() {
+        println!("CORS: allowed origins: {}", server_config.cors_origins.join(", "));
+    }
 
-    let app = Router::new()
-        .route("/", get(index))
-        .route("/admin", get(admin))
-        .route("/s/{short_id}", get(view_snippet))
-        .route("/snippets", post(create_snippet))
-        .merge(api_routes)
-        .route("/assets/{*path}", get(serve_assets))
-        .route("/static/{*path}", get(serve_static))
-        .with_state(state);
+    let state = AppState::new(server_config, db::init_db().expect("Failed to initialize database"));
+    let db = state.db.clone();
+    let addr = format!("{}:{}", state.server_config.host, state.server_config.port);
+    let app = build_app(state);
 
-    let addr = format!("{}:{}", host, port);
     let listener = tokio::net::TcpListener::bind(&addr)
         .await
         .unwrap_or_else(|_| panic!("Failed to bind to {}", addr));
 
-    println!("Server running at http://{}:{}", host, port);
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    match tls_config {
+        Some(config) => {
+            let listener = TlsListener::new(listener, config).expect("Failed to start TLS listener");
+            println!("Server running at https://{} (serving HTTPS)", addr);
+            // A no-op `tap_io` lets axum pass the peer address on as `ConnectInfo`
+            axum::serve(listener.tap_io(|_| {}), service)
+                .with_graceful_shutdown(shutdown_signal())
+                .await
+                .expect("Failed to start server");
+        }
+        None => {
+            println!("Server running at http://{}", addr);
+            axum::serve(listener, service)
+                .with_graceful_shutdown(shutdown_signal())
+                .await
+                .expect("Failed to start server");
+        }
+    }
 
-    axum::serve(listener, app)
-        .await
-        .expect("Failed to start server");
+    if let Err(e) = db::checkpoint(&db) {
+        eprintln!("Warning: final WAL checkpoint failed: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use std::collections::HashMap;
+    use tower::ServiceExt;
+
+    /// Builds the app on a fresh in-memory database with `settings` as its
+    /// environment, without request logging.
+    fn app(settings: &[(&str, &str)]) -> Router {
+        build_app(state(settings))
+    }
+
+    /// The state [`app`] builds its router from.
+    fn state(settings: &[(&str, &str)]) -> AppState {
+        let mut env: HashMap<String, String> =
+            settings.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        env.entry("SIPP_LOG".to_string()).or_insert_with(|| "off".to_string());
+        let config = ServerConfig::from_lookup(|name| env.get(name).cloned()).unwrap();
+        let db = db::init_db_in_memory().unwrap();
+        AppState::new(config, db)
+    }
+
+    async fn send(app: &Router, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    fn create_request(content: &str, api_key: Option<&str>) -> Request<Body> {
+        let mut builder = Request::post("/api/snippets").header(header::CONTENT_TYPE, "application/json");
+        if let Some(key) = api_key {
+            builder = builder.header("x-api-key", key);
+        }
+        let body = serde_json::json!({"name": "hello.rs", "content": content});
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    /// Sends a browser request, with `cookie` if given, and returns the page.
+    async fn page(app: &Router, method: &str, uri: &str, cookie: Option<&str>, form: &str) -> (StatusCode, HeaderMap, String) {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+        if let Some(cookie) = cookie {
+            builder = builder.header(header::COOKIE, cookie);
+        }
+        let response = app.clone().oneshot(builder.body(Body::from(form.to_string())).unwrap()).await.unwrap();
+        let (status, headers) = (response.status(), response.headers().clone());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, headers, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    fn request(method: &str, uri: &str, api_key: Option<&str>) -> Request<Body> {
+        let mut builder = Request::builder().method(method).uri(uri);
+        if let Some(key) = api_key {
+            builder = builder.header("x-api-key", key);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn responses_are_gzipped_when_the_client_asks() {
+        let router = app(&[]);
+        let encoding = async |uri: &str, accept: Option<&str>| {
+            let mut builder = Request::get(uri);
+            if let Some(accept) = accept {
+                builder = builder.header(header::ACCEPT_ENCODING, accept);
+            }
+            let response = router.clone().oneshot(builder.body(Body::empty()).unwrap()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+            response.headers().get(header::CONTENT_ENCODING).map(|v| v.to_str().unwrap().to_string())
+        };
+
+        assert_eq!(encoding("/", Some("gzip")).await.as_deref(), Some("gzip"));
+        assert_eq!(encoding("/", None).await, None);
+        assert_eq!(encoding("/static/styles.css", Some("gzip")).await.as_deref(), Some("gzip"));
+        // Images are already compressed
+        assert_eq!(encoding("/assets/icon.png", Some("gzip")).await, None);
+    }
+
+    #[tokio::test]
+    async fn asset_paths_cannot_escape_their_folders() {
+        let router = app(&[]);
+        for uri in [
+            "/static/styles.css",
+            "/static/./styles.css",
+            "/assets/fonts/CommitMono-400-Regular.otf",
+            "/assets/fonts//CommitMono-400-Regular.otf",
+        ] {
+            assert_eq!(page(&router, "GET", uri, None, "").await.0, StatusCode::OK, "{}", uri);
+        }
+        for uri in [
+            "/static/../static/styles.css",
+            "/static/%2e%2e/static/styles.css",
+            "/static/%2E%2E%2Fstatic%2Fstyles.css",
+            "/assets/fonts/../icon.png",
+            "/assets/fonts/%2e%2e/icon.png",
+            "/assets/%2ffonts/CommitMono-400-Regular.otf",
+            "/assets/fonts%5cCommitMono-400-Regular.otf",
+        ] {
+            assert_eq!(page(&router, "GET", uri, None, "").await.0, StatusCode::NOT_FOUND, "{}", uri);
+        }
+    }
+
+    #[test]
+    fn cli_user_agents_match_by_product_name() {
+        let defaults: Vec<String> = DEFAULT_CLI_USER_AGENTS.iter().map(|s| s.to_string()).collect();
+        for ua in [
+            "curl/8.5.0",
+            "Wget/1.21.4",
+            "HTTPie/3.2.2",
+            "python-requests/2.31.0",
+            "fetch libfetch/2.0",
+            "aria2/1.37.0",
+            "Mozilla/5.0 (Windows NT 10.0; Microsoft Windows 10.0.22631; en-US) PowerShell/7.4.1",
+            "Mozilla/5.0 (Windows NT; Windows NT 10.0; en-US) WindowsPowerShell/5.1.22621.2506",
+        ] {
+            assert!(matches_user_agent(ua, &defaults), "{} should get raw content", ua);
+        }
+        for ua in [
+            "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0",
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Safari/605.1.15",
+            "curlish/1.0",
+            "",
+        ] {
+            assert!(!matches_user_agent(ua, &defaults), "{} should get HTML", ua);
+        }
+        assert!(matches_user_agent("MyTool/1.0", &["mytool".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn snippet_page_varies_by_user_agent() {
+        let app = app(&[("SIPP_AUTH_ENDPOINTS", "none")]);
+        let (_, created) = send(&app, create_request("x", None)).await;
+        let uri = format!("/s/{}", created["short_id"].as_str().unwrap());
+
+        let response = app.clone().oneshot(request("GET", &uri, None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get_all(header::VARY).iter().any(|v| v == "user-agent"));
+    }
+
+    #[tokio::test]
+    async fn create_get_delete_round_trip() {
+        let app = app(&[("SIPP_AUTH_ENDPOINTS", "none")]);
+
+        let (status, created) = send(&app, create_request("fn main() {}", None)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let short_id = created["short_id"].as_str().unwrap().to_string();
+        let uri = format!("/api/snippets/{}", short_id);
+
+        let (status, fetched) = send(&app, request("GET", &uri, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(fetched["content"], "fn main() {}");
+        assert_eq!(fetched["name"], "hello.rs");
+
+        let (status, _) = send(&app, request("DELETE", &uri, None)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = send(&app, request("GET", &uri, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn gated_endpoints_require_the_api_key() {
+        let app = app(&[("SIPP_API_KEY", "secret")]);
+
+        let (status, _) = send(&app, request("GET", "/api/snippets", None)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&app, request("GET", "/api/snippets", Some("wrong"))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&app, request("GET", "/api/snippets", Some("secret"))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Creating is open by default, deleting is not
+        let (status, created) = send(&app, create_request("x", None)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let uri = format!("/api/snippets/{}", created["short_id"].as_str().unwrap());
+        let (status, _) = send(&app, request("DELETE", &uri, None)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&app, request("DELETE", &uri, Some("secret"))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn gated_pages_sign_in_with_a_session_cookie() {
+        let app = app(&[("SIPP_API_KEY", "secret")]);
+        send(&app, create_request("x", None)).await;
+
+        let (status, _, body) = page(&app, "GET", "/list?page=1", None, "").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains(r#"action="/login""#) && body.contains(r#"value="/list?page=1""#));
+
+        let (status, _, _) = page(&app, "POST", "/login", None, "key=wrong&next=%2Flist").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, headers, _) = page(&app, "POST", "/login", None, "key=secret&next=%2Flist").await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert_eq!(headers[header::LOCATION], "/list");
+        let cookie = headers[header::SET_COOKIE].to_str().unwrap().split(';').next().unwrap().to_string();
+
+        let (status, _, body) = page(&app, "GET", "/list", Some(&cookie), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(" UTC &middot; /s/"));
+
+        // Signing in never redirects off the server
+        let (_, headers, _) = page(&app, "POST", "/login", None, "key=secret&next=%2F%2Fevil.example").await;
+        assert_eq!(headers[header::LOCATION], "/");
+    }
+
+    #[tokio::test]
+    async fn edit_page_signs_in_and_its_button_only_shows_when_usable() {
+        let keyed = app(&[("SIPP_API_KEY", "secret")]);
+        let (_, created) = send(&keyed, create_request("x", None)).await;
+        let short_id = created["short_id"].as_str().unwrap();
+
+        let (status, _, body) = page(&keyed, "GET", &format!("/s/{}/edit", short_id), None, "").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains(r#"action="/login""#));
+        let (_, _, body) = page(&keyed, "GET", &format!("/s/{}", short_id), None, "").await;
+        assert!(body.contains(r#"id="editBtn""#));
+
+        // Gated without a key to sign in with, editing can't succeed
+        let keyless = app(&[]);
+        let (_, created) = send(&keyless, create_request("x", None)).await;
+        let uri = format!("/s/{}", created["short_id"].as_str().unwrap());
+        let (_, _, body) = page(&keyless, "GET", &uri, None, "").await;
+        assert!(!body.contains(r#"id="editBtn""#));
+    }
+
+    #[tokio::test]
+    async fn delete_page_signs_in_and_its_button_only_shows_when_usable() {
+        let keyed = app(&[("SIPP_API_KEY", "secret")]);
+        let (_, created) = send(&keyed, create_request("x", None)).await;
+        let short_id = created["short_id"].as_str().unwrap();
+        let delete_uri = format!("/s/{}/delete", short_id);
+
+        let (status, _, body) = page(&keyed, "POST", &delete_uri, None, "").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains(r#"action="/login""#));
+        let (_, headers, _) = page(&keyed, "POST", "/login", None, "key=secret").await;
+        let cookie = headers[header::SET_COOKIE].to_str().unwrap().split(';').next().unwrap().to_string();
+        let (status, headers, _) = page(&keyed, "POST", &delete_uri, Some(&cookie), "").await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert_eq!(headers[header::LOCATION], "/list");
+        let (status, _) = send(&keyed, request("GET", &format!("/api/snippets/{}", short_id), None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let keyless = app(&[]);
+        let (_, created) = send(&keyless, create_request("x", None)).await;
+        let uri = format!("/s/{}", created["short_id"].as_str().unwrap());
+        let (_, _, body) = page(&keyless, "GET", &uri, None, "").await;
+        assert!(!body.contains(r#"id="deleteBtn""#));
+        let (status, _, _) = page(&keyless, "POST", &format!("{}/delete", uri), None, "").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn error_bodies_get_the_request_id_unless_too_large() {
+        let large = "x".repeat(MAX_ERROR_BODY);
+        let errors = Router::new()
+            .route("/small", get(|| async { (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": "bad"}))) }))
+            .route("/large", get(move || async move { (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": large}))) }))
+            .layer(middleware::from_fn(add_request_id_to_errors));
+        let with_id = |uri: &str| Request::get(uri).header(REQUEST_ID_HEADER, "req-1").body(Body::empty()).unwrap();
+
+        let (status, body) = send(&errors, with_id("/small")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, serde_json::json!({"error": "bad", "request_id": "req-1"}));
+
+        let (status, body) = send(&errors, with_id("/large")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"].as_str().map(str::len), Some(MAX_ERROR_BODY));
+        assert!(body.get("request_id").is_none());
+    }
+
+    #[test]
+    fn timestamps_format_as_utc_dates() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00 UTC");
+        assert_eq!(format_timestamp(1_792_114_356), "2026-10-16 01:32 UTC");
+    }
+
+    #[tokio::test]
+    async fn oversized_content_is_rejected() {
+        let app = app(&[("SIPP_AUTH_ENDPOINTS", "none"), ("SIPP_MAX_CONTENT_SIZE", "16")]);
+
+        let (status, _) = send(&app, create_request(&"a".repeat(16), None)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, body) = send(&app, create_request(&"a".repeat(17), None)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(body["error"].as_str().unwrap().contains("16 bytes"));
+    }
+
+    #[tokio::test]
+    async fn snippet_cap_rejects_or_evicts() {
+        let rejecting = app(&[("SIPP_AUTH_ENDPOINTS", "none"), ("SIPP_MAX_SNIPPETS", "2")]);
+        for _ in 0..2 {
+            let (status, _) = send(&rejecting, create_request("x", None)).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+        let (status, body) = send(&rejecting, create_request("x", None)).await;
+        assert_eq!(status, StatusCode::INSUFFICIENT_STORAGE);
+        assert!(body["error"].as_str().unwrap().contains("at most 2"));
+
+        let evicting = app(&[
+            ("SIPP_AUTH_ENDPOINTS", "none"),
+            ("SIPP_MAX_SNIPPETS", "2"),
+            ("SIPP_EVICT", "oldest"),
+        ]);
+        for content in ["first", "second", "third"] {
+            let (status, _) = send(&evicting, create_request(content, None)).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+        let (_, listed) = send(&evicting, request("GET", "/api/snippets", None)).await;
+        let contents: Vec<&str> =
+            listed.as_array().unwrap().iter().map(|s| s["content"].as_str().unwrap()).collect();
+        assert_eq!(contents, ["third", "second"]);
+    }
+
+    #[tokio::test]
+    async fn stream_exports_one_json_object_per_line() {
+        let app = app(&[("SIPP_AUTH_ENDPOINTS", "none")]);
+        for content in ["first", "second", "third"] {
+            send(&app, create_request(content, None)).await;
+        }
+        let response = app.clone().oneshot(request("GET", "/api/snippets/stream", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let contents: Vec<String> = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["content"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(contents, ["third", "second", "first"]);
+    }
+
+    #[tokio::test]
+    async fn validate_checks_without_creating() {
+        let app = app(&[("SIPP_AUTH_ENDPOINTS", "none"), ("SIPP_MAX_CONTENT_SIZE", "16")]);
+        let validate = |content: &str| {
+            Request::post("/api/snippets/validate")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(serde_json::json!({"name": "hello.rs", "content": content}).to_string()))
+                .unwrap()
+        };
+        let (status, body) = send(&app, validate("fine")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["ok"], true);
+        let (status, body) = send(&app, validate(&"a".repeat(17))).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(body["error"].as_str().unwrap().contains("16 bytes"));
+        let (_, listed) = send(&app, request("GET", "/api/snippets", None)).await;
+        assert_eq!(listed.as_array().unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn create_strips_ansi_only_when_asked() {
+        let app = app(&[("SIPP_AUTH_ENDPOINTS", "none")]);
+        let colored = "\u{1b}[1;31merror\u{1b}[0m: \u{1b}]8;;https://sipp.so\u{7}link\u{1b}]8;;\u{7}";
+        for (strip, expected) in [(true, "error: link"), (false, colored)] {
+            let body = serde_json::json!({"name": "out.txt", "content": colored, "strip_ansi": strip});
+            let request = Request::post("/api/snippets")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let (status, created) = send(&app, request).await;
+            assert_eq!(status, StatusCode::CREATED);
+            assert_eq!(created["content"], expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn create_sends_location_of_the_snippet() {
+        for (settings, base) in [
+            (vec![("SIPP_AUTH_ENDPOINTS", "none")], ""),
+            (vec![("SIPP_AUTH_ENDPOINTS", "none"), ("SIPP_PUBLIC_URL", "https://sipp.example.com/")], "https://sipp.example.com"),
+        ] {
+            let response = app(&settings).oneshot(create_request("x", None)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+            let location = response.headers()[header::LOCATION].to_str().unwrap().to_string();
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let created: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(location, format!("{}/s/{}", base, created["short_id"].as_str().unwrap()));
+        }
+    }
+
+    #[tokio::test]
+    async fn snippet_json_carries_its_share_url() {
+        let public = app(&[("SIPP_AUTH_ENDPOINTS", "none"), ("SIPP_PUBLIC_URL", "https://sipp.example.com")]);
+        let (status, created) = send(&public, create_request("x", None)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let short_id = created["short_id"].as_str().unwrap();
+        let url = format!("https://sipp.example.com/s/{}", short_id);
+        assert_eq!(created["url"], url.as_str());
+
+        let (_, fetched) = send(&public, request("GET", &format!("/api/snippets/{}", short_id), None)).await;
+        assert_eq!(fetched["url"], url.as_str());
+        for uri in ["/api/snippets", "/api/snippets?include_content=false"] {
+            let (_, listed) = send(&public, request("GET", uri, None)).await;
+            assert_eq!(listed[0]["url"], url.as_str(), "{}", uri);
+        }
+
+        // Without a public URL the link is a path on the server itself
+        let internal = app(&[("SIPP_AUTH_ENDPOINTS", "none")]);
+        let (_, created) = send(&internal, create_request("x", None)).await;
+        assert_eq!(created["url"], format!("/s/{}", created["short_id"].as_str().unwrap()).as_str());
+    }
+
+    #[tokio::test]
+    async fn base64_snippets_are_served_as_bytes() {
+        let app = app(&[("SIPP_AUTH_ENDPOINTS", "none")]);
+        let body = serde_json::json!({"name": "logo.png", "content": "iVBORw0KGgoA/w==", "encoding": "base64"});
+        let create = Request::post("/api/snippets")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (status, created) = send(&app, create).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created["encoding"], "base64");
+        let short_id = created["short_id"].as_str().unwrap();
+
+        for uri in [format!("/api/snippets/{}/raw", short_id), format!("/s/{}/raw", short_id)] {
+            let response = app.clone().oneshot(request("GET", &uri, None)).await.unwrap();
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "application/octet-stream", "{}", uri);
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(&bytes[..], b"\x89PNG\r\n\x1a\n\x00\xff", "{}", uri);
+        }
+
+        // Browsers get a download link instead of highlighted content
+        let response = app.clone().oneshot(request("GET", &format!("/s/{}", short_id), None)).await.unwrap();
+        let page = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let page = String::from_utf8_lossy(&page);
+        assert!(page.contains("download to view"));
+        assert!(!page.contains("editBtn"));
+
+        let invalid = serde_json::json!({"name": "x.bin", "content": "%%%", "encoding": "base64"});
+        let create = Request::post("/api/snippets")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(invalid.to_string()))
+            .unwrap();
+        let (status, body) = send(&app, create).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("base64"));
+    }
+
+    #[tokio::test]
+    async fn highlighted_pages_are_cached_until_updated_or_deleted() {
+        let state = state(&[("SIPP_AUTH_ENDPOINTS", "none")]);
+        let (cache, db, theme) = (
+            state.highlight_cache.clone(),
+            state.db.clone(),
+            state.highlighter.default_theme().to_string(),
+        );
+        let router = build_app(state);
+        let (_, created) = send(&router, create_request("fn main() {}", None)).await;
+        let short_id = created["short_id"].as_str().unwrap().to_string();
+        let view = format!("/s/{}", short_id);
+
+        let snippet = db::get_snippet_by_short_id(&db, &short_id).unwrap().unwrap();
+        let hash = highlight_cache::source_hash(&snippet.name, snippet.language.as_deref(), &snippet.content);
+        // Whether the page's highlighting is cached, rendering `html` into the cache if not
+        let cached = |html: &str| {
+            let mut hit = true;
+            cache.get_or_render(&short_id, &theme, hash, || {
+                hit = false;
+                html.to_string()
+            });
+            hit
+        };
+
+        assert_eq!(page(&router, "GET", &view, None, "").await.0, StatusCode::OK);
+        assert!(cached(""));
+        cache.invalidate(&short_id);
+        assert!(!cached("<p>from the cache</p>"));
+        let (_, _, body) = page(&router, "GET", &view, None, "").await;
+        assert!(body.contains("<p>from the cache</p>"));
+
+        // Writes drop the entry even when the content hashes the same
+        let api = format!("/api/snippets/{}", short_id);
+        let patch = Request::patch(&api)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"name": "hello.rs"}"#))
+            .unwrap();
+        assert_eq!(send(&router, patch).await.0, StatusCode::OK);
+        assert!(!cached(""));
+        assert_eq!(send(&router, request("DELETE", &api, None)).await.0, StatusCode::OK);
+        assert!(!cached(""));
+    }
+
+    #[tokio::test]
+    async fn batch_create_stores_every_snippet_or_none() {
+        let router = app(&[
+            ("SIPP_API_KEY", "secret"),
+            ("SIPP_AUTH_ENDPOINTS", "api_batch_create"),
+            ("SIPP_MAX_CONTENT_SIZE", "100"),
+            ("SIPP_MAX_BATCH_SIZE", "100"),
+        ]);
+        let batch = |items: serde_json::Value, api_key: Option<&str>| {
+            let mut builder = Request::post("/api/snippets/batch").header(header::CONTENT_TYPE, "application/json");
+            if let Some(key) = api_key {
+                builder = builder.header("x-api-key", key);
+            }
+            builder.body(Body::from(items.to_string())).unwrap()
+        };
+        let count = async |router: &Router| {
+            let (_, listed) = send(router, request("GET", "/api/snippets", None)).await;
+            listed.as_array().unwrap().len()
+        };
+
+        let items = serde_json::json!([
+            {"name": "a.rs", "content": "fn a() {}"},
+            {"name": "b.py", "content": "b = 1", "language": "python"},
+        ]);
+        assert_eq!(send(&router, batch(items.clone(), None)).await.0, StatusCode::UNAUTHORIZED);
+        let (status, created) = send(&router, batch(items, Some("secret"))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let created = created.as_array().unwrap();
+        assert_eq!(created.len(), 2);
+        assert_eq!((created[0]["name"].as_str(), created[1]["language"].as_str()), (Some("a.rs"), Some("python")));
+        for snippet in created {
+            let uri = format!("/api/snippets/{}", snippet["short_id"].as_str().unwrap());
+            let (status, fetched) = send(&router, request("GET", &uri, None)).await;
+            assert_eq!((status, &fetched["content"]), (StatusCode::OK, &snippet["content"]));
+        }
+
+        // One oversized item, or too much content in total, rejects the whole batch
+        let oversized = serde_json::json!([
+            {"name": "ok.txt", "content": "small"},
+            {"name": "big.txt", "content": "x".repeat(101)},
+        ]);
+        let (status, body) = send(&router, batch(oversized, Some("secret"))).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(body["error"].as_str().unwrap().contains("item 1"));
+        let over_total = serde_json::json!([
+            {"name": "a.txt", "content": "x".repeat(60)},
+            {"name": "b.txt", "content": "x".repeat(60)},
+        ]);
+        let (status, body) = send(&router, batch(over_total, Some("secret"))).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(body["error"].as_str().unwrap().contains("Batch too large"));
+        assert_eq!(send(&router, batch(serde_json::json!([]), Some("secret"))).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(count(&router).await, 2);
+    }
+
+    #[tokio::test]
+    async fn forwarded_ips_are_only_believed_from_trusted_proxies() {
+        let proxy: SocketAddr = "10.0.0.2:4000".parse().unwrap();
+        let create_from = |peer: SocketAddr, forwarded: &str| {
+            let mut create = create_request("fn main() {}", None);
+            create.headers_mut().insert("x-forwarded-for", HeaderValue::from_str(forwarded).unwrap());
+            create.extensions_mut().insert(ConnectInfo(peer));
+            create
+        };
+
+        // Without trusted proxies, a spoofed header doesn't buy a fresh limit
+        let direct = app(&[("SIPP_CREATE_RATE_PER_MIN", "1")]);
+        assert_eq!(send(&direct, create_from(proxy, "203.0.113.1")).await.0, StatusCode::CREATED);
+        assert_eq!(send(&direct, create_from(proxy, "203.0.113.2")).await.0, StatusCode::TOO_MANY_REQUESTS);
+
+        let proxied = app(&[("SIPP_CREATE_RATE_PER_MIN", "1"), ("SIPP_TRUSTED_PROXIES", "10.0.0.1, 10.0.0.2")]);
+        assert_eq!(send(&proxied, create_from(proxy, "203.0.113.1")).await.0, StatusCode::CREATED);
+        // A client can prepend entries, but the proxy appends the real address
+        let spoofed = create_from(proxy, "198.51.100.7, 203.0.113.1, 10.0.0.1");
+        assert_eq!(send(&proxied, spoofed).await.0, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(send(&proxied, create_from(proxy, "203.0.113.2")).await.0, StatusCode::CREATED);
+        let untrusted: SocketAddr = "192.0.2.9:4000".parse().unwrap();
+        assert_eq!(send(&proxied, create_from(untrusted, "203.0.113.3")).await.0, StatusCode::CREATED);
+        assert_eq!(send(&proxied, create_from(untrusted, "203.0.113.4")).await.0, StatusCode::TOO_MANY_REQUESTS);
+
+        let invalid = ServerConfig::from_lookup(|name| (name == "SIPP_TRUSTED_PROXIES").then(|| "10.0.0.0/8".to_string()));
+        assert_eq!(invalid.err().as_deref(), Some("SIPP_TRUSTED_PROXIES: invalid IP address '10.0.0.0/8'"));
+    }
+
+    #[tokio::test]
+    async fn writes_never_reveal_protected_content() {
+        let app = app(&[("SIPP_AUTH_ENDPOINTS", "none")]);
+        let body = serde_json::json!({"name": "secret.txt", "content": "hidden", "view_password": "pw"});
+        let create = Request::post("/api/snippets")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (_, created) = send(&app, create).await;
+        let short_id = created["short_id"].as_str().unwrap();
+
+        let patch = |password: Option<&str>| {
+            let mut builder = Request::patch(format!("/api/snippets/{}", short_id))
+                .header(header::CONTENT_TYPE, "application/json");
+            if let Some(password) = password {
+                builder = builder.header(SNIPPET_PASSWORD_HEADER, password);
+            }
+            builder.body(Body::from(r#"{"name": "secret.md"}"#)).unwrap()
+        };
+        let (status, patched) = send(&app, patch(None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((patched["name"].as_str(), patched["content"].as_str()), (Some("secret.md"), Some("")));
+        let (_, patched) = send(&app, patch(Some("pw"))).await;
+        assert_eq!(patched["content"], "hidden");
+
+        let rename = Request::post(format!("/api/snippets/{}/rename", short_id))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"name": "renamed.md"}"#))
+            .unwrap();
+        let (status, renamed) = send(&app, rename).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((renamed["name"].as_str(), renamed["content"].as_str()), (Some("renamed.md"), Some("")));
+
+        let response = app.clone().oneshot(request("GET", &format!("/s/{}/edit", short_id), None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let page = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(!String::from_utf8_lossy(&page).contains("hidden"));
+    }
+
+    #[tokio::test]
+    async fn route_names_are_reserved_short_ids() {
+        let app = app(&[("SIPP_AUTH_ENDPOINTS", "none")]);
+        for short_id in ["batch", "stream", "validate", "Validate"] {
+            let body = serde_json::json!({"name": "x.txt", "content": "x", "short_id": short_id});
+            let create = Request::post("/api/snippets")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let (status, body) = send(&app, create).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", short_id);
+            assert!(body["error"].as_str().unwrap().contains("reserved"), "{}", short_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn read_only_mode_rejects_writes() {
+        let app = app(&[("SIPP_AUTH_ENDPOINTS", "none"), ("SIPP_READ_ONLY", "true")]);
+
+        let (status, body) = send(&app, create_request("x", None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body["error"].as_str().unwrap().contains("read-only"));
+        let (status, _) = send(&app, request("DELETE", "/api/snippets/abc", None)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&app, request("GET", "/api/snippets", None)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn listing_filters_by_normalized_tags() {
+        let app = app(&[("SIPP_AUTH_ENDPOINTS", "none")]);
+
+        for (name, tags) in [("a.rs", vec![" rust ", "scratch,rust"]), ("b.sql", vec!["sql"])] {
+            let body = serde_json::json!({"name": name, "content": "x", "tags": tags});
+            let request = Request::post("/api/snippets")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let (status, _) = send(&app, request).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+
+        let (status, listed) = send(&app, request("GET", "/api/snippets?tag=rust", None)).await;
+        assert_eq!(status, StatusCode::OK);
+        let listed = listed.as_array().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0]["name"], "a.rs");
+        assert_eq!(listed[0]["tags"], serde_json::json!(["rust", "scratch"]));
+
+        let (_, listed) = send(&app, request("GET", "/api/snippets?tag=ru", None)).await;
+        assert_eq!(listed.as_array().unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn auth_endpoints_none_disables_gating() {
+        let app = app(&[("SIPP_API_KEY", "secret"), ("SIPP_AUTH_ENDPOINTS", "none")]);
+
+        let (status, created) = send(&app, create_request("x", None)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let uri = format!("/api/snippets/{}", created["short_id"].as_str().unwrap());
+        let (status, _) = send(&app, request("GET", "/api/snippets", None)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, request("DELETE", &uri, None)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
 }
@@ -1,17 +1,28 @@
-use arboard::Clipboard;
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use arboard::Clipboard as SystemClipboard;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{
     DefaultTerminal,
-    layout::{Alignment, Constraint, Layout},
+    layout::{Alignment, Constraint, Layout, Margin},
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Widget, Wrap},
+    widgets::{
+        Block, Borders, Clear, List, ListItem, ListState, Paragraph, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, Widget, Wrap,
+    },
 };
-use crate::backend::Backend;
+use crate::backend::{self, BackendError, LocalBackend, RemoteBackend, SharedStore, SnippetStore};
+use crate::clipboard::{Clipboard, FallbackClipboard};
 use crate::config;
-use crate::db::Snippet;
-use std::io::Cursor;
-use std::path::PathBuf;
+use crate::diff;
+use crate::editor::TextEditor;
+use crate::highlight;
+use crate::keymap::{self, Action, Key, Keymap, Lookup};
+use crate::db::{self, Snippet, SnippetMeta};
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, mpsc};
 use std::time::{Duration, Instant};
 use syntect::easy::HighlightLines;
 use syntect::highlighting::Theme;
@@ -22,69 +33,279 @@ enum Focus {
     List,
     Content,
     CreateName,
+    CreateLanguage,
+    CreateTags,
     CreateContent,
     EditName,
+    EditLanguage,
+    EditTags,
     EditContent,
     Search,
 }
 
+#[derive(Clone, Copy)]
+enum SortMode {
+    Newest,
+    Oldest,
+    NameAsc,
+    NameDesc,
+    Modified,
+}
+
+impl SortMode {
+    fn next(self) -> Self {
+        match self {
+            SortMode::Newest => SortMode::Oldest,
+            SortMode::Oldest => SortMode::NameAsc,
+            SortMode::NameAsc => SortMode::NameDesc,
+            SortMode::NameDesc => SortMode::Modified,
+            SortMode::Modified => SortMode::Newest,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SortMode::Newest => "Newest",
+            SortMode::Oldest => "Oldest",
+            SortMode::NameAsc => "Name A–Z",
+            SortMode::NameDesc => "Name Z–A",
+            SortMode::Modified => "Modified",
+        }
+    }
+}
+
+/// Fetches snippet contents on a background thread, so the list stays
+/// responsive while a remote request is in flight.
+struct ContentLoader {
+    requests: mpsc::Sender<String>,
+    results: mpsc::Receiver<(String, Result<Option<Snippet>, BackendError>)>,
+}
+
+impl ContentLoader {
+    fn spawn(backend: SharedStore) -> Self {
+        let (requests, pending) = mpsc::channel::<String>();
+        let (done, results) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok(mut short_id) = pending.recv() {
+                // Only the latest selection matters when scrolling quickly
+                while let Ok(next) = pending.try_recv() {
+                    short_id = next;
+                }
+                let result = backend.get_snippet(&short_id);
+                if done.send((short_id, result)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { requests, results }
+    }
+}
+
+/// Size figures for the selected snippet, shown in the status bar.
+#[derive(Clone, Copy)]
+struct ContentStats {
+    lines: usize,
+    words: usize,
+    bytes: usize,
+}
+
+impl ContentStats {
+    fn of(content: &str) -> Self {
+        Self {
+            lines: content.lines().count(),
+            words: content.split_whitespace().count(),
+            bytes: content.len(),
+        }
+    }
+
+    fn label(self) -> String {
+        format!(
+            "{} lines · {} words · {}",
+            self.lines,
+            self.words,
+            format_size(self.bytes)
+        )
+    }
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 KiB`.
+fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// What's kept of a deleted snippet so it can be recreated.
+struct DeletedSnippet {
+    name: String,
+    content: String,
+    language: Option<String>,
+    tags: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+enum MessageKind {
+    Info,
+    Success,
+    Error,
+}
+
 struct App {
-    snippets: Vec<Snippet>,
+    snippets: Vec<SnippetMeta>,
+    /// Contents fetched so far, keyed by short id.
+    contents: HashMap<String, String>,
+    /// The short id most recently sent to the [`ContentLoader`].
+    loading: Option<String>,
+    /// The last failed fetch, as a short id and message, so it isn't retried
+    /// on every frame.
+    load_error: Option<(String, String)>,
+    /// Stats for the snippet with this short id, so large snippets aren't
+    /// recounted every frame.
+    stats_cache: Option<(String, ContentStats)>,
+    clipboard: Box<dyn Clipboard>,
+    /// Text that couldn't be copied anywhere, printed to stderr on quit.
+    uncopied: Option<String>,
     list_state: ListState,
     should_quit: bool,
-    status_message: Option<(String, Instant)>,
+    status_message: Option<(String, MessageKind, Instant)>,
+    status_timeout: Duration,
     focus: Focus,
     content_scroll: u16,
+    content_scroll_x: u16,
+    /// Line under the cursor in the content pane; `content_scroll` follows it.
+    content_cursor: usize,
+    /// Rows inside the content pane as of the last draw, for paging.
+    content_height: u16,
     show_help: bool,
     confirm_delete: bool,
+    /// Set by the `edit_external` action; the editor is launched from the
+    /// event loop, which owns the terminal.
+    external_edit_requested: bool,
+    /// Recent deletions, newest last, that `u` can bring back.
+    deleted: Vec<DeletedSnippet>,
+    /// Whether the diff of the edit in progress is shown over the form.
+    show_diff: bool,
+    diff_scroll: u16,
     syntax_set: SyntaxSet,
     theme: Theme,
     create_name: String,
-    create_content: String,
+    create_language: String,
+    /// Comma-separated, as typed in the form.
+    create_tags: String,
+    create_content: TextEditor,
+    /// Remove ANSI escape sequences from the content when it is saved.
+    create_strip_ansi: bool,
+    /// What `create_strip_ansi` starts as in a new form, from the config.
+    strip_ansi_default: bool,
     edit_short_id: Option<String>,
+    /// Content of the snippet when the edit started, to diff against.
+    edit_original: String,
     search_query: String,
     filtered_indices: Option<Vec<usize>>,
+    /// Only snippets with this tag are listed.
+    tag_filter: Option<String>,
+    search_matches: HashMap<usize, Vec<usize>>,
+    search_content: bool,
+    /// Whether `contents` holds every snippet, fetched when content search
+    /// is first turned on.
+    all_contents_loaded: bool,
+    matcher: SkimMatcherV2,
     is_remote: bool,
     remote_url: Option<String>,
+    /// Configured base for share links, taking precedence over `remote_url`.
+    public_url: Option<String>,
+    /// Why the last health check failed, while the store is unreachable.
+    disconnected: Option<String>,
     wrap_content: bool,
+    wrap_view: bool,
+    /// Shows the first lines of the selected snippet under the list.
+    show_preview: bool,
+    show_line_numbers: bool,
     edit_scroll: u16,
+    sort_mode: SortMode,
+    keymap: Keymap,
+    /// Keys typed so far toward a multi-key binding such as `gg`.
+    pending_keys: Vec<Key>,
 }
 
 impl App {
-    fn new(snippets: Vec<Snippet>, is_remote: bool, remote_url: Option<String>) -> Self {
+    fn new(
+        snippets: Vec<SnippetMeta>,
+        is_remote: bool,
+        remote_url: Option<String>,
+        theme: Option<&str>,
+        status_timeout: Duration,
+    ) -> Self {
         let mut list_state = ListState::default();
         if !snippets.is_empty() {
             list_state.select(Some(0));
         }
         let syntax_set = SyntaxSet::load_defaults_newlines();
-        let theme_data = include_bytes!("ansi.tmTheme");
-        let theme =
-            syntect::highlighting::ThemeSet::load_from_reader(&mut Cursor::new(&theme_data[..]))
-                .expect("failed to load base16 theme");
+        let theme = highlight::load_theme(theme, highlight::DEFAULT_TUI_THEME);
         Self {
             snippets,
+            contents: HashMap::new(),
+            loading: None,
+            load_error: None,
+            stats_cache: None,
+            clipboard: Box::new(FallbackClipboard::detect()),
+            uncopied: None,
             list_state,
             should_quit: false,
             status_message: None,
+            status_timeout,
             focus: Focus::List,
             content_scroll: 0,
+            content_scroll_x: 0,
+            content_cursor: 0,
+            content_height: 0,
             show_help: false,
             confirm_delete: false,
+            external_edit_requested: false,
+            deleted: Vec::new(),
+            show_diff: false,
+            diff_scroll: 0,
             syntax_set,
             theme,
             create_name: String::new(),
-            create_content: String::new(),
+            create_language: String::new(),
+            create_tags: String::new(),
+            create_content: TextEditor::default(),
             edit_short_id: None,
+            edit_original: String::new(),
             search_query: String::new(),
             filtered_indices: None,
+            tag_filter: None,
+            search_matches: HashMap::new(),
+            search_content: false,
+            all_contents_loaded: false,
+            matcher: SkimMatcherV2::default().ignore_case(),
             is_remote,
             remote_url,
+            public_url: None,
+            disconnected: None,
             wrap_content: true,
+            wrap_view: false,
+            show_line_numbers: false,
             edit_scroll: 0,
+            sort_mode: SortMode::Newest,
+            create_strip_ansi: false,
+            strip_ansi_default: false,
+            show_preview: false,
+            keymap: Keymap::default(),
+            pending_keys: Vec::new(),
         }
     }
 
-    fn selected_snippet(&self) -> Option<&Snippet> {
+    fn selected_snippet(&self) -> Option<&SnippetMeta> {
         self.list_state.selected().and_then(|i| {
             if let Some(indices) = &self.filtered_indices {
                 indices.get(i).and_then(|&real| self.snippets.get(real))
@@ -94,6 +315,57 @@ impl App {
         })
     }
 
+    fn selected_content(&self) -> Option<&str> {
+        self.selected_snippet()
+            .and_then(|s| self.contents.get(&s.short_id))
+            .map(String::as_str)
+    }
+
+    /// Stats for the selected snippet once its content is loaded.
+    fn selected_stats(&mut self) -> Option<ContentStats> {
+        let short_id = self.selected_snippet()?.short_id.clone();
+        if let Some((id, stats)) = &self.stats_cache
+            && *id == short_id
+        {
+            return Some(*stats);
+        }
+        let stats = ContentStats::of(self.contents.get(&short_id)?);
+        self.stats_cache = Some((short_id, stats));
+        Some(stats)
+    }
+
+    /// Asks the loader for the selected snippet's content unless it is cached,
+    /// already requested, or just failed to load.
+    fn request_selected_content(&mut self, loader: &ContentLoader) {
+        let Some(short_id) = self.selected_snippet().map(|s| s.short_id.clone()) else {
+            return;
+        };
+        if self.contents.contains_key(&short_id)
+            || self.loading.as_ref() == Some(&short_id)
+            || self.load_error.as_ref().is_some_and(|(id, _)| *id == short_id)
+        {
+            return;
+        }
+        if loader.requests.send(short_id.clone()).is_ok() {
+            self.loading = Some(short_id);
+        }
+    }
+
+    fn receive_contents(&mut self, loader: &ContentLoader) {
+        while let Ok((short_id, result)) = loader.results.try_recv() {
+            if self.loading.as_ref() == Some(&short_id) {
+                self.loading = None;
+            }
+            match result {
+                Ok(Some(snippet)) => {
+                    self.contents.insert(short_id, snippet.content);
+                }
+                Ok(None) => self.load_error = Some((short_id, "Snippet not found".to_string())),
+                Err(e) => self.load_error = Some((short_id, e.to_string())),
+            }
+        }
+    }
+
     fn visible_count(&self) -> usize {
         match &self.filtered_indices {
             Some(indices) => indices.len(),
@@ -113,6 +385,8 @@ impl App {
         };
         self.list_state.select(Some(i));
         self.content_scroll = 0;
+        self.content_scroll_x = 0;
+        self.content_cursor = 0;
     }
 
     fn move_down(&mut self) {
@@ -127,68 +401,240 @@ impl App {
         };
         self.list_state.select(Some(i));
         self.content_scroll = 0;
+        self.content_scroll_x = 0;
+        self.content_cursor = 0;
+    }
+
+    /// Orders `snippets` by the current sort mode. Ids grow with creation
+    /// time, so they stand in for timestamps.
+    fn sort_snippets(&mut self) {
+        match self.sort_mode {
+            SortMode::Newest => self.snippets.sort_by_key(|s| std::cmp::Reverse(s.id)),
+            SortMode::Oldest => self.snippets.sort_by_key(|s| s.id),
+            SortMode::NameAsc => self.snippets.sort_by_cached_key(|s| s.name.to_lowercase()),
+            SortMode::NameDesc => self
+                .snippets
+                .sort_by_cached_key(|s| std::cmp::Reverse(s.name.to_lowercase())),
+            SortMode::Modified => self
+                .snippets
+                .sort_by_key(|s| std::cmp::Reverse((s.updated_at, s.id))),
+        }
+    }
+
+    /// Selects the snippet with `short_id` if it is currently visible.
+    fn select_short_id(&mut self, short_id: &str) {
+        let position = match &self.filtered_indices {
+            Some(indices) => indices
+                .iter()
+                .position(|&i| self.snippets.get(i).is_some_and(|s| s.short_id == short_id)),
+            None => self.snippets.iter().position(|s| s.short_id == short_id),
+        };
+        if let Some(pos) = position {
+            self.list_state.select(Some(pos));
+        }
+    }
+
+    /// Reselects the snippet from a saved session and scrolls the list back
+    /// to where it was. A snippet that no longer exists leaves the selection
+    /// at the top.
+    fn restore_session(&mut self, session: &config::SessionState) {
+        let Some(short_id) = &session.selected else {
+            return;
+        };
+        if self.snippets.iter().any(|s| s.short_id == *short_id) {
+            self.select_short_id(short_id);
+            *self.list_state.offset_mut() = session.list_offset;
+        }
+    }
+
+    /// Indices of the snippets the tag filter lets through.
+    fn tagged_indices(&self) -> Vec<usize> {
+        (0..self.snippets.len())
+            .filter(|&i| match &self.tag_filter {
+                Some(tag) => self.snippets[i].tags.contains(tag),
+                None => true,
+            })
+            .collect()
+    }
+
+    /// Drops any search, leaving only the tag filter, and keeps the selection
+    /// within the list.
+    fn reset_filter(&mut self) {
+        self.filtered_indices = self.tag_filter.is_some().then(|| self.tagged_indices());
+        let count = self.visible_count();
+        match self.list_state.selected() {
+            _ if count == 0 => self.list_state.select(None),
+            Some(i) if i >= count => self.list_state.select(Some(count - 1)),
+            Some(_) => {}
+            None => self.list_state.select(Some(0)),
+        }
+    }
+
+    /// Steps the tag filter through every tag in use, alphabetically, then
+    /// back to showing everything.
+    fn cycle_tag_filter(&mut self) {
+        let mut tags: Vec<&String> = self.snippets.iter().flat_map(|s| &s.tags).collect();
+        tags.sort_by_cached_key(|t| t.to_lowercase());
+        tags.dedup();
+        if tags.is_empty() {
+            self.set_status(MessageKind::Info, "No tagged snippets");
+            return;
+        }
+        let next = match &self.tag_filter {
+            Some(current) => tags
+                .iter()
+                .position(|t| *t == current)
+                .and_then(|i| tags.get(i + 1)),
+            None => tags.first(),
+        };
+        self.tag_filter = next.map(|t| t.to_string());
+        let selected = self.selected_snippet().map(|s| s.short_id.clone());
+        self.list_state.select(None);
+        self.reset_filter();
+        if let Some(short_id) = selected {
+            self.select_short_id(&short_id);
+        }
+        let message = match &self.tag_filter {
+            Some(tag) => format!("Showing #{}", tag),
+            None => "Showing all snippets".to_string(),
+        };
+        self.set_status(MessageKind::Info, message);
+    }
+
+    fn cycle_sort(&mut self) {
+        let selected = self.selected_snippet().map(|s| s.short_id.clone());
+        self.sort_mode = self.sort_mode.next();
+        self.sort_snippets();
+        if self.filtered_indices.is_some() {
+            self.update_search_filter();
+        }
+        if let Some(short_id) = selected {
+            self.select_short_id(&short_id);
+        }
+    }
+
+    /// Moves the content cursor by `delta` lines, stopping at the first and
+    /// last line.
+    fn move_cursor(&mut self, delta: isize) {
+        let line_count = self.selected_content().map_or(0, |c| c.lines().count());
+        self.content_cursor = self
+            .content_cursor
+            .saturating_add_signed(delta)
+            .min(line_count.saturating_sub(1));
+    }
+
+    /// Rows of content visible in the pane, at least one.
+    fn page_size(&self) -> isize {
+        self.content_height.max(1) as isize
     }
 
-    fn scroll_up(&mut self) {
-        self.content_scroll = self.content_scroll.saturating_sub(1);
+    fn scroll_left(&mut self) {
+        self.content_scroll_x = self.content_scroll_x.saturating_sub(1);
     }
 
-    fn scroll_down(&mut self, max_lines: u16) {
-        if self.content_scroll < max_lines {
-            self.content_scroll += 1;
+    fn scroll_right(&mut self, max_width: u16) {
+        if self.content_scroll_x < max_width {
+            self.content_scroll_x += 1;
         }
     }
 
     fn copy_selected(&mut self) {
-        if let Some(snippet) = self.selected_snippet() {
-            if let Ok(mut clipboard) = Clipboard::new() {
-                let _ = clipboard.set_text(&snippet.content);
-                self.status_message = Some(("Copied!".to_string(), Instant::now()));
+        if self.selected_snippet().is_none() {
+            return;
+        }
+        match self.selected_content().map(str::to_string) {
+            Some(content) => self.copy_text(content, "Copied!"),
+            None => {
+                self.set_status(MessageKind::Info, "Content is still loading");
             }
         }
     }
 
+    /// Copies the content inside a markdown code fence tagged with its language.
+    fn copy_markdown(&mut self) {
+        let Some(snippet) = self.selected_snippet() else {
+            return;
+        };
+        let Some(content) = self.selected_content() else {
+            self.set_status(MessageKind::Info, "Content is still loading");
+            return;
+        };
+        let syntax = highlight::find_syntax(
+            &self.syntax_set,
+            &snippet.name,
+            snippet.language.as_deref(),
+            content,
+        );
+        let language = highlight::fence_language(&self.syntax_set, syntax);
+        let block = highlight::markdown_code_block(content, language.as_deref());
+        self.copy_text(block, "Copied as markdown!");
+    }
+
+    /// The share link of the selected snippet, or `None` when there's no
+    /// server to link to.
+    fn selected_link(&self) -> Option<String> {
+        if self.remote_url.is_none() && self.public_url.is_none() {
+            return None;
+        }
+        self.selected_snippet().map(|snippet| {
+            snippet_link(
+                self.public_url.as_deref(),
+                self.remote_url.as_deref(),
+                snippet.url.as_deref(),
+                &snippet.short_id,
+            )
+        })
+    }
+
     fn copy_link(&mut self) {
-        match &self.remote_url {
-            Some(url) => {
-                if let Some(snippet) = self.selected_snippet() {
-                    let link = format!("{}/s/{}", url.trim_end_matches('/'), snippet.short_id);
-                    if let Ok(mut clipboard) = Clipboard::new() {
-                        let _ = clipboard.set_text(&link);
-                        self.status_message =
-                            Some(("Link copied!".to_string(), Instant::now()));
-                    }
-                }
-            }
-            None => {
-                self.status_message =
-                    Some(("No remote URL configured".to_string(), Instant::now()));
+        if self.remote_url.is_none() && self.public_url.is_none() {
+            self.set_status(MessageKind::Error, "No remote URL configured");
+        } else if let Some(link) = self.selected_link() {
+            self.copy_text(link, "Link copied!");
+        }
+    }
+
+    /// Copies the raw text of the line under the content cursor.
+    fn copy_line(&mut self) {
+        if let Some(line) = self
+            .selected_content()
+            .and_then(|c| c.lines().nth(self.content_cursor))
+            .map(str::to_string)
+        {
+            self.copy_text(line, "Line copied!");
+        }
+    }
+
+    /// Copies `text`, or keeps it for printing on quit when no clipboard works.
+    fn copy_text(&mut self, text: String, done: &str) {
+        match self.clipboard.set_text(&text) {
+            Ok(()) => self.set_status(MessageKind::Success, done),
+            Err(_) => {
+                self.uncopied = Some(text);
+                self.set_status(
+                    MessageKind::Error,
+                    "Clipboard unavailable; it will be printed when you quit",
+                );
             }
         }
     }
 
     fn open_in_browser(&mut self) {
-        match &self.remote_url {
-            Some(url) => {
-                if let Some(snippet) = self.selected_snippet() {
-                    let link = format!("{}/s/{}", url.trim_end_matches('/'), snippet.short_id);
-                    if let Err(e) = open::that(&link) {
-                        self.status_message =
-                            Some((format!("Failed to open browser: {}", e), Instant::now()));
-                    } else {
-                        self.status_message =
-                            Some(("Opened in browser!".to_string(), Instant::now()));
-                    }
-                }
-            }
-            None => {
-                self.status_message =
-                    Some(("No remote URL configured".to_string(), Instant::now()));
+        if self.remote_url.is_none() && self.public_url.is_none() {
+            self.set_status(MessageKind::Error, "No remote URL configured");
+        } else if let Some(link) = self.selected_link() {
+            if let Err(e) = open::that(&link) {
+                self.set_status(
+                    MessageKind::Error,
+                    format!("Failed to open browser: {}", e),
+                );
+            } else {
+                self.set_status(MessageKind::Success, "Opened in browser!");
             }
         }
     }
 
-    fn delete_selected(&mut self, backend: &Backend) {
+    fn delete_selected(&mut self, backend: &dyn SnippetStore) {
         if let Some(selected_index) = self.list_state.selected() {
             let real_index = if let Some(indices) = &self.filtered_indices {
                 match indices.get(selected_index) {
@@ -200,9 +646,19 @@ impl App {
             };
             if let Some(snippet) = self.snippets.get(real_index) {
                 let short_id = snippet.short_id.clone();
+                // The content has to be in hand before it's gone from the store.
+                // Protected snippets can't be undone, since recreating one would
+                // drop its password, nor binary ones, which would come back as text
+                let content = backend
+                    .get_snippet(&short_id)
+                    .ok()
+                    .flatten()
+                    .filter(|s| !s.is_protected() && !s.is_binary())
+                    .map(|s| s.content);
                 match backend.delete_snippet(&short_id) {
                     Ok(true) => {
-                        self.snippets.remove(real_index);
+                        let snippet = self.snippets.remove(real_index);
+                        self.contents.remove(&short_id);
                         if self.filtered_indices.is_some() {
                             self.update_search_filter();
                         }
@@ -214,38 +670,159 @@ impl App {
                         } else {
                             self.list_state.select(Some(selected_index));
                         }
-                        self.status_message = Some(("Deleted!".to_string(), Instant::now()));
+                        match content {
+                            Some(content) => {
+                                if self.deleted.len() == UNDO_LIMIT {
+                                    self.deleted.remove(0);
+                                }
+                                self.deleted.push(DeletedSnippet {
+                                    name: snippet.name,
+                                    content,
+                                    language: snippet.language,
+                                    tags: snippet.tags,
+                                });
+                                let undo = self.keymap.short_label(Action::Undo).unwrap_or_else(|| "undo".to_string());
+                                self.set_status(MessageKind::Success, format!("Deleted ({} to undo)", undo));
+                            }
+                            None => self.set_status(MessageKind::Success, "Deleted! (can't be undone)"),
+                        }
                     }
                     Ok(false) => {
-                        self.status_message =
-                            Some(("Snippet not found".to_string(), Instant::now()));
+                        self.set_status(MessageKind::Error, "Snippet not found");
                     }
                     Err(e) => {
-                        self.status_message = Some((e.to_string(), Instant::now()));
+                        self.set_status(MessageKind::Error, e.to_string());
                     }
                 }
             }
         }
     }
 
-    fn refresh(&mut self, backend: &Backend) {
-        match backend.list_snippets() {
+    /// Recreates the most recently deleted snippet. The store hands out a new
+    /// short id, so old links to it stay broken.
+    fn undo_delete(&mut self, backend: &dyn SnippetStore) {
+        let Some(deleted) = self.deleted.pop() else {
+            self.set_status(MessageKind::Info, "Nothing to undo");
+            return;
+        };
+        match backend.create_snippet(&deleted.name, &deleted.content, deleted.language.as_deref(), &deleted.tags) {
+            Ok(snippet) => {
+                let short_id = snippet.short_id.clone();
+                self.snippets.insert(0, snippet.meta());
+                self.contents.insert(short_id.clone(), snippet.content);
+                self.stats_cache = None;
+                self.sort_snippets();
+                if self.filtered_indices.is_some() {
+                    self.update_search_filter();
+                }
+                self.select_short_id(&short_id);
+                self.set_status(MessageKind::Success, format!("Restored {} as {}", deleted.name, short_id));
+            }
+            Err(e) => {
+                self.set_status(MessageKind::Error, format!("Undo failed: {}", e));
+                self.deleted.push(deleted);
+            }
+        }
+    }
+
+    /// Feeds a key press to the keymap and returns the action once a binding
+    /// is complete. Keys that start a longer binding are held until the next
+    /// press.
+    fn resolve_key(&mut self, context: keymap::Context, key: &KeyEvent) -> Option<Action> {
+        self.pending_keys.push(Key::from_event(key));
+        match self.keymap.lookup(context, &self.pending_keys) {
+            Lookup::Action(action) => {
+                self.pending_keys.clear();
+                Some(action)
+            }
+            Lookup::Pending => None,
+            Lookup::None => {
+                // A key that breaks off a sequence still counts on its own
+                let retry = self.pending_keys.len() > 1;
+                self.pending_keys.clear();
+                if retry { self.resolve_key(context, key) } else { None }
+            }
+        }
+    }
+
+    fn run_action(&mut self, action: Action, backend: &dyn SnippetStore, content_max_width: u16) {
+        let in_list = matches!(self.focus, Focus::List);
+        match action {
+            Action::Quit => self.should_quit = true,
+            Action::Back => self.focus = Focus::List,
+            Action::Open => {
+                if self.selected_snippet().is_some() {
+                    self.focus = Focus::Content;
+                }
+            }
+            Action::MoveDown if in_list => self.move_down(),
+            Action::MoveUp if in_list => self.move_up(),
+            Action::MoveDown => self.move_cursor(1),
+            Action::MoveUp => self.move_cursor(-1),
+            Action::HalfPageDown => self.move_cursor(self.page_size() / 2),
+            Action::HalfPageUp => self.move_cursor(-(self.page_size() / 2)),
+            Action::PageDown => self.move_cursor(self.page_size()),
+            Action::PageUp => self.move_cursor(-self.page_size()),
+            Action::Top => self.content_cursor = 0,
+            Action::Bottom => self.move_cursor(isize::MAX),
+            Action::ScrollLeft => self.scroll_left(),
+            Action::ScrollRight => {
+                if !self.wrap_view {
+                    self.scroll_right(content_max_width);
+                }
+            }
+            Action::ToggleWrap => {
+                self.wrap_view = !self.wrap_view;
+                self.content_scroll = 0;
+                self.content_scroll_x = 0;
+            }
+            Action::ToggleLineNumbers => self.show_line_numbers = !self.show_line_numbers,
+            Action::Copy => self.copy_selected(),
+            Action::CopyLink => self.copy_link(),
+            Action::CopyMarkdown => self.copy_markdown(),
+            Action::CopyLine => self.copy_line(),
+            Action::Edit => self.start_edit(),
+            Action::EditExternal => self.external_edit_requested = true,
+            Action::OpenInBrowser => self.open_in_browser(),
+            Action::Delete => self.confirm_delete = true,
+            Action::Undo => self.undo_delete(backend),
+            Action::Create => self.start_create(),
+            Action::Search => self.start_search(),
+            Action::Sort => self.cycle_sort(),
+            Action::TagFilter => self.cycle_tag_filter(),
+            Action::TogglePreview => self.show_preview = !self.show_preview,
+            Action::Refresh => {
+                if self.is_remote || self.disconnected.is_some() {
+                    self.refresh(backend);
+                }
+            }
+            Action::Help => self.show_help = true,
+        }
+    }
+
+    fn refresh(&mut self, backend: &dyn SnippetStore) {
+        if let Err(e) = backend.health_check() {
+            self.disconnected = Some(e.to_string());
+            self.set_status(MessageKind::Error, e.to_string());
+            return;
+        }
+        self.disconnected = None;
+        match backend.list_snippet_metas() {
             Ok(snippets) => {
                 self.snippets = snippets;
-                self.filtered_indices = None;
+                // Contents may have changed on the server too
+                self.contents.clear();
+                self.all_contents_loaded = false;
+                self.stats_cache = None;
+                self.loading = None;
+                self.load_error = None;
+                self.sort_snippets();
                 self.search_query.clear();
-                if self.snippets.is_empty() {
-                    self.list_state.select(None);
-                } else {
-                    let idx = self.list_state.selected().unwrap_or(0);
-                    if idx >= self.snippets.len() {
-                        self.list_state.select(Some(self.snippets.len() - 1));
-                    }
-                }
-                self.status_message = Some(("Refreshed!".to_string(), Instant::now()));
+                self.reset_filter();
+                self.set_status(MessageKind::Success, "Refreshed!");
             }
             Err(e) => {
-                self.status_message = Some((e.to_string(), Instant::now()));
+                self.set_status(MessageKind::Error, e.to_string());
             }
         }
     }
@@ -255,35 +832,49 @@ impl App {
         if w == 0 {
             return (0, 0);
         }
-        let text = &self.create_content;
-        let mut visual_row: usize = 0;
-        let lines: Vec<&str> = if text.is_empty() {
-            vec![""]
-        } else if text.ends_with('\n') {
-            text.split('\n').collect()
-        } else {
-            text.split('\n').collect()
-        };
-        let last_idx = lines.len() - 1;
-        for (i, line) in lines.iter().enumerate() {
-            let line_len = line.len();
-            let wrapped_lines = if line_len == 0 {
-                1
-            } else {
-                (line_len + w - 1) / w
-            };
-            if i < last_idx {
-                visual_row += wrapped_lines;
-            } else {
-                // cursor is at end of this last line
-                let cursor_col = if text.ends_with('\n') { 0 } else { line_len };
-                let extra_rows = cursor_col / w;
-                let col = cursor_col % w;
-                visual_row += extra_rows;
-                return (col as u16, visual_row as u16);
+        let (line, col) = self.create_content.cursor_line_col();
+        let rows_above: usize = self
+            .create_content
+            .as_str()
+            .split('\n')
+            .take(line)
+            .map(|l| l.chars().count().div_ceil(w).max(1))
+            .sum();
+        ((col % w) as u16, (rows_above + col / w) as u16)
+    }
+
+    fn paste_clipboard(&mut self) {
+        match SystemClipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+            Ok(text) => self.create_content.insert_str(&text.replace("\r\n", "\n")),
+            Err(e) => {
+                self.set_status(MessageKind::Error, format!("Paste failed: {}", e));
             }
         }
-        (0, visual_row as u16)
+    }
+
+    /// Applies a cursor movement or edit to the content field.
+    fn edit_content_key(&mut self, key: KeyEvent) {
+        let word = key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        match key.code {
+            // Terminals report Ctrl+Backspace as either Backspace or Ctrl+H
+            KeyCode::Backspace if word => self.create_content.delete_word_back(),
+            KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.create_content.delete_word_back()
+            }
+            KeyCode::Backspace => self.create_content.backspace(),
+            KeyCode::Delete => self.create_content.delete(),
+            KeyCode::Enter => self.create_content.insert_char('\n'),
+            KeyCode::Left => self.create_content.move_left(),
+            KeyCode::Right => self.create_content.move_right(),
+            KeyCode::Up => self.create_content.move_up(),
+            KeyCode::Down => self.create_content.move_down(),
+            KeyCode::Home => self.create_content.move_home(),
+            KeyCode::End => self.create_content.move_end(),
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.create_content.insert_char(c)
+            }
+            _ => {}
+        }
     }
 
     fn auto_scroll_edit(&mut self, cursor_visual_row: u16, visible_height: u16) {
@@ -299,105 +890,295 @@ impl App {
 
     fn start_create(&mut self) {
         self.create_name.clear();
+        self.create_language.clear();
+        self.create_tags.clear();
         self.create_content.clear();
+        self.create_strip_ansi = self.strip_ansi_default;
         self.edit_scroll = 0;
         self.focus = Focus::CreateName;
     }
 
-    fn save_create(&mut self, backend: &Backend) {
+    fn save_create(&mut self, backend: &dyn SnippetStore) {
         if self.create_name.trim().is_empty() {
-            self.status_message = Some(("Name cannot be empty".to_string(), Instant::now()));
+            self.set_status(MessageKind::Error, "Name cannot be empty");
             return;
         }
-        match backend.create_snippet(&self.create_name, &self.create_content) {
+        let content = if self.create_strip_ansi {
+            highlight::strip_ansi(self.create_content.as_str())
+        } else {
+            self.create_content.as_str().to_string()
+        };
+        match backend.create_snippet(
+            &self.create_name,
+            &content,
+            self.language_input(),
+            &self.tags_input(),
+        ) {
             Ok(snippet) => {
-                self.snippets.insert(0, snippet);
-                self.list_state.select(Some(0));
-                self.filtered_indices = None;
+                let short_id = snippet.short_id.clone();
+                self.snippets.insert(0, snippet.meta());
+                self.contents.insert(short_id.clone(), snippet.content);
+                self.sort_snippets();
                 self.search_query.clear();
-                self.status_message = Some(("Created!".to_string(), Instant::now()));
+                self.reset_filter();
+                self.select_short_id(&short_id);
+                self.set_status(MessageKind::Success, "Created!");
                 self.focus = Focus::List;
                 self.create_name.clear();
+                self.create_language.clear();
+                self.create_tags.clear();
                 self.create_content.clear();
             }
             Err(e) => {
-                self.status_message = Some((e.to_string(), Instant::now()));
+                self.set_status(MessageKind::Error, e.to_string());
             }
         }
     }
 
     fn cancel_create(&mut self) {
         self.create_name.clear();
+        self.create_language.clear();
+        self.create_tags.clear();
         self.create_content.clear();
         self.focus = Focus::List;
     }
 
+    /// Binary snippets are shown as base64, which saving would store as text.
+    fn refuse_binary_edit(&mut self) -> bool {
+        if self.selected_snippet().is_some_and(|s| !s.encoding.is_utf8()) {
+            self.set_status(MessageKind::Info, "Binary snippets can't be edited");
+            return true;
+        }
+        false
+    }
+
     fn start_edit(&mut self) {
+        if self.refuse_binary_edit() {
+            return;
+        }
+        if self.selected_snippet().is_some() && self.selected_content().is_none() {
+            self.set_status(MessageKind::Info, "Content is still loading");
+            return;
+        }
         let data = self.selected_snippet().map(|s| {
-            (s.name.clone(), s.content.clone(), s.short_id.clone())
+            (
+                s.name.clone(),
+                s.language.clone().unwrap_or_default(),
+                s.tags.join(", "),
+                self.contents[&s.short_id].clone(),
+                s.short_id.clone(),
+            )
         });
-        if let Some((name, content, short_id)) = data {
+        if let Some((name, language, tags, content, short_id)) = data {
             self.create_name = name;
-            self.create_content = content;
+            self.create_language = language;
+            self.create_tags = tags;
+            self.create_content.set_text(content.clone());
+            self.edit_original = content;
             self.edit_short_id = Some(short_id);
             self.edit_scroll = 0;
             self.focus = Focus::EditName;
         }
     }
 
-    fn save_edit(&mut self, backend: &Backend) {
+    fn save_edit(&mut self, backend: &dyn SnippetStore) {
         if self.create_name.trim().is_empty() {
-            self.status_message = Some(("Name cannot be empty".to_string(), Instant::now()));
+            self.set_status(MessageKind::Error, "Name cannot be empty");
             return;
         }
         let short_id = match &self.edit_short_id {
             Some(id) => id.clone(),
             None => return,
         };
-        match backend.update_snippet(&short_id, &self.create_name, &self.create_content) {
+        let tags = self.tags_input();
+        let name_only = self.snippets.iter().find(|s| s.short_id == short_id).is_some_and(|s| {
+            s.language.as_deref() == self.language_input()
+                && s.tags == tags
+                && self.contents.get(&short_id).map(String::as_str) == Some(self.create_content.as_str())
+        });
+        let result = if name_only {
+            backend.rename_snippet(&short_id, &self.create_name)
+        } else {
+            backend.update_snippet(
+                &short_id,
+                &self.create_name,
+                self.create_content.as_str(),
+                self.language_input(),
+                &tags,
+            )
+        };
+        match result {
             Ok(Some(updated)) => {
-                if let Some(pos) = self.snippets.iter().position(|s| s.short_id == short_id) {
-                    self.snippets[pos] = updated;
-                }
-                self.status_message = Some(("Updated!".to_string(), Instant::now()));
+                self.apply_update(updated);
+                self.set_status(MessageKind::Success, "Updated!");
                 self.focus = Focus::List;
                 self.create_name.clear();
+                self.create_language.clear();
+                self.create_tags.clear();
                 self.create_content.clear();
                 self.edit_short_id = None;
             }
             Ok(None) => {
-                self.status_message = Some(("Snippet not found".to_string(), Instant::now()));
+                self.set_status(MessageKind::Error, "Snippet not found");
+            }
+            Err(e) => {
+                self.set_status(MessageKind::Error, e.to_string());
             }
+        }
+    }
+
+    /// Replaces the cached copy of a snippet with what the store returned
+    /// after changing it, keeping it selected.
+    fn apply_update(&mut self, updated: Snippet) {
+        let short_id = updated.short_id.clone();
+        if let Some(pos) = self.snippets.iter().position(|s| s.short_id == short_id) {
+            self.snippets[pos] = updated.meta();
+        }
+        self.contents.insert(short_id.clone(), updated.content);
+        self.stats_cache = None;
+        // A rename or any edit can move the snippet when sorting by name or
+        // modification time, and a retag can move it in or out of the tag filter
+        self.sort_snippets();
+        if self.filtered_indices.is_some() {
+            self.update_search_filter();
+        }
+        self.select_short_id(&short_id);
+    }
+
+    /// Opens the selected snippet in `$VISUAL` or `$EDITOR` and saves what
+    /// comes back. The TUI is suspended while the editor runs; an editor that
+    /// exits with an error discards the changes.
+    fn edit_external(&mut self, terminal: &mut DefaultTerminal, backend: &dyn SnippetStore) {
+        if self.refuse_binary_edit() {
+            return;
+        }
+        let Some(snippet) = self.selected_snippet().cloned() else {
+            return;
+        };
+        let Some(original) = self.selected_content().map(str::to_string) else {
+            self.set_status(MessageKind::Info, "Content is still loading");
+            return;
+        };
+        let editor = std::env::var("VISUAL")
+            .ok()
+            .or_else(|| std::env::var("EDITOR").ok())
+            .filter(|e| !e.trim().is_empty())
+            .unwrap_or_else(|| "vi".to_string());
+
+        // Named after the snippet so the editor picks its highlighting from the extension
+        let path = std::env::temp_dir().join(format!(
+            "sipp-{}-{}-{}",
+            std::process::id(),
+            snippet.short_id,
+            sanitize_file_name(&snippet.name)
+        ));
+        let edited = write_new_file(&path, &original)
+            .and_then(|()| suspend_for(terminal, || run_editor(&editor, &path)))
+            .and_then(|()| std::fs::read_to_string(&path));
+        let _ = std::fs::remove_file(&path);
+
+        let content = match edited {
+            Ok(content) => content,
             Err(e) => {
-                self.status_message = Some((e.to_string(), Instant::now()));
+                self.set_status(MessageKind::Error, format!("Changes discarded: {}", e));
+                return;
+            }
+        };
+        if content == original {
+            self.set_status(MessageKind::Info, "No changes");
+            return;
+        }
+        match backend.update_snippet(
+            &snippet.short_id,
+            &snippet.name,
+            &content,
+            snippet.language.as_deref(),
+            &snippet.tags,
+        ) {
+            Ok(Some(updated)) => {
+                self.apply_update(updated);
+                self.set_status(MessageKind::Success, "Updated!");
             }
+            Ok(None) => self.set_status(MessageKind::Error, "Snippet not found"),
+            Err(e) => self.set_status(MessageKind::Error, e.to_string()),
         }
     }
 
     fn cancel_edit(&mut self) {
         self.create_name.clear();
+        self.create_language.clear();
+        self.create_tags.clear();
         self.create_content.clear();
         self.edit_short_id = None;
+        self.edit_original.clear();
         self.focus = Focus::List;
     }
 
+    fn language_input(&self) -> Option<&str> {
+        let language = self.create_language.trim();
+        if language.is_empty() { None } else { Some(language) }
+    }
+
+    fn tags_input(&self) -> Vec<String> {
+        db::parse_tags(&self.create_tags)
+    }
+
     fn start_search(&mut self) {
         self.search_query.clear();
-        self.filtered_indices = Some((0..self.snippets.len()).collect());
+        self.filtered_indices = Some(self.tagged_indices());
         self.focus = Focus::Search;
         self.list_state.select(if self.snippets.is_empty() { None } else { Some(0) });
     }
 
+    /// Turns content search on or off. Turning it on fetches every content
+    /// not viewed yet, so the search covers the whole store.
+    fn toggle_search_content(&mut self, backend: &dyn SnippetStore) {
+        self.search_content = !self.search_content;
+        if self.search_content && !self.all_contents_loaded {
+            match backend.list_snippets() {
+                Ok(snippets) => {
+                    for snippet in snippets {
+                        // Protected snippets are listed without their content, which
+                        // still loads on selection
+                        if !snippet.content.is_empty() {
+                            self.contents.entry(snippet.short_id).or_insert(snippet.content);
+                        }
+                    }
+                    self.all_contents_loaded = true;
+                }
+                Err(e) => self.set_status(MessageKind::Error, format!("Couldn't load contents to search: {}", e)),
+            }
+        }
+        self.update_search_filter();
+    }
+
     fn update_search_filter(&mut self) {
-        let query = self.search_query.to_lowercase();
-        let indices: Vec<usize> = self
-            .snippets
-            .iter()
-            .enumerate()
-            .filter(|(_, s)| s.name.to_lowercase().contains(&query))
-            .map(|(i, _)| i)
-            .collect();
-        self.filtered_indices = Some(indices);
+        self.search_matches.clear();
+        if self.search_query.is_empty() {
+            self.filtered_indices = Some(self.tagged_indices());
+        } else {
+            let content_query = self.search_query.to_lowercase();
+            let mut scored: Vec<(usize, i64)> = Vec::new();
+            for i in self.tagged_indices() {
+                let s = &self.snippets[i];
+                if let Some((score, positions)) =
+                    self.matcher.fuzzy_indices(&s.name, &self.search_query)
+                {
+                    scored.push((i, score));
+                    self.search_matches.insert(i, positions);
+                } else if self.search_content
+                    // `any` stops at the first matching line, so large snippets stay cheap
+                    && self.contents.get(&s.short_id).is_some_and(|content| {
+                        content.lines().any(|l| l.to_lowercase().contains(&content_query))
+                    })
+                {
+                    scored.push((i, 0));
+                }
+            }
+            // Stable sort keeps the original order for equal scores
+            scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+            self.filtered_indices = Some(scored.into_iter().map(|(i, _)| i).collect());
+        }
         if self.visible_count() == 0 {
             self.list_state.select(None);
         } else {
@@ -406,55 +1187,58 @@ impl App {
     }
 
     fn cancel_search(&mut self) {
-        self.filtered_indices = None;
+        self.search_matches.clear();
         self.search_query.clear();
         self.focus = Focus::List;
+        self.reset_filter();
     }
 
     fn confirm_search(&mut self) {
-        let real_index = self.list_state.selected().and_then(|i| {
-            self.filtered_indices.as_ref().and_then(|indices| indices.get(i).copied())
-        });
-        self.filtered_indices = None;
+        let selected = self.selected_snippet().map(|s| s.short_id.clone());
+        self.search_matches.clear();
         self.search_query.clear();
         self.focus = Focus::List;
-        if let Some(ri) = real_index {
-            self.list_state.select(Some(ri));
+        self.reset_filter();
+        if let Some(short_id) = selected {
+            self.select_short_id(&short_id);
         }
     }
 
+    fn set_status(&mut self, kind: MessageKind, message: impl Into<String>) {
+        self.status_message = Some((message.into(), kind, Instant::now()));
+    }
+
     fn clear_expired_status(&mut self) {
-        if let Some((_, time)) = &self.status_message {
-            if time.elapsed() > Duration::from_secs(2) {
-                self.status_message = None;
-            }
+        if let Some((_, _, time)) = &self.status_message
+            && time.elapsed() > self.status_timeout
+        {
+            self.status_message = None;
         }
     }
 
-    fn highlight_content(&self, name: &str, content: &str) -> Text<'static> {
-        let raw_ext = name.rsplit('.').next().unwrap_or("");
-        let ext = match raw_ext {
-            "ts" | "tsx" | "jsx" => "js",
-            other => other,
-        };
-        let syntax = self
-            .syntax_set
-            .find_syntax_by_extension(ext)
-            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
+    fn highlight_content(&self, name: &str, language: Option<&str>, content: &str) -> Text<'static> {
+        let syntax = highlight::find_syntax(&self.syntax_set, name, language, content);
         let mut highlighter = HighlightLines::new(syntax, &self.theme);
+        let gutter_width = LinesWithEndings::from(content).count().to_string().len();
 
         let lines: Vec<Line<'static>> = LinesWithEndings::from(content)
-            .map(|line| {
+            .enumerate()
+            .map(|(i, line)| {
                 let ranges = highlighter
                     .highlight_line(line, &self.syntax_set)
                     .unwrap_or_default();
-                let spans: Vec<Span<'static>> = ranges
-                    .into_iter()
-                    .map(|(style, text)| {
-                        let color = to_ratatui_color(style.foreground);
-                        Span::styled(text.to_owned(), Style::default().fg(color))
-                    })
-                    .collect();
+                let mut spans: Vec<Span<'static>> = Vec::with_capacity(ranges.len() + 1);
+                // The gutter is its own span so highlighted spans keep their colors
+                if self.show_line_numbers {
+                    spans.push(Span::styled(
+                        format!("{:>width$} ", i + 1, width = gutter_width),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                spans.extend(ranges.into_iter().map(|(style, text)| {
+                    let color = to_ratatui_color(style.foreground);
+                    Span::styled(text.to_owned(), Style::default().fg(color))
+                }));
                 Line::from(spans)
             })
             .collect();
@@ -463,116 +1247,710 @@ impl App {
     }
 }
 
+/// Appends a snippet's tags to its list entry, dimmed.
+fn with_tags<'a>(mut line: Line<'a>, tags: &'a [String]) -> Line<'a> {
+    for tag in tags {
+        line.spans.push(Span::styled(format!(" #{}", tag), Style::default().fg(Color::DarkGray)));
+    }
+    line
+}
+
+/// Renders `name` with the fuzzy-matched character positions in bold.
+fn highlight_matches<'a>(name: &'a str, positions: &[usize]) -> Line<'a> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, c) in name.chars().enumerate() {
+        let matched = positions.contains(&i);
+        if matched != run_matched && !run.is_empty() {
+            let text = std::mem::take(&mut run);
+            spans.push(if run_matched { Span::styled(text, bold) } else { Span::raw(text) });
+        }
+        run_matched = matched;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(if run_matched { Span::styled(run, bold) } else { Span::raw(run) });
+    }
+    Line::from(spans)
+}
+
+/// Converts a syntect color to a terminal color. ANSI themes encode a palette
+/// index in `r` with `a == 0`, and the terminal default with `a == 1`; any
+/// other color is a real RGB value.
 fn to_ratatui_color(color: syntect::highlighting::Color) -> Color {
-    if color.a == 0 {
-        Color::Indexed(color.r)
-    } else {
-        Color::Reset
+    match color.a {
+        0 => Color::Indexed(color.r),
+        1 => Color::Reset,
+        _ => Color::Rgb(color.r, color.g, color.b),
     }
 }
 
-fn resolve_backend(remote: Option<String>, api_key: Option<String>) -> Result<(Backend, bool, Option<String>), Box<dyn std::error::Error>> {
+fn remote_backend(
+    url: String,
+    api_key: Option<String>,
+    auth_scheme: config::AuthScheme,
+    insecure: bool,
+) -> SharedStore {
+    let backend = RemoteBackend::with_opts(
+        url,
+        api_key,
+        backend::DEFAULT_TIMEOUT,
+        backend::DEFAULT_CONNECT_TIMEOUT,
+        backend::DEFAULT_RETRIES,
+        insecure,
+    )
+    .with_auth_scheme(auth_scheme);
+    Arc::new(backend)
+}
+
+/// The `public_url` from `cfg`, checked the same way as `remote_url`.
+fn configured_public_url(cfg: &config::Config) -> Result<Option<String>, String> {
+    cfg.public_url
+        .as_deref()
+        .map(config::normalize_remote_url)
+        .transpose()
+        .map_err(|e| format!("{} in {}", e, config::config_path().display()))
+}
+
+/// Picks the backend: an explicit remote, else the local database if it exists,
+/// else the configured remote. The local database path comes from `db_path`
+/// (the `--db-path` flag or `SIPP_DB_PATH`), then the config file, then the default.
+fn resolve_backend(
+    remote: Option<String>,
+    api_key: Option<String>,
+    db_path: Option<PathBuf>,
+    profile: Option<&str>,
+    insecure: bool,
+) -> Result<(SharedStore, bool, Option<String>), Box<dyn std::error::Error>> {
+    let mut cfg = config::load_active(profile)?;
+    let remote = remote.map(|url| config::normalize_remote_url(&url)).transpose()?;
+    cfg.remote_url = cfg
+        .remote_url
+        .map(|url| config::normalize_remote_url(&url))
+        .transpose()
+        .map_err(|e| format!("{} in {}", e, config::config_path().display()))?;
+
     if let Some(url) = remote {
         return Ok((
-            Backend::remote(url.clone(), api_key),
+            remote_backend(url.clone(), api_key, cfg.auth_scheme, insecure),
+            true,
+            Some(url),
+        ));
+    }
+
+    // An explicitly chosen profile means its remote, even if a local database exists
+    if profile.is_some()
+        && let Some(url) = cfg.remote_url.clone()
+    {
+        let api_key = api_key.or(cfg.api_key);
+        return Ok((
+            remote_backend(url.clone(), api_key, cfg.auth_scheme, insecure),
             true,
             Some(url),
         ));
     }
 
-    if !std::path::Path::new(&crate::db::db_path()).exists() {
-        let cfg = config::load_config();
+    let db_path = db_path
+        .or_else(|| cfg.db_path.clone().map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from(crate::db::db_path()));
+    if !db_path.exists() {
         let url = cfg.remote_url.unwrap_or_else(|| "http://localhost:3000".to_string());
         let api_key = api_key.or(cfg.api_key);
-        return Ok((Backend::remote(url.clone(), api_key), true, Some(url)));
+        return Ok((
+            remote_backend(url.clone(), api_key, cfg.auth_scheme, insecure),
+            true,
+            Some(url),
+        ));
     }
 
-    Ok((Backend::local()?, false, Some("http://localhost:3000".to_string())))
+    Ok((Arc::new(LocalBackend::open_at(db_path)?), false, Some("http://localhost:3000".to_string())))
 }
 
-pub fn run_auth() -> Result<(), Box<dyn std::error::Error>> {
+/// Prompts for a remote URL and API key and saves them to the config file,
+/// under `[profiles.<profile>]` when a profile is given.
+pub fn run_auth(profile: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
     use std::io::{self, Write};
 
-    print!("Remote URL: ");
-    io::stdout().flush()?;
-    let mut remote_url = String::new();
-    io::stdin().read_line(&mut remote_url)?;
-    let remote_url = remote_url.trim().to_string();
-
-    print!("API Key: ");
+    let remote_url = loop {
+        print!("Remote URL: ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Err("No remote URL entered".into());
+        }
+        let input = input.trim();
+        if input.is_empty() {
+            break String::new();
+        }
+        match config::normalize_remote_url(input) {
+            Ok(url) => break url,
+            Err(e) => eprintln!("{}", e),
+        }
+    };
+
+    print!("API Key: ");
     io::stdout().flush()?;
     let api_key = rpassword::read_password()?;
     let api_key = api_key.trim().to_string();
 
-    let cfg = config::Config {
-        remote_url: if remote_url.is_empty() {
-            None
-        } else {
-            Some(remote_url)
-        },
-        api_key: if api_key.is_empty() {
-            None
-        } else {
-            Some(api_key)
-        },
-    };
+    let remote_url = if remote_url.is_empty() { None } else { Some(remote_url) };
+    let api_key = if api_key.is_empty() { None } else { Some(api_key) };
+
+    let mut cfg = config::load_config();
+    match profile {
+        Some(name) => {
+            let entry = cfg.profiles.entry(name.to_string()).or_default();
+            entry.remote_url = remote_url;
+            entry.api_key = api_key;
+        }
+        None => {
+            cfg.remote_url = remote_url;
+            cfg.api_key = api_key;
+        }
+    }
 
     config::save_config(&cfg)?;
     println!("Config saved to {}", config::config_path().display());
     Ok(())
 }
 
-pub fn run_interactive(remote: Option<String>, api_key: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
-    let (backend, is_remote, remote_url) = resolve_backend(remote, api_key)?;
+pub fn run_interactive(
+    remote: Option<String>,
+    api_key: Option<String>,
+    db_path: Option<PathBuf>,
+    profile: Option<&str>,
+    insecure: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (backend, is_remote, remote_url) = resolve_backend(remote, api_key, db_path, profile, insecure)?;
+
+    // An unreachable store is shown in the footer rather than on stderr,
+    // where it would scroll away as the TUI starts
+    let health = backend.health_check();
+    let snippets = match &health {
+        Ok(()) => match backend.list_snippet_metas() {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Failed to load snippets: {}", e);
+                Vec::new()
+            }
+        },
+        Err(_) => Vec::new(),
+    };
 
-    let snippets = match backend.list_snippets() {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("Failed to load snippets: {}", e);
-            Vec::new()
+    let cfg = config::load_active(profile)?;
+    let status_timeout =
+        Duration::from_millis(cfg.status_timeout_ms.unwrap_or(config::DEFAULT_STATUS_TIMEOUT_MS));
+    let (keymap, keymap_warnings) = Keymap::from_config(&cfg.keybindings);
+    // Printed before the TUI takes over the screen, so they're still there on quit
+    for warning in &keymap_warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    let mut app = App::new(snippets, is_remote, remote_url, cfg.theme.as_deref(), status_timeout);
+    app.public_url = configured_public_url(&cfg)?;
+    app.disconnected = health.err().map(|e| e.to_string());
+    app.keymap = keymap;
+    app.strip_ansi_default = cfg.strip_ansi;
+    if cfg.restore_session {
+        app.restore_session(&config::load_session());
+    }
+    if let Some(first) = keymap_warnings.first() {
+        let more = match keymap_warnings.len() {
+            1 => String::new(),
+            n => format!(" (and {} more)", n - 1),
+        };
+        app.set_status(MessageKind::Error, format!("Keybindings: {}{}", first, more));
+    }
+    ratatui::run(|terminal| run_app(terminal, &mut app, backend))?;
+    if cfg.restore_session {
+        let session = config::SessionState {
+            selected: app.selected_snippet().map(|s| s.short_id.clone()),
+            list_offset: app.list_state.offset(),
+        };
+        if let Err(e) = config::save_session(&session) {
+            eprintln!("Failed to save session: {}", e);
         }
-    };
+    }
+    if let Some(text) = app.uncopied {
+        eprintln!("{}", text);
+    }
+    Ok(())
+}
 
-    ratatui::run(|terminal| run_app(terminal, App::new(snippets, is_remote, remote_url), &backend))
+/// How `sipp <FILE>` and `sipp -` upload and report.
+#[derive(Clone, Copy, Default)]
+pub struct UploadOptions {
+    /// Print the snippet as JSON instead of a link.
+    pub json: bool,
+    /// Upload content that isn't UTF-8 text, replacing invalid bytes.
+    pub force: bool,
 }
 
-pub fn run_file_upload(remote: Option<String>, api_key: Option<String>, file: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    let (backend, _, remote_url) = resolve_backend(remote, api_key)?;
+pub fn run_file_upload(
+    remote: Option<String>,
+    api_key: Option<String>,
+    db_path: Option<PathBuf>,
+    profile: Option<&str>,
+    insecure: bool,
+    file: PathBuf,
+    options: UploadOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (backend, _, remote_url) = resolve_backend(remote, api_key, db_path, profile, insecure)?;
 
     let name = file
         .file_name()
         .ok_or("Invalid file path")?
         .to_string_lossy()
         .to_string();
-    let content = std::fs::read_to_string(&file)
+    let bytes = std::fs::read(&file)
         .map_err(|e| format!("Failed to read file: {}", e))?;
-    let snippet = backend
-        .create_snippet(&name, &content)
+    let content = upload_text(bytes, &name, options.force)?;
+    let public_url = configured_public_url(&config::load_active(profile)?)?;
+    share_upload(backend.as_ref(), public_url.as_deref(), remote_url.as_deref(), &name, &content, options.json)
+}
+
+/// Reads all of stdin into a snippet called `name` (or `stdin-<timestamp>.txt`)
+/// and prints its link. Refuses to run when stdin is a terminal, since there
+/// is nothing piped in to read.
+pub fn run_stdin_upload(
+    remote: Option<String>,
+    api_key: Option<String>,
+    db_path: Option<PathBuf>,
+    profile: Option<&str>,
+    insecure: bool,
+    name: Option<String>,
+    options: UploadOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::{IsTerminal, Read};
+
+    let mut stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return Err("Nothing to read from stdin; pipe content in, e.g. `cat file.rs | sipp - --name file.rs`".into());
+    }
+    let (backend, _, remote_url) = resolve_backend(remote, api_key, db_path, profile, insecure)?;
+
+    let mut bytes = Vec::new();
+    stdin
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read stdin: {}", e))?;
+    let name = name.unwrap_or_else(|| {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        format!("stdin-{}.txt", timestamp)
+    });
+    let content = upload_text(bytes, &name, options.force)?;
+    let public_url = configured_public_url(&config::load_active(profile)?)?;
+    share_upload(backend.as_ref(), public_url.as_deref(), remote_url.as_deref(), &name, &content, options.json)
+}
+
+/// Whether `bytes` look like binary data rather than text. Like git, this
+/// only looks for a NUL byte near the start, which text practically never has.
+fn looks_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(8000)].contains(&0)
+}
+
+/// Turns uploaded bytes into snippet content. Binary data and text in other
+/// encodings are refused unless `force` is set, in which case bytes that
+/// aren't valid UTF-8 become U+FFFD.
+fn upload_text(bytes: Vec<u8>, name: &str, force: bool) -> Result<String, String> {
+    if !force && looks_binary(&bytes) {
+        return Err(format!("Refusing to upload binary file {} (use --force to upload it anyway)", name));
+    }
+    match String::from_utf8(bytes) {
+        Ok(content) => Ok(content),
+        Err(e) if force => Ok(String::from_utf8_lossy(e.as_bytes()).into_owned()),
+        Err(_) => Err(format!(
+            "Refusing to upload {}: it isn't UTF-8 text (use --force to read it lossily)",
+            name
+        )),
+    }
+}
+
+/// Uploads at least this large are checked with the store before being sent.
+const PREFLIGHT_MIN_BYTES: usize = 64 * 1024;
+
+/// Creates a snippet, prints its link, and copies the link to the clipboard.
+/// With `json` it prints the snippet's short id, name, and link as one JSON
+/// object instead and leaves the clipboard alone. Large uploads are checked
+/// with the store first.
+fn share_upload(
+    backend: &dyn SnippetStore,
+    public_url: Option<&str>,
+    remote_url: Option<&str>,
+    name: &str,
+    content: &str,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Checked first so a large file that would be refused gets a clear reason
+    if content.len() >= PREFLIGHT_MIN_BYTES {
+        backend
+            .validate_snippet(name, content)
+            .map_err(|e| format!("Can't upload {}: {}", name, e))?;
+    }
+    let (snippet, link) = backend
+        .create_snippet_with_link(name, content, None, &[])
         .map_err(|e| format!("{}", e))?;
-    let link = match &remote_url {
-        Some(url) => format!("{}/s/{}", url.trim_end_matches('/'), snippet.short_id),
-        None => snippet.short_id.clone(),
-    };
+    if json {
+        // A local store has nothing to link to, so `url` is null rather than the short id
+        let url = (public_url.is_some() || remote_url.is_some() || link.is_some())
+            .then(|| snippet_link(public_url, remote_url, link.as_deref(), &snippet.short_id));
+        println!(
+            "{}",
+            serde_json::json!({"short_id": snippet.short_id, "name": snippet.name, "url": url})
+        );
+        return Ok(());
+    }
+    let link = snippet_link(public_url, remote_url, link.as_deref(), &snippet.short_id);
     println!("{}", link);
-    if let Ok(mut clipboard) = Clipboard::new() {
-        let _ = clipboard.set_text(&link);
+    if FallbackClipboard::detect().set_text(&link).is_ok() {
         println!("\u{2714} Copied to clipboard!");
     }
     Ok(())
 }
 
+/// How many deletions the TUI can undo.
+const UNDO_LIMIT: usize = 5;
+
+/// Lines of the selected snippet shown in the list pane's preview.
+const PREVIEW_LINES: usize = 8;
+
+/// A footer of `keys: label` hints for the actions bound in `keymap`, using
+/// the first key of each action. Hints whose actions are all unbound are
+/// left out.
+fn key_hints(keymap: &Keymap, hints: &[(&[Action], &str)]) -> Line<'static> {
+    let mut spans = Vec::new();
+    for (actions, label) in hints {
+        let Some(keys) = help_keys(keymap, actions) else {
+            continue;
+        };
+        if !spans.is_empty() {
+            spans.push(Span::raw("  "));
+        }
+        spans.push(Span::styled(keys, Style::default().fg(Color::Yellow)));
+        spans.push(Span::raw(format!(": {}", label)));
+    }
+    Line::from(spans)
+}
+
+/// The first key bound to each of `actions`, joined with `/`, or `None` when
+/// none of them is bound.
+fn help_keys(keymap: &Keymap, actions: &[Action]) -> Option<String> {
+    let keys: Vec<String> = actions.iter().filter_map(|&a| keymap.short_label(a)).collect();
+    if keys.is_empty() { None } else { Some(keys.join("/")) }
+}
+
+/// A unified diff from `old` to `new`, with removed lines in red and added
+/// ones in green. Unchanged stretches are cut down to a few lines of context
+/// around each change.
+fn diff_lines(old: &str, new: &str) -> Vec<Line<'static>> {
+    let hunks = diff::hunks(old, new, 3);
+    if hunks.is_empty() {
+        return vec![Line::from(Span::styled("No changes", Style::default().fg(Color::DarkGray)))];
+    }
+    let mut lines = Vec::new();
+    for hunk in hunks {
+        lines.push(Line::from(Span::styled(
+            format!("@@ -{},{} +{},{} @@", hunk.old_start, hunk.old_len, hunk.new_start, hunk.new_len),
+            Style::default().fg(Color::Cyan),
+        )));
+        for change in hunk.changes {
+            let (sign, style) = match change.tag {
+                similar::ChangeTag::Delete => ("-", Style::default().fg(Color::Red)),
+                similar::ChangeTag::Insert => ("+", Style::default().fg(Color::Green)),
+                similar::ChangeTag::Equal => (" ", Style::default()),
+            };
+            lines.push(Line::from(Span::styled(format!("{}{}", sign, change.line), style)));
+        }
+    }
+    lines
+}
+
+/// Creates `path`, which must not exist yet, readable only by the owner.
+fn write_new_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+/// Hands the terminal back to the shell while `run` runs, then takes it over
+/// again and redraws from scratch.
+fn suspend_for(
+    terminal: &mut DefaultTerminal,
+    run: impl FnOnce() -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    use crossterm::execute;
+    use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode};
+
+    disable_raw_mode()?;
+    execute!(std::io::stdout(), LeaveAlternateScreen, crossterm::cursor::Show)?;
+    let result = run();
+    // Restored whatever the editor did, so the TUI keeps working
+    enable_raw_mode()?;
+    execute!(std::io::stdout(), EnterAlternateScreen)?;
+    terminal.clear()?;
+    result
+}
+
+/// Runs `editor` on `path` and waits for it. `editor` may carry arguments,
+/// as in `code --wait`.
+fn run_editor(editor: &str, path: &Path) -> std::io::Result<()> {
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|e| std::io::Error::new(e.kind(), format!("can't run {}: {}", program, e)))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("{} exited with {}", program, status)))
+    }
+}
+
+/// Makes a snippet name safe to use as a file name on any platform.
+fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect();
+    let sanitized = sanitized.trim_start_matches('.');
+    if sanitized.is_empty() { "snippet".to_string() } else { sanitized.to_string() }
+}
+
+/// Writes every snippet to `out` as `<short_id>__<name>`, or prints them all
+/// as one JSON array when `json` is set.
+pub fn run_export(
+    remote: Option<String>,
+    api_key: Option<String>,
+    db_path: Option<PathBuf>,
+    profile: Option<&str>,
+    insecure: bool,
+    out: Option<PathBuf>,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (backend, _, _) = resolve_backend(remote, api_key, db_path, profile, insecure)?;
+    let snippets = backend.list_snippets().map_err(|e| format!("{}", e))?;
+
+    if json {
+        let stdout = std::io::stdout();
+        serde_json::to_writer_pretty(stdout.lock(), &snippets)?;
+        println!();
+        return Ok(());
+    }
+
+    let out = out.ok_or("An output directory is required unless --format json is used")?;
+    std::fs::create_dir_all(&out)
+        .map_err(|e| format!("Failed to create {}: {}", out.display(), e))?;
+    // Short ids differ only by case on case-insensitive file systems
+    let mut used = HashSet::new();
+    for snippet in &snippets {
+        let base = format!("{}__{}", snippet.short_id, sanitize_file_name(&snippet.name));
+        let mut file_name = base.clone();
+        let mut n = 2;
+        while !used.insert(file_name.to_lowercase()) {
+            file_name = format!("{}-{}", base, n);
+            n += 1;
+        }
+        let path = out.join(&file_name);
+        std::fs::write(&path, snippet.raw_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    println!("Exported {} snippets to {}", snippets.len(), out.display());
+    Ok(())
+}
+
+/// Files larger than this are skipped by [`run_dir_upload`].
+const MAX_IMPORT_SIZE: u64 = 512_000;
+
+/// Where a snippet can be shared from: under the configured `public_url`
+/// when there is one, else the link the server gave for it, else under the
+/// remote URL. Local snippets without any of those are named by short id.
+fn snippet_link(
+    public_url: Option<&str>,
+    remote_url: Option<&str>,
+    server_link: Option<&str>,
+    short_id: &str,
+) -> String {
+    if let Some(base) = public_url {
+        return format!("{}/s/{}", base.trim_end_matches('/'), short_id);
+    }
+    match (server_link, remote_url) {
+        (Some(link), _) if link.contains("://") => link.to_string(),
+        // Servers without a public URL of their own report just the path
+        (Some(path), Some(base)) if path.starts_with('/') => {
+            format!("{}{}", base.trim_end_matches('/'), path)
+        }
+        (_, Some(base)) => format!("{}/s/{}", base.trim_end_matches('/'), short_id),
+        (_, None) => short_id.to_string(),
+    }
+}
+
+/// Collects the regular files in `dir`, descending into subdirectories when
+/// `recursive` is set, in a stable order.
+fn collect_files(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|e| e.path());
+    for entry in entries {
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if recursive {
+                collect_files(&entry.path(), recursive, files)?;
+            }
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Uploads every text file in `dir` as a snippet named after the file and
+/// prints each link. Binary and oversized files are skipped; failed uploads
+/// don't stop the import but make it return an error at the end.
+pub fn run_dir_upload(
+    remote: Option<String>,
+    api_key: Option<String>,
+    db_path: Option<PathBuf>,
+    profile: Option<&str>,
+    insecure: bool,
+    dir: PathBuf,
+    recursive: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (backend, _, remote_url) = resolve_backend(remote, api_key, db_path, profile, insecure)?;
+    let public_url = configured_public_url(&config::load_active(profile)?)?;
+
+    let mut files = Vec::new();
+    collect_files(&dir, recursive, &mut files)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+
+    let mut uploaded = Vec::new();
+    let mut skipped = Vec::new();
+    let mut failed = Vec::new();
+    for file in files {
+        let name = file
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let size = std::fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+        if size > MAX_IMPORT_SIZE {
+            skipped.push((file, format!("larger than {} bytes", MAX_IMPORT_SIZE)));
+            continue;
+        }
+        let bytes = match std::fs::read(&file) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                failed.push((file, e.to_string()));
+                continue;
+            }
+        };
+        if looks_binary(&bytes) {
+            skipped.push((file, "binary".to_string()));
+            continue;
+        }
+        let Ok(content) = String::from_utf8(bytes) else {
+            skipped.push((file, "not UTF-8 text".to_string()));
+            continue;
+        };
+        match backend.create_snippet_with_link(&name, &content, None, &[]) {
+            Ok((snippet, link)) => {
+                let link = snippet_link(
+                    public_url.as_deref(),
+                    remote_url.as_deref(),
+                    link.as_deref(),
+                    &snippet.short_id,
+                );
+                uploaded.push((name, link))
+            }
+            Err(e) => failed.push((file, e.to_string())),
+        }
+    }
+
+    let width = uploaded.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+    for (name, link) in &uploaded {
+        println!("{:<width$}  {}", name, link, width = width);
+    }
+    for (file, reason) in &skipped {
+        println!("Skipped {}: {}", file.display(), reason);
+    }
+    for (file, reason) in &failed {
+        eprintln!("Failed {}: {}", file.display(), reason);
+    }
+    println!(
+        "{} uploaded, {} skipped, {} failed",
+        uploaded.len(),
+        skipped.len(),
+        failed.len()
+    );
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} file(s) failed to upload", failed.len()).into())
+    }
+}
+
+pub fn run_get(
+    remote: Option<String>,
+    api_key: Option<String>,
+    db_path: Option<PathBuf>,
+    profile: Option<&str>,
+    insecure: bool,
+    short_id: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (backend, _, _) = resolve_backend(remote, api_key, db_path, profile, insecure)?;
+
+    match backend.get_snippet(short_id).map_err(|e| format!("{}", e))? {
+        Some(snippet) if snippet.is_binary() => {
+            std::io::Write::write_all(&mut std::io::stdout(), &snippet.raw_bytes())?;
+            Ok(())
+        }
+        Some(snippet) => {
+            print!("{}", snippet.content);
+            Ok(())
+        }
+        None => Err(format!("Snippet '{}' not found", short_id).into()),
+    }
+}
+
+pub fn run_count(
+    remote: Option<String>,
+    api_key: Option<String>,
+    db_path: Option<PathBuf>,
+    profile: Option<&str>,
+    insecure: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (backend, _, _) = resolve_backend(remote, api_key, db_path, profile, insecure)?;
+    println!("{}", backend.count_snippets().map_err(|e| format!("{}", e))?);
+    Ok(())
+}
+
 fn run_app(
     terminal: &mut DefaultTerminal,
-    mut app: App,
-    backend: &Backend,
+    app: &mut App,
+    backend: SharedStore,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let loader = ContentLoader::spawn(backend.clone());
+    let backend = backend.as_ref();
     while !app.should_quit {
         app.clear_expired_status();
+        app.receive_contents(&loader);
+        app.request_selected_content(&loader);
 
-        let content_line_count = app
-            .selected_snippet()
-            .map(|s| s.content.lines().count() as u16)
-            .unwrap_or(0);
+        let stats = match app.focus {
+            Focus::List | Focus::Content => app.selected_stats(),
+            _ => None,
+        };
+        let content_max_width = app
+            .selected_content()
+            .and_then(|c| c.lines().map(|l| l.chars().count()).max())
+            .unwrap_or(0) as u16;
 
         terminal.draw(|frame| {
             let outer = Layout::vertical([Constraint::Min(1), Constraint::Length(1)])
@@ -588,12 +1966,12 @@ fn run_app(
                 indices
                     .iter()
                     .filter_map(|&i| app.snippets.get(i))
-                    .map(|s| ListItem::new(s.name.as_str()))
+                    .map(|s| ListItem::new(with_tags(Line::from(s.name.as_str()), &s.tags)))
                     .collect()
             } else {
                 app.snippets
                     .iter()
-                    .map(|s| ListItem::new(s.name.as_str()))
+                    .map(|s| ListItem::new(with_tags(Line::from(s.name.as_str()), &s.tags)))
                     .collect()
             };
 
@@ -606,10 +1984,14 @@ fn run_app(
                 _ => Style::default().fg(Color::DarkGray),
             };
 
+            let list_title = match &app.tag_filter {
+                Some(tag) => format!(" Snippets [{}] #{} ", app.sort_mode.label(), tag),
+                None => format!(" Snippets [{}] ", app.sort_mode.label()),
+            };
             let list = List::new(items)
                 .block(
                     Block::default()
-                        .title(" Snippets ")
+                        .title(list_title.as_str())
                         .borders(Borders::ALL)
                         .border_style(list_border_style),
                 )
@@ -630,16 +2012,25 @@ fn run_app(
                 let search_items: Vec<ListItem> = if let Some(indices) = &app.filtered_indices {
                     indices
                         .iter()
-                        .filter_map(|&i| app.snippets.get(i))
-                        .map(|s| ListItem::new(s.name.as_str()))
+                        .filter_map(|&i| app.snippets.get(i).map(|s| (i, s)))
+                        .map(|(i, s)| {
+                            let name = match app.search_matches.get(&i) {
+                                Some(positions) => highlight_matches(&s.name, positions),
+                                None => Line::from(s.name.as_str()),
+                            };
+                            ListItem::new(with_tags(name, &s.tags))
+                        })
                         .collect()
                 } else {
-                    app.snippets.iter().map(|s| ListItem::new(s.name.as_str())).collect()
+                    app.snippets
+                        .iter()
+                        .map(|s| ListItem::new(with_tags(Line::from(s.name.as_str()), &s.tags)))
+                        .collect()
                 };
                 let search_list = List::new(search_items)
                 .block(
                     Block::default()
-                        .title(" Snippets ")
+                        .title(list_title.as_str())
                         .borders(Borders::ALL)
                         .border_style(list_border_style),
                 )
@@ -651,9 +2042,14 @@ fn run_app(
                 .highlight_symbol("▶ ");
                 frame.render_stateful_widget(search_list, search_split[0], &mut app.list_state);
 
+                let search_title = if app.search_content {
+                    " Search [content] "
+                } else {
+                    " Search "
+                };
                 let search_input = Paragraph::new(app.search_query.as_str()).block(
                     Block::default()
-                        .title(" Search ")
+                        .title(search_title)
                         .borders(Borders::ALL)
                         .border_style(Style::default().fg(Color::Yellow)),
                 );
@@ -662,14 +2058,47 @@ fn run_app(
                 let x = search_split[1].x + 1 + app.search_query.len() as u16;
                 let y = search_split[1].y + 1;
                 frame.set_cursor_position((x, y));
+            } else if app.show_preview && matches!(app.focus, Focus::List) {
+                let [list_area, preview_area] =
+                    Layout::vertical([Constraint::Min(3), Constraint::Length(PREVIEW_LINES as u16 + 2)])
+                        .areas(chunks[0]);
+                frame.render_stateful_widget(list, list_area, &mut app.list_state);
+
+                let preview = match (app.selected_snippet(), app.selected_content()) {
+                    (Some(s), Some(content)) => {
+                        // Only the visible lines are highlighted, however long the snippet
+                        let head: String = LinesWithEndings::from(content).take(PREVIEW_LINES).collect();
+                        app.highlight_content(&s.name, s.language.as_deref(), &head)
+                    }
+                    (Some(_), None) => Text::styled("loading…", Style::default().fg(Color::DarkGray)),
+                    (None, _) => Text::default(),
+                };
+                frame.render_widget(
+                    Paragraph::new(preview).block(
+                        Block::default()
+                            .title(" Preview ")
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(Color::DarkGray)),
+                    ),
+                    preview_area,
+                );
             } else {
                 frame.render_stateful_widget(list, chunks[0], &mut app.list_state);
             }
 
             match app.focus {
-                Focus::CreateName | Focus::CreateContent | Focus::EditName | Focus::EditContent => {
+                Focus::CreateName
+                | Focus::CreateLanguage
+                | Focus::CreateTags
+                | Focus::CreateContent
+                | Focus::EditName
+                | Focus::EditLanguage
+                | Focus::EditTags
+                | Focus::EditContent => {
                     let form_title = match app.focus {
-                        Focus::EditName | Focus::EditContent => " Edit Snippet ",
+                        Focus::EditName | Focus::EditLanguage | Focus::EditTags | Focus::EditContent => {
+                            " Edit Snippet "
+                        }
                         _ => " New Snippet ",
                     };
                     let create_block = Block::default()
@@ -681,6 +2110,8 @@ fn run_app(
                     frame.render_widget(create_block, chunks[1]);
 
                     let form_layout = Layout::vertical([
+                        Constraint::Length(3),
+                        Constraint::Length(3),
                         Constraint::Length(3),
                         Constraint::Min(1),
                     ])
@@ -698,13 +2129,45 @@ fn run_app(
                     );
                     frame.render_widget(name_input, form_layout[0]);
 
+                    let language_style = match app.focus {
+                        Focus::CreateLanguage | Focus::EditLanguage => Style::default().fg(Color::Yellow),
+                        _ => Style::default().fg(Color::DarkGray),
+                    };
+                    let language_input = Paragraph::new(app.create_language.as_str()).block(
+                        Block::default()
+                            .title(" Language (optional) ")
+                            .borders(Borders::ALL)
+                            .border_style(language_style),
+                    );
+                    frame.render_widget(language_input, form_layout[1]);
+
+                    let tags_style = match app.focus {
+                        Focus::CreateTags | Focus::EditTags => Style::default().fg(Color::Yellow),
+                        _ => Style::default().fg(Color::DarkGray),
+                    };
+                    let tags_input = Paragraph::new(app.create_tags.as_str()).block(
+                        Block::default()
+                            .title(" Tags (comma-separated, optional) ")
+                            .borders(Borders::ALL)
+                            .border_style(tags_style),
+                    );
+                    frame.render_widget(tags_input, form_layout[2]);
+
                     let content_style = match app.focus {
                         Focus::CreateContent | Focus::EditContent => Style::default().fg(Color::Yellow),
                         _ => Style::default().fg(Color::DarkGray),
                     };
+                    let content_title = match app.focus {
+                        Focus::CreateName | Focus::CreateLanguage | Focus::CreateTags | Focus::CreateContent
+                            if app.create_strip_ansi =>
+                        {
+                            " Content [strip ANSI] "
+                        }
+                        _ => " Content ",
+                    };
                     let mut content_input = Paragraph::new(app.create_content.as_str()).block(
                         Block::default()
-                            .title(" Content ")
+                            .title(content_title)
                             .borders(Borders::ALL)
                             .border_style(content_style),
                     );
@@ -712,11 +2175,11 @@ fn run_app(
                         content_input = content_input.wrap(Wrap { trim: false });
                     }
                     content_input = content_input.scroll((app.edit_scroll, 0));
-                    frame.render_widget(content_input, form_layout[1]);
+                    frame.render_widget(content_input, form_layout[3]);
 
                     let content_inner = Block::default()
                         .borders(Borders::ALL)
-                        .inner(form_layout[1]);
+                        .inner(form_layout[3]);
                     let inner_width = content_inner.width;
                     let inner_height = content_inner.height;
 
@@ -726,20 +2189,22 @@ fn run_app(
                             let y = form_layout[0].y + 1;
                             frame.set_cursor_position((x, y));
                         }
+                        Focus::CreateLanguage | Focus::EditLanguage => {
+                            let x = form_layout[1].x + 1 + app.create_language.len() as u16;
+                            let y = form_layout[1].y + 1;
+                            frame.set_cursor_position((x, y));
+                        }
+                        Focus::CreateTags | Focus::EditTags => {
+                            let x = form_layout[2].x + 1 + app.create_tags.len() as u16;
+                            let y = form_layout[2].y + 1;
+                            frame.set_cursor_position((x, y));
+                        }
                         Focus::CreateContent | Focus::EditContent => {
                             let (cx, cy) = if app.wrap_content {
                                 app.cursor_position_wrapped(inner_width)
                             } else {
-                                let last_line = app.create_content.lines().last().unwrap_or("");
-                                let line_count = app.create_content.lines().count()
-                                    + if app.create_content.ends_with('\n') { 1 } else { 0 };
-                                let y_offset = if line_count == 0 { 0 } else { line_count - 1 };
-                                let col = if app.create_content.ends_with('\n') {
-                                    0
-                                } else {
-                                    last_line.len() as u16
-                                };
-                                (col, y_offset as u16)
+                                let (line, col) = app.create_content.cursor_line_col();
+                                (col as u16, line as u16)
                             };
                             app.auto_scroll_edit(cy, inner_height);
                             let screen_y = cy.saturating_sub(app.edit_scroll);
@@ -752,80 +2217,205 @@ fn run_app(
 
                 }
                 _ => {
-                    let highlighted = match app.selected_snippet() {
-                        Some(s) => app.highlight_content(&s.name, &s.content),
-                        None => Text::raw(""),
+                    let mut highlighted = match (app.selected_snippet(), app.selected_content()) {
+                        (Some(s), Some(content)) => {
+                            app.highlight_content(&s.name, s.language.as_deref(), content)
+                        }
+                        (Some(s), None) => match &app.load_error {
+                            Some((id, e)) if *id == s.short_id => Text::styled(
+                                format!("Failed to load: {}", e),
+                                Style::default().fg(Color::Red),
+                            ),
+                            _ => Text::styled("loading…", Style::default().fg(Color::DarkGray)),
+                        },
+                        (None, _) => {
+                            let hint = if app.snippets.is_empty() {
+                                "No snippets yet — press 'c' to create one"
+                            } else if app.visible_count() == 0 {
+                                "No matches"
+                            } else {
+                                ""
+                            };
+                            // Blank lines push the hint to the middle of the pane
+                            let padding = chunks[1].height.saturating_sub(2) / 2;
+                            let mut lines = vec![Line::raw(""); padding as usize];
+                            lines.push(
+                                Line::styled(hint, Style::default().fg(Color::DarkGray))
+                                    .alignment(Alignment::Center),
+                            );
+                            Text::from(lines)
+                        }
+                    };
+
+                    let inner_width = chunks[1].width.saturating_sub(2);
+                    let inner_height = chunks[1].height.saturating_sub(2) as usize;
+                    app.content_height = inner_height as u16;
+                    // Wrapped lines take extra rows, so the cursor's row counts them
+                    let cursor_row = if app.wrap_view {
+                        let above: Vec<Line> =
+                            highlighted.lines.iter().take(app.content_cursor).cloned().collect();
+                        Paragraph::new(above).wrap(Wrap { trim: false }).line_count(inner_width)
+                    } else {
+                        app.content_cursor
                     };
+                    let scroll = app.content_scroll as usize;
+                    if cursor_row < scroll {
+                        app.content_scroll = cursor_row as u16;
+                    } else if inner_height > 0 && cursor_row >= scroll + inner_height {
+                        app.content_scroll = (cursor_row + 1 - inner_height) as u16;
+                    }
+
+                    if matches!(app.focus, Focus::Content)
+                        && app.selected_content().is_some()
+                        && let Some(line) = highlighted.lines.get_mut(app.content_cursor)
+                    {
+                        line.style = Style::default().bg(Color::DarkGray);
+                        // The gutter is drawn in dark gray, so it needs its own color here
+                        if app.show_line_numbers
+                            && let Some(gutter) = line.spans.first_mut()
+                        {
+                            gutter.style = gutter.style.fg(Color::Yellow);
+                        }
+                    }
 
-                    let paragraph = Paragraph::new(highlighted)
+                    let mut paragraph = Paragraph::new(highlighted);
+                    if app.wrap_view {
+                        paragraph = paragraph.wrap(Wrap { trim: false });
+                    }
+                    // Rows the whole snippet takes up, counting wrapped lines
+                    let total_rows = paragraph.line_count(inner_width);
+                    let paragraph = paragraph
                         .block(
                             Block::default()
                                 .title(" Content ")
                                 .borders(Borders::ALL)
                                 .border_style(content_border_style),
                         )
-                        .scroll((app.content_scroll, 0));
+                        .scroll((
+                            app.content_scroll,
+                            if app.wrap_view { 0 } else { app.content_scroll_x },
+                        ));
 
                     frame.render_widget(paragraph, chunks[1]);
+
+                    if total_rows > inner_height {
+                        let mut scrollbar_state = ScrollbarState::new(total_rows - inner_height)
+                            .position(app.content_scroll as usize)
+                            .viewport_content_length(inner_height);
+                        frame.render_stateful_widget(
+                            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                                .begin_symbol(None)
+                                .end_symbol(None)
+                                .style(content_border_style),
+                            chunks[1].inner(Margin { vertical: 1, horizontal: 0 }),
+                            &mut scrollbar_state,
+                        );
+                    }
                 }
             }
 
             let hints = match app.focus {
-                Focus::List => Line::from(vec![
-                    Span::styled("j/k", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Navigate  "),
-                    Span::styled("Enter", Style::default().fg(Color::Yellow)),
-                    Span::raw(": View  "),
-                    Span::styled("y", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Copy  "),
-                    Span::styled("e", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Edit  "),
-                    Span::styled("d", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Delete  "),
-                    Span::styled("c", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Create  "),
-                    Span::styled("/", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Search  "),
-                    Span::styled("?", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Help  "),
-                    Span::styled("q", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Quit"),
-                ]),
-                Focus::Content => Line::from(vec![
-                    Span::styled("j/k", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Scroll  "),
-                    Span::styled("y", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Copy  "),
-                    Span::styled("e", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Edit  "),
-                    Span::styled("Esc", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Back  "),
-                    Span::styled("?", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Help"),
-                ]),
-                Focus::CreateName | Focus::CreateContent
-                | Focus::EditName | Focus::EditContent => Line::from(vec![
-                    Span::styled("Tab", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Switch field  "),
-                    Span::styled("Ctrl+S", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Save  "),
-                    Span::styled("Ctrl+W", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Wrap  "),
-                    Span::styled("Esc", Style::default().fg(Color::Yellow)),
-                    Span::raw(": Cancel"),
-                ]),
+                Focus::List => key_hints(
+                    &app.keymap,
+                    &[
+                        (&[Action::MoveDown, Action::MoveUp], "Navigate"),
+                        (&[Action::Open], "View"),
+                        (&[Action::Copy], "Copy"),
+                        (&[Action::Edit], "Edit"),
+                        (&[Action::Delete], "Delete"),
+                        (&[Action::Create], "Create"),
+                        (&[Action::Search], "Search"),
+                        (&[Action::Sort], "Sort"),
+                        (&[Action::Help], "Help"),
+                        (&[Action::Quit], "Quit"),
+                    ],
+                ),
+                Focus::Content => key_hints(
+                    &app.keymap,
+                    &[
+                        (&[Action::MoveDown, Action::MoveUp, Action::ScrollLeft, Action::ScrollRight], "Move"),
+                        (&[Action::PageUp, Action::PageDown, Action::Top, Action::Bottom], "Page/Top/Bottom"),
+                        (&[Action::ToggleWrap], "Wrap"),
+                        (&[Action::ToggleLineNumbers], "Line numbers"),
+                        (&[Action::Copy], "Copy"),
+                        (&[Action::CopyLine], "Copy line"),
+                        (&[Action::Edit], "Edit"),
+                        (&[Action::Back], "Back"),
+                        (&[Action::Help], "Help"),
+                    ],
+                ),
+                Focus::CreateName | Focus::CreateLanguage | Focus::CreateTags | Focus::CreateContent
+                | Focus::EditName | Focus::EditLanguage | Focus::EditTags | Focus::EditContent => {
+                    let mut spans = vec![
+                        Span::styled("Tab", Style::default().fg(Color::Yellow)),
+                        Span::raw(": Switch field  "),
+                        Span::styled("Ctrl+S", Style::default().fg(Color::Yellow)),
+                        Span::raw(": Save  "),
+                        Span::styled("Ctrl+W", Style::default().fg(Color::Yellow)),
+                        Span::raw(": Wrap  "),
+                        Span::styled("Ctrl+V", Style::default().fg(Color::Yellow)),
+                        Span::raw(": Paste  "),
+                    ];
+                    if matches!(app.focus, Focus::EditContent) {
+                        spans.push(Span::styled("Ctrl+G", Style::default().fg(Color::Yellow)));
+                        spans.push(Span::raw(": Diff  "));
+                    }
+                    spans.push(Span::styled("Esc", Style::default().fg(Color::Yellow)));
+                    spans.push(Span::raw(": Cancel"));
+                    Line::from(spans)
+                }
                 Focus::Search => Line::from(vec![
                     Span::styled("Type", Style::default().fg(Color::Yellow)),
                     Span::raw(": Filter  "),
+                    Span::styled("Ctrl+F", Style::default().fg(Color::Yellow)),
+                    Span::raw(": Search content  "),
                     Span::styled("Enter", Style::default().fg(Color::Yellow)),
                     Span::raw(": Select  "),
                     Span::styled("Esc", Style::default().fg(Color::Yellow)),
                     Span::raw(": Cancel"),
                 ]),
             };
-            frame.render_widget(Paragraph::new(hints), outer[1]);
+            let hints = match &app.disconnected {
+                Some(_) => {
+                    let target = app.remote_url.as_deref().unwrap_or("the local database");
+                    let mut spans = vec![
+                        Span::styled(
+                            format!("\u{26a0} disconnected from {} ", target),
+                            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                        ),
+                        Span::styled("r", Style::default().fg(Color::Yellow)),
+                        Span::raw(": Retry  "),
+                    ];
+                    spans.extend(hints.spans);
+                    Line::from(spans)
+                }
+                None => hints,
+            };
+            match stats {
+                Some(stats) => {
+                    let label = stats.label();
+                    let [hints_area, stats_area] = Layout::horizontal([
+                        Constraint::Min(0),
+                        Constraint::Length(label.chars().count() as u16 + 1),
+                    ])
+                    .areas(outer[1]);
+                    frame.render_widget(Paragraph::new(hints), hints_area);
+                    frame.render_widget(
+                        Paragraph::new(label)
+                            .style(Style::default().fg(Color::DarkGray))
+                            .alignment(Alignment::Right),
+                        stats_area,
+                    );
+                }
+                None => frame.render_widget(Paragraph::new(hints), outer[1]),
+            }
 
-            if let Some((msg, _)) = &app.status_message {
+            if let Some((msg, kind, _)) = &app.status_message {
+                let color = match kind {
+                    MessageKind::Info => Color::Yellow,
+                    MessageKind::Success => Color::Green,
+                    MessageKind::Error => Color::Red,
+                };
                 let area = frame.area();
                 let msg_width = (msg.len() as u16 + 4).max(20).min(area.width.saturating_sub(4));
                 let popup_area = ratatui::layout::Rect {
@@ -836,12 +2426,12 @@ fn run_app(
                 };
                 Clear.render(popup_area, frame.buffer_mut());
                 let status_popup = Paragraph::new(Line::from(msg.as_str()))
-                    .style(Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))
+                    .style(Style::default().fg(color).add_modifier(Modifier::BOLD))
                     .alignment(Alignment::Center)
                     .block(
                         Block::default()
                             .borders(Borders::ALL)
-                            .border_style(Style::default().fg(Color::Green)),
+                            .border_style(Style::default().fg(color)),
                     );
                 frame.render_widget(status_popup, popup_area);
             }
@@ -871,167 +2461,105 @@ fn run_app(
                 frame.render_widget(confirm_popup, popup_area);
             }
 
-            if app.show_help {
+            if app.show_diff {
                 let area = frame.area();
-                let popup_width = 34u16.min(area.width.saturating_sub(4));
-                let popup_height = 21u16.min(area.height.saturating_sub(4));
                 let popup_area = ratatui::layout::Rect {
-                    x: (area.width.saturating_sub(popup_width)) / 2,
-                    y: (area.height.saturating_sub(popup_height)) / 2,
-                    width: popup_width,
-                    height: popup_height,
+                    x: area.width / 10,
+                    y: area.height / 10,
+                    width: area.width - area.width / 5,
+                    height: area.height - area.height / 5,
                 };
+                let diff_lines = diff_lines(&app.edit_original, app.create_content.as_str());
+                let max_scroll = (diff_lines.len() as u16).saturating_sub(popup_area.height.saturating_sub(2));
+                app.diff_scroll = app.diff_scroll.min(max_scroll);
 
-                let mut help_lines = vec![
-                    Line::from(""),
-                    Line::from(vec![
-                        Span::styled(
-                            "  j/↓  ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Move down / Scroll down"),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(
-                            "  k/↑  ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Move up / Scroll up"),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(
-                            "  Enter",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("  Focus content pane"),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(
-                            "  Esc  ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Back / Quit"),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(
-                            "  y    ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Copy snippet"),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(
-                            "  Y    ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Copy link"),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(
-                            "  o    ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Open in browser"),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(
-                            "  d    ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Delete snippet"),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(
-                            "  c    ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Create snippet"),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(
-                            "  e    ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Edit snippet"),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(
-                            "  /    ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Search snippets"),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(
-                            "  ^W   ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Toggle word wrap (edit)"),
-                    ]),
-                ];
+                Clear.render(popup_area, frame.buffer_mut());
+                let diff = Paragraph::new(Text::from(diff_lines))
+                    .scroll((app.diff_scroll, 0))
+                    .block(
+                        Block::default()
+                            .title(" Changes ")
+                            .title_bottom(" j/k: Scroll  any other key: Back to editing ")
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(Color::Yellow)),
+                    );
+                frame.render_widget(diff, popup_area);
+            }
 
-                if app.is_remote {
-                    help_lines.push(Line::from(vec![
-                        Span::styled(
-                            "  r    ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Refresh snippets"),
-                    ]));
+            if app.show_help {
+                let area = frame.area();
+                let km = &app.keymap;
+                let mut rows: Vec<(Option<String>, &str)> = vec![
+                    (km.label(Action::MoveDown), "Move down / Scroll down"),
+                    (km.label(Action::MoveUp), "Move up / Scroll up"),
+                    (help_keys(km, &[Action::ScrollLeft, Action::ScrollRight]), "Scroll left / right"),
+                    (help_keys(km, &[Action::Open]), "Focus content pane"),
+                    (help_keys(km, &[Action::Back]), "Back to the list (content pane)"),
+                    (km.label(Action::Copy), "Copy snippet"),
+                    (km.label(Action::CopyLink), "Copy link"),
+                    (km.label(Action::CopyMarkdown), "Copy as markdown code block"),
+                    (km.label(Action::CopyLine), "Copy line under cursor (content pane)"),
+                    (help_keys(km, &[Action::Top, Action::Bottom]), "Top / bottom (content pane)"),
+                    (help_keys(km, &[Action::PageUp, Action::PageDown]), "Page up / down (content pane)"),
+                    (help_keys(km, &[Action::HalfPageUp, Action::HalfPageDown]), "Half page up / down (content pane)"),
+                    (km.label(Action::OpenInBrowser), "Open in browser"),
+                    (km.label(Action::Delete), "Delete snippet"),
+                    (km.label(Action::Undo), "Undo the last delete"),
+                    (km.label(Action::Create), "Create snippet"),
+                    (km.label(Action::Edit), "Edit snippet"),
+                    (km.label(Action::EditExternal), "Edit snippet in $EDITOR"),
+                    (km.label(Action::Search), "Search snippets"),
+                    (km.label(Action::Sort), "Cycle sort order"),
+                    (km.label(Action::TagFilter), "Filter by tag (cycles through tags)"),
+                    (km.label(Action::TogglePreview), "Toggle preview under the list"),
+                    (Some("^W".to_string()), "Toggle word wrap (edit)"),
+                    (Some("^G".to_string()), "Show changes (edit)"),
+                    (km.label(Action::ToggleWrap), "Toggle word wrap (view)"),
+                    (km.label(Action::ToggleLineNumbers), "Toggle line numbers"),
+                ];
+                if app.is_remote || app.disconnected.is_some() {
+                    rows.push((km.label(Action::Refresh), "Refresh snippets"));
                 }
-
-                help_lines.extend([
+                rows.push((km.label(Action::Quit), "Quit"));
+                rows.push((km.label(Action::Help), "Toggle this help"));
+
+                // Unbound actions are left out; the key column fits the widest label
+                let rows: Vec<(String, &str)> = rows.into_iter().filter_map(|(keys, desc)| Some((keys?, desc))).collect();
+                let key_width = rows.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or(0).max(5);
+                let mut help_lines = vec![Line::from("")];
+                help_lines.extend(rows.iter().map(|(keys, desc)| {
                     Line::from(vec![
                         Span::styled(
-                            "  q    ",
+                            format!("  {:<width$}  ", keys, width = key_width),
                             Style::default()
                                 .fg(Color::Yellow)
                                 .add_modifier(Modifier::BOLD),
                         ),
-                        Span::raw("Quit"),
-                    ]),
-                    Line::from(vec![
-                        Span::styled(
-                            "  ?    ",
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw("Toggle this help"),
-                    ]),
+                        Span::raw(*desc),
+                    ])
+                }));
+                help_lines.extend([
                     Line::from(""),
                     Line::from(Span::styled(
                         "  Press any key to close",
                         Style::default().fg(Color::DarkGray),
                     )),
+                    Line::from(Span::styled(
+                        concat!("  sipp v", env!("CARGO_PKG_VERSION")),
+                        Style::default().fg(Color::DarkGray),
+                    )),
                 ]);
 
+                let text_width = help_lines.iter().map(Line::width).max().unwrap_or(0) as u16;
+                let popup_width = (text_width + 4).min(area.width.saturating_sub(4));
+                let popup_height = (help_lines.len() as u16 + 2).min(area.height.saturating_sub(4));
+                let popup_area = ratatui::layout::Rect {
+                    x: (area.width.saturating_sub(popup_width)) / 2,
+                    y: (area.height.saturating_sub(popup_height)) / 2,
+                    width: popup_width,
+                    height: popup_height,
+                };
+
                 let help_text = Text::from(help_lines);
 
                 Clear.render(popup_area, frame.buffer_mut());
@@ -1045,152 +2573,202 @@ fn run_app(
             }
         })?;
 
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                if app.show_help {
-                    app.show_help = false;
-                } else if app.status_message.is_some() {
-                    app.status_message = None;
-                } else if app.confirm_delete {
-                    if key.code == KeyCode::Char('y') {
-                        app.delete_selected(backend);
+        if event::poll(Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()?
+        {
+            // A key dismisses the status popup and still does its usual action
+            app.status_message = None;
+            if app.show_help {
+                app.show_help = false;
+            } else if app.show_diff {
+                // Scrolling keeps the diff up; anything else goes back to editing
+                match key.code {
+                    KeyCode::Down | KeyCode::Char('j') => app.diff_scroll = app.diff_scroll.saturating_add(1),
+                    KeyCode::Up | KeyCode::Char('k') => app.diff_scroll = app.diff_scroll.saturating_sub(1),
+                    _ => app.show_diff = false,
+                }
+            } else if app.confirm_delete {
+                if key.code == KeyCode::Char('y') {
+                    app.delete_selected(backend);
+                }
+                app.confirm_delete = false;
+            } else {
+                match app.focus {
+                    Focus::List | Focus::Content => {
+                        let context = match app.focus {
+                            Focus::List => keymap::Context::List,
+                            _ => keymap::Context::Content,
+                        };
+                        if let Some(action) = app.resolve_key(context, &key) {
+                            app.run_action(action, backend, content_max_width);
+                        }
+                        if std::mem::take(&mut app.external_edit_requested) {
+                            app.edit_external(terminal, backend);
+                        }
                     }
-                    app.confirm_delete = false;
-                } else {
-                    match app.focus {
-                        Focus::List => match key.code {
-                            KeyCode::Char('q') | KeyCode::Esc => app.should_quit = true,
-                            KeyCode::Char('j') | KeyCode::Down => app.move_down(),
-                            KeyCode::Char('k') | KeyCode::Up => app.move_up(),
-                            KeyCode::Char('y') => app.copy_selected(),
-                            KeyCode::Char('Y') => app.copy_link(),
-                            KeyCode::Char('d') => app.confirm_delete = true,
-                            KeyCode::Char('c') => app.start_create(),
-                            KeyCode::Char('e') => app.start_edit(),
-                            KeyCode::Char('/') => app.start_search(),
-                            KeyCode::Char('o') => app.open_in_browser(),
-                            KeyCode::Char('r') if app.is_remote => app.refresh(backend),
-                            KeyCode::Char('?') => app.show_help = true,
-                            KeyCode::Enter | KeyCode::Char('l') => {
-                                if app.selected_snippet().is_some() {
-                                    app.focus = Focus::Content;
+                    Focus::CreateName => {
+                        if key.modifiers.contains(KeyModifiers::CONTROL)
+                            && key.code == KeyCode::Char('s')
+                        {
+                            app.save_create(backend);
+                        } else {
+                            match key.code {
+                                KeyCode::Esc => app.cancel_create(),
+                                KeyCode::Enter | KeyCode::Tab => {
+                                    app.focus = Focus::CreateLanguage
                                 }
+                                KeyCode::Backspace => {
+                                    app.create_name.pop();
+                                }
+                                KeyCode::Char(c) => app.create_name.push(c),
+                                _ => {}
                             }
-                            _ => {}
-                        },
-                        Focus::Content => match key.code {
-                          KeyCode::Char(' ') | KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('h') => {
-                                app.focus = Focus::List;
-                            }
-                            KeyCode::Char('j') | KeyCode::Down => {
-                                app.scroll_down(content_line_count);
+                        }
+                    }
+                    Focus::CreateLanguage => {
+                        if key.modifiers.contains(KeyModifiers::CONTROL)
+                            && key.code == KeyCode::Char('s')
+                        {
+                            app.save_create(backend);
+                        } else {
+                            match key.code {
+                                KeyCode::Esc => app.cancel_create(),
+                                KeyCode::Enter | KeyCode::Tab => {
+                                    app.focus = Focus::CreateTags
+                                }
+                                KeyCode::Backspace => {
+                                    app.create_language.pop();
+                                }
+                                KeyCode::Char(c) => app.create_language.push(c),
+                                _ => {}
                             }
-                            KeyCode::Char('k') | KeyCode::Up => app.scroll_up(),
-                            KeyCode::Char('y') => app.copy_selected(),
-                            KeyCode::Char('Y') => app.copy_link(),
-                            KeyCode::Char('e') => app.start_edit(),
-                            KeyCode::Char('o') => app.open_in_browser(),
-                            KeyCode::Char('?') => app.show_help = true,
-                            _ => {}
-                        },
-                        Focus::CreateName => {
-                            if key.modifiers.contains(KeyModifiers::CONTROL)
-                                && key.code == KeyCode::Char('s')
-                            {
-                                app.save_create(backend);
-                            } else {
-                                match key.code {
-                                    KeyCode::Esc => app.cancel_create(),
-                                    KeyCode::Enter | KeyCode::Tab => {
-                                        app.focus = Focus::CreateContent
-                                    }
-                                    KeyCode::Backspace => {
-                                        app.create_name.pop();
-                                    }
-                                    KeyCode::Char(c) => app.create_name.push(c),
-                                    _ => {}
+                        }
+                    }
+                    Focus::CreateTags => {
+                        if key.modifiers.contains(KeyModifiers::CONTROL)
+                            && key.code == KeyCode::Char('s')
+                        {
+                            app.save_create(backend);
+                        } else {
+                            match key.code {
+                                KeyCode::Esc => app.cancel_create(),
+                                KeyCode::Enter | KeyCode::Tab => {
+                                    app.focus = Focus::CreateContent
+                                }
+                                KeyCode::Backspace => {
+                                    app.create_tags.pop();
                                 }
+                                KeyCode::Char(c) => app.create_tags.push(c),
+                                _ => {}
+                            }
+                        }
+                    }
+                    Focus::CreateContent => {
+                        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                        match key.code {
+                            KeyCode::Char('s') if ctrl => app.save_create(backend),
+                            KeyCode::Char('v') if ctrl => app.paste_clipboard(),
+                            KeyCode::Char('t') if ctrl => app.create_strip_ansi = !app.create_strip_ansi,
+                            KeyCode::Char('w') if ctrl => {
+                                app.wrap_content = !app.wrap_content;
+                                app.edit_scroll = 0;
                             }
+                            KeyCode::Esc => app.cancel_create(),
+                            KeyCode::Tab => app.focus = Focus::CreateName,
+                            _ => app.edit_content_key(key),
                         }
-                        Focus::CreateContent => {
-                            if key.modifiers.contains(KeyModifiers::CONTROL) {
-                                match key.code {
-                                    KeyCode::Char('s') => app.save_create(backend),
-                                    KeyCode::Char('w') => {
-                                        app.wrap_content = !app.wrap_content;
-                                        app.edit_scroll = 0;
-                                    }
-                                    _ => {}
+                    }
+                    Focus::EditName => {
+                        if key.modifiers.contains(KeyModifiers::CONTROL)
+                            && key.code == KeyCode::Char('s')
+                        {
+                            app.save_edit(backend);
+                        } else {
+                            match key.code {
+                                KeyCode::Esc => app.cancel_edit(),
+                                KeyCode::Enter | KeyCode::Tab => {
+                                    app.focus = Focus::EditLanguage
                                 }
-                            } else {
-                                match key.code {
-                                    KeyCode::Esc => app.cancel_create(),
-                                    KeyCode::Tab => app.focus = Focus::CreateName,
-                                    KeyCode::Enter => app.create_content.push('\n'),
-                                    KeyCode::Backspace => {
-                                        app.create_content.pop();
-                                    }
-                                    KeyCode::Char(c) => app.create_content.push(c),
-                                    _ => {}
+                                KeyCode::Backspace => {
+                                    app.create_name.pop();
                                 }
+                                KeyCode::Char(c) => app.create_name.push(c),
+                                _ => {}
                             }
                         }
-                        Focus::EditName => {
-                            if key.modifiers.contains(KeyModifiers::CONTROL)
-                                && key.code == KeyCode::Char('s')
-                            {
-                                app.save_edit(backend);
-                            } else {
-                                match key.code {
-                                    KeyCode::Esc => app.cancel_edit(),
-                                    KeyCode::Enter | KeyCode::Tab => {
-                                        app.focus = Focus::EditContent
-                                    }
-                                    KeyCode::Backspace => {
-                                        app.create_name.pop();
-                                    }
-                                    KeyCode::Char(c) => app.create_name.push(c),
-                                    _ => {}
+                    }
+                    Focus::EditLanguage => {
+                        if key.modifiers.contains(KeyModifiers::CONTROL)
+                            && key.code == KeyCode::Char('s')
+                        {
+                            app.save_edit(backend);
+                        } else {
+                            match key.code {
+                                KeyCode::Esc => app.cancel_edit(),
+                                KeyCode::Enter | KeyCode::Tab => {
+                                    app.focus = Focus::EditTags
                                 }
+                                KeyCode::Backspace => {
+                                    app.create_language.pop();
+                                }
+                                KeyCode::Char(c) => app.create_language.push(c),
+                                _ => {}
                             }
                         }
-                        Focus::EditContent => {
-                            if key.modifiers.contains(KeyModifiers::CONTROL) {
-                                match key.code {
-                                    KeyCode::Char('s') => app.save_edit(backend),
-                                    KeyCode::Char('w') => {
-                                        app.wrap_content = !app.wrap_content;
-                                        app.edit_scroll = 0;
-                                    }
-                                    _ => {}
+                    }
+                    Focus::EditTags => {
+                        if key.modifiers.contains(KeyModifiers::CONTROL)
+                            && key.code == KeyCode::Char('s')
+                        {
+                            app.save_edit(backend);
+                        } else {
+                            match key.code {
+                                KeyCode::Esc => app.cancel_edit(),
+                                KeyCode::Enter | KeyCode::Tab => {
+                                    app.focus = Focus::EditContent
                                 }
-                            } else {
-                                match key.code {
-                                    KeyCode::Esc => app.cancel_edit(),
-                                    KeyCode::Tab => app.focus = Focus::EditName,
-                                    KeyCode::Enter => app.create_content.push('\n'),
-                                    KeyCode::Backspace => {
-                                        app.create_content.pop();
-                                    }
-                                    KeyCode::Char(c) => app.create_content.push(c),
-                                    _ => {}
+                                KeyCode::Backspace => {
+                                    app.create_tags.pop();
                                 }
+                                KeyCode::Char(c) => app.create_tags.push(c),
+                                _ => {}
                             }
                         }
-                        Focus::Search => match key.code {
-                            KeyCode::Esc => app.cancel_search(),
-                            KeyCode::Enter => app.confirm_search(),
-                            KeyCode::Backspace => {
-                                app.search_query.pop();
-                                app.update_search_filter();
+                    }
+                    Focus::EditContent => {
+                        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                        match key.code {
+                            KeyCode::Char('s') if ctrl => app.save_edit(backend),
+                            KeyCode::Char('v') if ctrl => app.paste_clipboard(),
+                            KeyCode::Char('g') if ctrl => {
+                                app.show_diff = true;
+                                app.diff_scroll = 0;
                             }
-                            KeyCode::Char(c) => {
-                                app.search_query.push(c);
-                                app.update_search_filter();
+                            KeyCode::Char('w') if ctrl => {
+                                app.wrap_content = !app.wrap_content;
+                                app.edit_scroll = 0;
                             }
-                            _ => {}
-                        },
+                            KeyCode::Esc => app.cancel_edit(),
+                            KeyCode::Tab => app.focus = Focus::EditName,
+                            _ => app.edit_content_key(key),
+                        }
                     }
+                    Focus::Search => match key.code {
+                        KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            app.toggle_search_content(backend);
+                        }
+                        KeyCode::Esc => app.cancel_search(),
+                        KeyCode::Enter => app.confirm_search(),
+                        KeyCode::Backspace => {
+                            app.search_query.pop();
+                            app.update_search_filter();
+                        }
+                        KeyCode::Char(c) => {
+                            app.search_query.push(c);
+                            app.update_search_filter();
+                        }
+                        _ => {}
+                    },
                 }
             }
         }
@@ -1198,3 +2776,244 @@ fn run_app(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::LocalBackend;
+    use std::sync::Mutex;
+
+    /// A store that keeps snippets in a `Vec` and records which write
+    /// methods were called. When `offline` it can't be listed or written to.
+    #[derive(Default)]
+    struct MemoryStore {
+        snippets: Mutex<Vec<Snippet>>,
+        calls: Mutex<Vec<&'static str>>,
+        offline: bool,
+    }
+
+    impl MemoryStore {
+        fn with(names: &[&str]) -> Self {
+            let store = MemoryStore::default();
+            for name in names {
+                store.create_snippet(name, &format!("content of {}", name), None, &[]).unwrap();
+            }
+            store.calls.lock().unwrap().clear();
+            store
+        }
+
+        fn reachable(&self) -> Result<(), BackendError> {
+            if self.offline {
+                return Err(BackendError::Network("offline".to_string()));
+            }
+            Ok(())
+        }
+
+        fn record(&self, call: &'static str) -> Result<(), BackendError> {
+            self.reachable()?;
+            self.calls.lock().unwrap().push(call);
+            Ok(())
+        }
+
+        fn copy(snippet: &Snippet) -> Snippet {
+            Snippet {
+                short_id: snippet.short_id.clone(),
+                content: snippet.content.clone(),
+                name: snippet.name.clone(),
+                language: snippet.language.clone(),
+                tags: snippet.tags.clone(),
+                url: None,
+                password_hash: None,
+                ..*snippet
+            }
+        }
+    }
+
+    impl SnippetStore for MemoryStore {
+        fn list_snippets(&self) -> Result<Vec<Snippet>, BackendError> {
+            self.reachable()?;
+            Ok(self.snippets.lock().unwrap().iter().map(MemoryStore::copy).collect())
+        }
+
+        fn count_snippets(&self) -> Result<usize, BackendError> {
+            Ok(self.snippets.lock().unwrap().len())
+        }
+
+        fn health_check(&self) -> Result<(), BackendError> {
+            self.reachable()
+        }
+
+        fn get_snippet(&self, short_id: &str) -> Result<Option<Snippet>, BackendError> {
+            let snippets = self.snippets.lock().unwrap();
+            Ok(snippets.iter().find(|s| s.short_id == short_id).map(MemoryStore::copy))
+        }
+
+        fn create_snippet(
+            &self,
+            name: &str,
+            content: &str,
+            language: Option<&str>,
+            tags: &[String],
+        ) -> Result<Snippet, BackendError> {
+            self.record("create")?;
+            let mut snippets = self.snippets.lock().unwrap();
+            let id = snippets.iter().map(|s| s.id).max().unwrap_or(0) + 1;
+            let snippet = Snippet {
+                id,
+                short_id: format!("id{}", id),
+                content: content.to_string(),
+                name: name.to_string(),
+                language: language.map(str::to_string),
+                tags: tags.to_vec(),
+                view_count: 0,
+                created_at: Some(id),
+                updated_at: id,
+                encoding: Default::default(),
+                url: None,
+                password_hash: None,
+            };
+            snippets.push(MemoryStore::copy(&snippet));
+            Ok(snippet)
+        }
+
+        fn update_snippet(
+            &self,
+            short_id: &str,
+            name: &str,
+            content: &str,
+            language: Option<&str>,
+            tags: &[String],
+        ) -> Result<Option<Snippet>, BackendError> {
+            self.record("update")?;
+            let mut snippets = self.snippets.lock().unwrap();
+            Ok(snippets.iter_mut().find(|s| s.short_id == short_id).map(|s| {
+                s.name = name.to_string();
+                s.content = content.to_string();
+                s.language = language.map(str::to_string);
+                s.tags = tags.to_vec();
+                MemoryStore::copy(s)
+            }))
+        }
+
+        fn rename_snippet(&self, short_id: &str, name: &str) -> Result<Option<Snippet>, BackendError> {
+            self.record("rename")?;
+            let mut snippets = self.snippets.lock().unwrap();
+            Ok(snippets.iter_mut().find(|s| s.short_id == short_id).map(|s| {
+                s.name = name.to_string();
+                MemoryStore::copy(s)
+            }))
+        }
+
+        fn delete_snippet(&self, short_id: &str) -> Result<bool, BackendError> {
+            self.record("delete")?;
+            let mut snippets = self.snippets.lock().unwrap();
+            let before = snippets.len();
+            snippets.retain(|s| s.short_id != short_id);
+            Ok(snippets.len() < before)
+        }
+    }
+
+    fn test_app(backend: &dyn SnippetStore) -> App {
+        App::new(backend.list_snippet_metas().unwrap(), false, None, None, Duration::from_secs(2))
+    }
+
+    fn visible_names(app: &App) -> Vec<&str> {
+        match &app.filtered_indices {
+            Some(indices) => indices.iter().map(|&i| app.snippets[i].name.as_str()).collect(),
+            None => app.snippets.iter().map(|s| s.name.as_str()).collect(),
+        }
+    }
+
+    #[test]
+    fn content_search_covers_snippets_never_viewed() {
+        let backend = LocalBackend::from_db(crate::db::init_db_in_memory().unwrap());
+        backend.create_snippet("a.txt", "nothing here", None, &[]).unwrap();
+        backend.create_snippet("b.txt", "the needle is here", None, &[]).unwrap();
+        let mut app = test_app(&backend);
+        assert!(app.contents.is_empty());
+
+        app.start_search();
+        app.search_query = "needle".to_string();
+        app.update_search_filter();
+        assert!(visible_names(&app).is_empty());
+
+        app.toggle_search_content(&backend);
+        assert_eq!(visible_names(&app), ["b.txt"]);
+    }
+
+    #[test]
+    fn syntect_colors_map_to_terminal_colors() {
+        use syntect::highlighting::Color as SyntectColor;
+
+        let color = |r, g, b, a| to_ratatui_color(SyntectColor { r, g, b, a });
+        // ANSI themes: a palette index, or the terminal's own default
+        assert_eq!(color(3, 0, 0, 0), Color::Indexed(3));
+        assert_eq!(color(255, 0, 0, 0), Color::Indexed(255));
+        assert_eq!(color(0, 0, 0, 1), Color::Reset);
+        assert_eq!(color(9, 9, 9, 1), Color::Reset);
+        // Everything else is RGB, whatever its alpha
+        assert_eq!(color(0x12, 0x34, 0x56, 0xff), Color::Rgb(0x12, 0x34, 0x56));
+        assert_eq!(color(0x12, 0x34, 0x56, 2), Color::Rgb(0x12, 0x34, 0x56));
+        assert_eq!(color(0, 0, 0, 0xff), Color::Rgb(0, 0, 0));
+    }
+
+    fn status(app: &App) -> &str {
+        app.status_message.as_ref().map_or("", |(message, _, _)| message.as_str())
+    }
+
+    #[test]
+    fn deleted_snippets_come_back_with_undo() {
+        let store = MemoryStore::with(&["a.txt", "b.txt"]);
+        let mut app = test_app(&store);
+        app.select_short_id("id1");
+
+        app.delete_selected(&store);
+        assert_eq!(visible_names(&app), ["b.txt"]);
+        assert_eq!(store.count_snippets().unwrap(), 1);
+        assert_eq!(status(&app), "Deleted (u to undo)");
+
+        app.undo_delete(&store);
+        assert_eq!(status(&app), "Restored a.txt as id3");
+        assert_eq!(app.selected_snippet().map(|s| s.short_id.as_str()), Some("id3"));
+        assert_eq!(store.get_snippet("id3").unwrap().unwrap().content, "content of a.txt");
+        app.undo_delete(&store);
+        assert_eq!(status(&app), "Nothing to undo");
+        assert_eq!(*store.calls.lock().unwrap(), ["delete", "create"]);
+    }
+
+    #[test]
+    fn edits_that_only_rename_skip_resending_the_content() {
+        let store = MemoryStore::with(&["a.txt"]);
+        let mut app = test_app(&store);
+        app.select_short_id("id1");
+        app.contents.insert("id1".to_string(), store.get_snippet("id1").unwrap().unwrap().content);
+
+        app.start_edit();
+        app.create_name = "renamed.txt".to_string();
+        app.save_edit(&store);
+        assert_eq!(visible_names(&app), ["renamed.txt"]);
+
+        app.start_edit();
+        app.create_content.set_text("new content".to_string());
+        app.save_edit(&store);
+        assert_eq!(app.contents["id1"], "new content");
+        assert_eq!(store.get_snippet("id1").unwrap().unwrap().content, "new content");
+        assert_eq!(*store.calls.lock().unwrap(), ["rename", "update"]);
+    }
+
+    #[test]
+    fn refreshing_an_unreachable_store_marks_it_disconnected() {
+        let store = MemoryStore::with(&["a.txt"]);
+        let mut app = test_app(&store);
+        let store = MemoryStore { offline: true, ..store };
+        app.refresh(&store);
+        assert_eq!(app.disconnected.as_deref(), Some("Network error: offline"));
+        assert_eq!(visible_names(&app), ["a.txt"]);
+
+        let store = MemoryStore { offline: false, ..store };
+        store.create_snippet("b.txt", "", None, &[]).unwrap();
+        app.refresh(&store);
+        assert_eq!(app.disconnected, None);
+        assert_eq!(visible_names(&app).len(), 2);
+    }
+}